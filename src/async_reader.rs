@@ -0,0 +1,155 @@
+use std::fs::File;
+use std::io::{self, Cursor};
+use std::ops::Bound;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::error::{Result, SstError};
+use crate::format::{Footer, HEADER_SIZE};
+use crate::reader::{RawValue, SstReader, read_exact_at};
+
+/// A reader for async code, whose lookups read the file without blocking the executor.
+///
+/// Opening reads the file's header and metadata (the bloom filter, index, properties
+/// and footer), which the lookups then use exactly as `SstReader` does. Each lookup
+/// reads only the data block it needs, along with a blob value it points to. Reads are
+/// positioned, like `SstReader`'s, and run on tokio's blocking thread pool just as
+/// `tokio::fs` runs its own, so concurrent lookups through a shared reader don't
+/// contend for a file cursor.
+///
+/// Only files using the default `BytewiseComparator` can be opened.
+pub struct AsyncSstReader {
+    file: Arc<File>,
+    len: u64,
+    // A reader over the file's header and metadata, without its data blocks
+    meta: SstReader,
+}
+
+impl AsyncSstReader {
+    /// Opens an SST file and loads its index.
+    pub async fn open(path: &Path) -> Result<Self> {
+        let file = tokio::fs::File::open(path).await?;
+        let len = file.metadata().await?.len();
+        let file = Arc::new(file.into_std().await);
+
+        // The footer's length depends on the first key it holds, so read a guess at it
+        // from the end of the file, and twice as much each time that falls short
+        let mut guess = len.min(4096);
+        let footer = loop {
+            let tail = read_at(&file, len, len - guess, guess).await?;
+            match Footer::read_from(&mut Cursor::new(&tail)) {
+                Err(SstError::UnexpectedEof) if guess < len => guess = len.min(guess * 2),
+                footer => break footer?,
+            }
+        };
+        let index_buf = read_at(&file, len, footer.index_offset, footer.index_size).await?;
+        let start = SstReader::metadata_start(&footer, &index_buf)?;
+        let header = read_at(&file, len, 0, len.min(HEADER_SIZE)).await?;
+        let tail = read_at(&file, len, start, len.saturating_sub(start)).await?;
+        let meta = SstReader::from_tail(header, tail, start, len)?;
+        Ok(AsyncSstReader { file, len, meta })
+    }
+
+    /// Searches for a key and returns the corresponding value.
+    /// Keys recorded as deleted are reported as missing, just like with `SstReader::get`.
+    pub async fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let meta = &self.meta;
+        if !meta.may_contain(key) {
+            return Ok(None);
+        }
+        let mut block_idx = meta.seek_block(Bound::Included(key))?;
+        // Past the last block, every key in the file is less than `key`
+        let Some(first_key) = meta.index_entry(block_idx)?.map(|entry| entry.first_key) else {
+            return Ok(None);
+        };
+        if first_key.is_some_and(|first_key| meta.comparator().compare(key, first_key).is_lt()) {
+            return Ok(None);
+        }
+
+        // Within a span of a sparse index, read the blocks in turn until one ends at or after the key
+        let span_end = meta.span_end(block_idx)?;
+        loop {
+            let Some(info) = meta.index_entry(block_idx)? else {
+                return Ok(None);
+            };
+            let raw_block = read_at(&self.file, self.len, info.block_offset, info.block_size).await?;
+            let block = meta.decode_block(&raw_block)?;
+            if block_idx + 1 < span_end
+                && meta.comparator().compare(key, &SstReader::last_key_in_block(&block, meta.version())?).is_gt()
+            {
+                block_idx += 1;
+                continue;
+            }
+            return match meta.search_in_block(&block, key)? {
+                Some(RawValue::Inline(value)) => Ok(Some(value.to_vec())),
+                Some(RawValue::Blob { offset, len }) => Ok(Some(read_at(&self.file, self.len, offset, len).await?)),
+                Some(RawValue::Tombstone) | None => Ok(None),
+            };
+        }
+    }
+}
+
+// Reads `len` bytes at `offset` from the file, which is `file_len` bytes long, on the
+// blocking thread pool. As with `SstReader`, a range past the end of the file is
+// rejected before a buffer is allocated for it.
+async fn read_at(file: &Arc<File>, file_len: u64, offset: u64, len: u64) -> Result<Vec<u8>> {
+    if offset.checked_add(len).is_none_or(|end| end > file_len) {
+        return Err(SstError::UnexpectedEof);
+    }
+    let len = usize::try_from(len)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Read is too large for this platform"))?;
+    let file = Arc::clone(file);
+    tokio::task::spawn_blocking(move || {
+        let mut buf = vec![0; len];
+        read_exact_at(&file, &mut buf, offset)?;
+        Ok(buf)
+    })
+    .await
+    .map_err(io::Error::other)?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempPath;
+    use crate::writer::SstWriterBuilder;
+
+    fn key(i: u32) -> Vec<u8> {
+        format!("key{:05}", i).into_bytes()
+    }
+
+    #[tokio::test]
+    async fn reads_what_the_sync_reader_reads() {
+        for (name, options) in [
+            ("flat", SstWriterBuilder::new().block_size(256)),
+            ("partitioned", SstWriterBuilder::new().block_size(256).index_partition_size(256).bloom_bits_per_key(0)),
+            ("sparse", SstWriterBuilder::new().block_size(256).index_sample_interval(4)),
+            ("blobs", SstWriterBuilder::new().block_size(256).blob_threshold(8)),
+        ] {
+            let path = TempPath::new(&format!("async-{}.sst", name));
+            let mut writer = options.build(&path).unwrap();
+            for i in (0..2000).step_by(2) {
+                writer.add(&key(i), &key(i)).unwrap();
+            }
+            writer.delete(&key(2000)).unwrap();
+            writer.finish().unwrap();
+
+            let sync = SstReader::open(&path).unwrap();
+            let reader = AsyncSstReader::open(&path).await.unwrap();
+            for i in 0..2002 {
+                assert_eq!(reader.get(&key(i)).await.unwrap(), sync.get(&key(i)).unwrap(), "{} key {}", name, i);
+            }
+            assert_eq!(reader.get(&key(1000)).await.unwrap(), Some(key(1000)));
+            assert_eq!(reader.get(&key(2000)).await.unwrap(), None);
+            assert_eq!(reader.get(b"a").await.unwrap(), None);
+            assert_eq!(reader.get(b"z").await.unwrap(), None);
+        }
+    }
+
+    #[tokio::test]
+    async fn files_too_small_for_a_footer_are_an_error() {
+        let path = TempPath::new("async-tiny.sst");
+        std::fs::write(&path, [0xAB; 10]).unwrap();
+        assert!(matches!(AsyncSstReader::open(&path).await, Err(SstError::UnexpectedEof)));
+    }
+}
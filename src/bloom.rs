@@ -0,0 +1,117 @@
+// A LevelDB-style bloom filter over all keys in an SST
+// Format: [filter_bits...][num_probes: u8]
+
+use std::path::{Path, PathBuf};
+
+use crate::error::{Result, SstError};
+
+// Marks the start of a bloom sidecar file
+const SIDECAR_MAGIC: [u8; 8] = *b"SSTBLOOM";
+
+// Hashes a key for the bloom filter (the same murmur-like hash LevelDB uses)
+pub(crate) fn hash(data: &[u8]) -> u32 {
+    const SEED: u32 = 0xbc9f1d34;
+    const M: u32 = 0xc6a4a793;
+
+    let mut h = SEED ^ (data.len() as u32).wrapping_mul(M);
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        h = h.wrapping_add(u32::from_le_bytes(chunk.try_into().unwrap()));
+        h = h.wrapping_mul(M);
+        h ^= h >> 16;
+    }
+
+    let rest = chunks.remainder();
+    if rest.len() >= 3 {
+        h = h.wrapping_add((rest[2] as u32) << 16);
+    }
+    if rest.len() >= 2 {
+        h = h.wrapping_add((rest[1] as u32) << 8);
+    }
+    if !rest.is_empty() {
+        h = h.wrapping_add(rest[0] as u32);
+        h = h.wrapping_mul(M);
+        h ^= h >> 24;
+    }
+    h
+}
+
+// Builds a filter from the hashes of every key, using `bits_per_key` bits per key
+pub(crate) fn build(key_hashes: &[u32], bits_per_key: usize) -> Vec<u8> {
+    // ln(2) * bits_per_key probes minimises the false positive rate
+    let num_probes = ((bits_per_key as f64 * 0.69) as u8).clamp(1, 30);
+
+    // Use a minimum filter size to keep the false positive rate low for tiny files
+    let num_bits = (key_hashes.len() * bits_per_key).max(64);
+    let num_bytes = num_bits.div_ceil(8);
+    let num_bits = num_bytes * 8;
+
+    let mut filter = vec![0u8; num_bytes + 1];
+    for &key_hash in key_hashes {
+        // Double hashing generates the probe sequence from a single hash
+        let mut h = key_hash;
+        let delta = h.rotate_right(17);
+        for _ in 0..num_probes {
+            let bit = h as usize % num_bits;
+            filter[bit / 8] |= 1 << (bit % 8);
+            h = h.wrapping_add(delta);
+        }
+    }
+    filter[num_bytes] = num_probes;
+    filter
+}
+
+// Returns false if the key is definitely absent, true if it may be present
+pub(crate) fn may_contain(filter: &[u8], key: &[u8]) -> bool {
+    let Some((&num_probes, bits)) = filter.split_last() else {
+        return true;
+    };
+    if bits.is_empty() {
+        return true;
+    }
+    let num_bits = bits.len() * 8;
+
+    let mut h = hash(key);
+    let delta = h.rotate_right(17);
+    for _ in 0..num_probes {
+        let bit = h as usize % num_bits;
+        if bits[bit / 8] & (1 << (bit % 8)) == 0 {
+            return false;
+        }
+        h = h.wrapping_add(delta);
+    }
+    true
+}
+/// Returns the path of the bloom sidecar written alongside the SST at `path` by writers
+/// with `SstWriterBuilder::bloom_sidecar` enabled: the SST's path with `.bloom` appended.
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    let mut sidecar = path.as_os_str().to_owned();
+    sidecar.push(".bloom");
+    PathBuf::from(sidecar)
+}
+
+// A sidecar holds a copy of an SST's bloom filter, tied to the SST by its file checksum
+// so that a sidecar left over from another file is never used to rule keys out.
+// Format: [magic: 8 bytes][file_checksum: u64][filter...], the filter empty if the SST has none
+pub(crate) fn sidecar_to_bytes(filter: &[u8], file_checksum: u64) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(16 + filter.len());
+    bytes.extend_from_slice(&SIDECAR_MAGIC);
+    bytes.extend_from_slice(&file_checksum.to_le_bytes());
+    bytes.extend_from_slice(filter);
+    bytes
+}
+
+// Returns the filter held in a sidecar, or None if the SST has none, failing if the
+// sidecar wasn't written for the SST with this file checksum
+pub(crate) fn sidecar_from_bytes(buf: &[u8], file_checksum: u64) -> Result<Option<Vec<u8>>> {
+    if buf.len() < 16 {
+        return Err(SstError::CorruptBloomSidecar(format!("{} bytes is too short for the header", buf.len())));
+    }
+    if buf[..8] != SIDECAR_MAGIC {
+        return Err(SstError::CorruptBloomSidecar("bad magic number".to_string()));
+    }
+    if buf[8..16] != file_checksum.to_le_bytes() {
+        return Err(SstError::CorruptBloomSidecar("sidecar was written for a different SST".to_string()));
+    }
+    Ok(Some(buf[16..].to_vec()).filter(|filter| !filter.is_empty()))
+}
@@ -0,0 +1,205 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// Hit and miss counts for a block cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+// Identifies a cached block: the id of the reader's file and the block's offset in it
+type BlockKey = (u64, u64);
+
+// A least-recently-used cache of decoded data blocks. Each block is charged against the
+// capacity, by its size in bytes or as a single unit depending on the cache.
+struct LruCache {
+    capacity: usize,
+    // Total charge of the cached blocks
+    used: usize,
+    // Incremented on every access to order entries by recency
+    tick: u64,
+    // Each block with its last-access tick and charge
+    entries: HashMap<BlockKey, (Arc<Vec<u8>>, u64, usize)>,
+    // Maps each entry's last-access tick to its key, oldest first
+    recency: BTreeMap<u64, BlockKey>,
+    stats: CacheStats,
+}
+
+impl LruCache {
+    fn new(capacity: usize) -> Self {
+        LruCache {
+            capacity,
+            used: 0,
+            tick: 0,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            stats: CacheStats::default(),
+        }
+    }
+
+    fn get(&mut self, key: BlockKey) -> Option<Arc<Vec<u8>>> {
+        self.tick += 1;
+        let Some((block, last_access, _)) = self.entries.get_mut(&key) else {
+            self.stats.misses += 1;
+            return None;
+        };
+        self.recency.remove(last_access);
+        self.recency.insert(self.tick, key);
+        *last_access = self.tick;
+        self.stats.hits += 1;
+        Some(block.clone())
+    }
+
+    fn insert(&mut self, key: BlockKey, block: Arc<Vec<u8>>, charge: usize) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.tick += 1;
+        if let Some((_, last_access, old_charge)) = self.entries.insert(key, (block, self.tick, charge)) {
+            self.recency.remove(&last_access);
+            self.used -= old_charge;
+        }
+        self.recency.insert(self.tick, key);
+        self.used += charge;
+
+        // Evict the least recently used blocks until we're back within capacity
+        while self.used > self.capacity {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            if let Some((_, _, charge)) = self.entries.remove(&oldest) {
+                self.used -= charge;
+            }
+        }
+    }
+}
+
+/// A thread-safe LRU cache of decoded data blocks that can be shared by many readers,
+/// capping the memory they spend on cached blocks together.
+///
+/// Blocks are keyed by the file they came from and their offset in it, so readers over
+/// different files never see each other's blocks. Blocks of a dropped reader stay cached
+/// until they are evicted.
+pub struct BlockCache {
+    inner: Mutex<LruCache>,
+    // Whether blocks are charged by their size in bytes, rather than one unit each
+    charge_bytes: bool,
+    next_file_id: AtomicU64,
+}
+
+impl BlockCache {
+    /// Creates a cache holding up to `capacity_bytes` bytes of decoded blocks. Pass it
+    /// to `SstReader::with_shared_block_cache` for every reader that should share it.
+    pub fn new(capacity_bytes: usize) -> Self {
+        Self::with_charge(capacity_bytes, true)
+    }
+
+    // Creates a cache holding up to `capacity` blocks, whatever their size
+    pub(crate) fn with_block_capacity(capacity: usize) -> Self {
+        Self::with_charge(capacity, false)
+    }
+
+    fn with_charge(capacity: usize, charge_bytes: bool) -> Self {
+        BlockCache {
+            inner: Mutex::new(LruCache::new(capacity)),
+            charge_bytes,
+            next_file_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the hit and miss counts of every reader using the cache.
+    pub fn stats(&self) -> CacheStats {
+        self.lock().stats
+    }
+
+    /// Returns the total size in bytes of the blocks currently cached.
+    pub fn size(&self) -> usize {
+        self.lock().entries.values().map(|(block, _, _)| block.len()).sum()
+    }
+
+    // Returns a new id distinguishing a reader's blocks from those of other readers
+    pub(crate) fn new_file_id(&self) -> u64 {
+        self.next_file_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    pub(crate) fn get(&self, file_id: u64, block_offset: u64) -> Option<Arc<Vec<u8>>> {
+        self.lock().get((file_id, block_offset))
+    }
+
+    pub(crate) fn insert(&self, file_id: u64, block_offset: u64, block: Arc<Vec<u8>>) {
+        let charge = if self.charge_bytes { block.len() } else { 1 };
+        self.lock().insert((file_id, block_offset), block, charge);
+    }
+
+    // A panic while the lock was held can't leave the cache inconsistent in a way
+    // that matters, so a poisoned lock is recovered
+    fn lock(&self) -> MutexGuard<'_, LruCache> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::SstReader;
+    use crate::test_util::{FRUIT, fruit};
+    use crate::writer::SstWriterBuilder;
+
+    #[test]
+    fn a_second_get_into_a_cached_block_reads_nothing() {
+        let reader = fruit().with_block_cache(8);
+        assert_eq!(reader.num_blocks(), 1);
+        assert_eq!(reader.cache_stats(), Some(CacheStats { hits: 0, misses: 0 }));
+
+        let (apple, banana) = (FRUIT[0], FRUIT[1]);
+        assert_eq!(reader.get(apple.0.as_bytes()).unwrap().as_deref(), Some(apple.1.as_bytes()));
+        assert_eq!(reader.cache_stats(), Some(CacheStats { hits: 0, misses: 1 }));
+        // The same block, so it's served from the cache rather than the file
+        assert_eq!(reader.get(banana.0.as_bytes()).unwrap().as_deref(), Some(banana.1.as_bytes()));
+        assert_eq!(reader.cache_stats(), Some(CacheStats { hits: 1, misses: 1 }));
+        assert_eq!(reader.get(apple.0.as_bytes()).unwrap().as_deref(), Some(apple.1.as_bytes()));
+        assert_eq!(reader.cache_stats(), Some(CacheStats { hits: 2, misses: 1 }));
+
+        assert_eq!(fruit().cache_stats(), None);
+    }
+
+    // Writes `n` keys starting with `prefix`, each with a 100-byte value, to an in-memory SST
+    // of 1 KiB blocks
+    fn blocks_of(prefix: &str, n: u32) -> SstReader {
+        let mut bytes = Vec::new();
+        let mut writer = SstWriterBuilder::new().block_size(1024).build_with_writer(&mut bytes);
+        for i in 0..n {
+            writer.add(format!("{}{:05}", prefix, i).as_bytes(), &[i as u8; 100]).unwrap();
+        }
+        writer.finish().unwrap();
+        SstReader::from_bytes(bytes).unwrap()
+    }
+
+    #[test]
+    fn readers_sharing_a_cache_stay_within_its_byte_budget() {
+        let cache = Arc::new(BlockCache::new(8 * 1024));
+        let a = blocks_of("a", 1000).with_shared_block_cache(Arc::clone(&cache));
+        let b = blocks_of("b", 1000).with_shared_block_cache(Arc::clone(&cache));
+        assert!(a.num_blocks() > 50, "{} blocks", a.num_blocks());
+
+        for i in 0..1000u32 {
+            // Both files hold the same values at the same offsets, so a block served to the
+            // wrong reader would go unnoticed; the keys tell them apart
+            let (key_a, key_b) = (format!("a{:05}", i), format!("b{:05}", i));
+            assert_eq!(a.get(key_a.as_bytes()).unwrap(), Some(vec![i as u8; 100]));
+            assert_eq!(b.get(key_b.as_bytes()).unwrap(), Some(vec![i as u8; 100]));
+            assert_eq!(a.get(key_b.as_bytes()).unwrap(), None);
+            assert!(cache.size() <= 8 * 1024, "{} bytes cached", cache.size());
+        }
+        assert!(cache.size() > 4 * 1024, "{} bytes cached", cache.size());
+
+        // The counts cover both readers
+        let stats = cache.stats();
+        assert_eq!(a.cache_stats(), Some(stats));
+        assert_eq!(b.cache_stats(), Some(stats));
+        assert_eq!(stats.misses, (a.num_blocks() + b.num_blocks()) as u64);
+        assert_eq!(stats.hits, 2000 - stats.misses);
+    }
+}
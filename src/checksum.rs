@@ -0,0 +1,350 @@
+use crate::error::{Result, SstError};
+
+// Checksum type tags stored in the last byte of every data block
+const CHECKSUM_NONE: u8 = 0;
+const CHECKSUM_CRC32: u8 = 1;
+const CHECKSUM_XXHASH64: u8 = 2;
+
+/// Checksum stored in the trailer of each data block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Checksum {
+    /// Blocks are not checksummed.
+    #[default]
+    None,
+    /// A CRC32 (IEEE) of each block.
+    Crc32,
+    /// An xxHash64 of each block, which is much faster to compute on large blocks.
+    XxHash64,
+}
+
+// Builds the lookup table for a reflected CRC32 polynomial at compile time
+const fn crc32_table(polynomial: u32) -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ polynomial } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+const CRC32_TABLE: [u32; 256] = crc32_table(0xEDB88320);
+// The Castagnoli polynomial, used by LevelDB
+const CRC32C_TABLE: [u32; 256] = crc32_table(0x82F63B78);
+
+fn crc32_with_table(data: &[u8], table: &[u32; 256]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc = table[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+/// Computes the CRC32 (IEEE) checksum of `data`.
+pub fn crc32(data: &[u8]) -> u32 {
+    crc32_with_table(data, &CRC32_TABLE)
+}
+
+/// Computes the CRC32C (Castagnoli) checksum of `data`.
+pub fn crc32c(data: &[u8]) -> u32 {
+    crc32_with_table(data, &CRC32C_TABLE)
+}
+
+const PRIME64_1: u64 = 0x9E3779B185EBCA87;
+const PRIME64_2: u64 = 0xC2B2AE3D27D4EB4F;
+const PRIME64_3: u64 = 0x165667B19E3779F9;
+const PRIME64_4: u64 = 0x85EBCA77C2B2AE63;
+const PRIME64_5: u64 = 0x27D4EB2F165667C5;
+
+/// Computes the xxHash64 of `data` with a seed of 0.
+pub fn xxhash64(data: &[u8]) -> u64 {
+    let mut hasher = XxHash64::new();
+    hasher.update(data);
+    hasher.finish()
+}
+
+fn xxhash64_round(acc: u64, lane: u64) -> u64 {
+    acc.wrapping_add(lane.wrapping_mul(PRIME64_2))
+        .rotate_left(31)
+        .wrapping_mul(PRIME64_1)
+}
+
+fn read_lane(bytes: &[u8]) -> u64 {
+    u64::from_le_bytes(bytes[..8].try_into().unwrap())
+}
+
+// Computes an xxHash64 with a seed of 0 over data that arrives in pieces, giving the
+// same hash as `xxhash64` over the pieces joined together
+pub(crate) struct XxHash64 {
+    // Four independent accumulators consume 32-byte stripes
+    acc: [u64; 4],
+    // The start of a stripe that hasn't been filled yet
+    buffer: [u8; 32],
+    buffered: usize,
+    total_len: u64,
+}
+
+impl XxHash64 {
+    pub(crate) fn new() -> Self {
+        XxHash64 {
+            acc: [
+                PRIME64_1.wrapping_add(PRIME64_2),
+                PRIME64_2,
+                0,
+                PRIME64_1.wrapping_neg(),
+            ],
+            buffer: [0; 32],
+            buffered: 0,
+            total_len: 0,
+        }
+    }
+
+    pub(crate) fn update(&mut self, mut data: &[u8]) {
+        self.total_len += data.len() as u64;
+
+        // Complete a stripe left over from the previous update first
+        if self.buffered > 0 {
+            let len = (32 - self.buffered).min(data.len());
+            self.buffer[self.buffered..self.buffered + len].copy_from_slice(&data[..len]);
+            self.buffered += len;
+            data = &data[len..];
+            if self.buffered < 32 {
+                return;
+            }
+            let stripe = self.buffer;
+            self.consume_stripe(&stripe);
+            self.buffered = 0;
+        }
+
+        while let Some((stripe, rest)) = data.split_first_chunk::<32>() {
+            self.consume_stripe(stripe);
+            data = rest;
+        }
+        self.buffer[..data.len()].copy_from_slice(data);
+        self.buffered = data.len();
+    }
+
+    fn consume_stripe(&mut self, stripe: &[u8; 32]) {
+        for (i, lane) in self.acc.iter_mut().enumerate() {
+            *lane = xxhash64_round(*lane, read_lane(&stripe[i * 8..]));
+        }
+    }
+
+    pub(crate) fn finish(&self) -> u64 {
+        let mut hash = if self.total_len >= 32 {
+            let mut hash = self.acc[0]
+                .rotate_left(1)
+                .wrapping_add(self.acc[1].rotate_left(7))
+                .wrapping_add(self.acc[2].rotate_left(12))
+                .wrapping_add(self.acc[3].rotate_left(18));
+            for lane in self.acc {
+                hash = (hash ^ xxhash64_round(0, lane)).wrapping_mul(PRIME64_1).wrapping_add(PRIME64_4);
+            }
+            hash
+        } else {
+            PRIME64_5
+        };
+        hash = hash.wrapping_add(self.total_len);
+
+        // Mix in the remaining 0 to 31 bytes
+        let mut rest = &self.buffer[..self.buffered];
+        while let Some((word, tail)) = rest.split_at_checked(8) {
+            hash ^= xxhash64_round(0, read_lane(word));
+            hash = hash.rotate_left(27).wrapping_mul(PRIME64_1).wrapping_add(PRIME64_4);
+            rest = tail;
+        }
+        if let Some((word, tail)) = rest.split_at_checked(4) {
+            hash ^= (u32::from_le_bytes(word.try_into().unwrap()) as u64).wrapping_mul(PRIME64_1);
+            hash = hash.rotate_left(23).wrapping_mul(PRIME64_2).wrapping_add(PRIME64_3);
+            rest = tail;
+        }
+        for &byte in rest {
+            hash ^= (byte as u64).wrapping_mul(PRIME64_5);
+            hash = hash.rotate_left(11).wrapping_mul(PRIME64_1);
+        }
+
+        // Final avalanche
+        hash ^= hash >> 33;
+        hash = hash.wrapping_mul(PRIME64_2);
+        hash ^= hash >> 29;
+        hash = hash.wrapping_mul(PRIME64_3);
+        hash ^= hash >> 32;
+        hash
+    }
+}
+
+// Length of the trailer `append_trailer` adds for `checksum`
+pub(crate) fn trailer_len(checksum: Checksum) -> usize {
+    match checksum {
+        Checksum::None => 1,
+        Checksum::Crc32 => 5,
+        Checksum::XxHash64 => 9,
+    }
+}
+
+// Appends the block trailer, including a checksum of the payload unless disabled
+// Format: [payload][checksum: u32 for CRC32, u64 for xxHash64, absent for none][checksum_type: u8]
+pub(crate) fn append_trailer(block: &mut Vec<u8>, checksum: Checksum) {
+    match checksum {
+        Checksum::None => block.push(CHECKSUM_NONE),
+        Checksum::Crc32 => {
+            let crc = crc32(block);
+            block.extend_from_slice(&crc.to_le_bytes());
+            block.push(CHECKSUM_CRC32);
+        }
+        Checksum::XxHash64 => {
+            let hash = xxhash64(block);
+            block.extend_from_slice(&hash.to_le_bytes());
+            block.push(CHECKSUM_XXHASH64);
+        }
+    }
+}
+
+// Strips the block trailer, verifying the checksum if one was written
+pub(crate) fn verify_trailer(block: &[u8]) -> Result<&[u8]> {
+    let Some((&checksum_type, rest)) = block.split_last() else {
+        return Err(SstError::CorruptBlock("missing block trailer".to_string()));
+    };
+
+    match checksum_type {
+        CHECKSUM_NONE => Ok(rest),
+        CHECKSUM_CRC32 if rest.len() >= 4 => {
+            let (payload, stored) = rest.split_at(rest.len() - 4);
+            let stored = u32::from_le_bytes(stored.try_into().unwrap());
+            if crc32(payload) != stored {
+                return Err(SstError::ChecksumMismatch);
+            }
+            Ok(payload)
+        }
+        CHECKSUM_XXHASH64 if rest.len() >= 8 => {
+            let (payload, stored) = rest.split_at(rest.len() - 8);
+            let stored = u64::from_le_bytes(stored.try_into().unwrap());
+            if xxhash64(payload) != stored {
+                return Err(SstError::ChecksumMismatch);
+            }
+            Ok(payload)
+        }
+        _ => Err(SstError::CorruptBlock(format!("invalid checksum type {}", checksum_type))),
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::SstReader;
+    use crate::writer::SstWriterBuilder;
+
+    // 0, 1, 2, ..., 255, 0, 1, ... for `len` bytes
+    fn counting(len: usize) -> Vec<u8> {
+        (0..=255).cycle().take(len).collect()
+    }
+
+    #[test]
+    fn crcs_match_the_check_values() {
+        // The standard check values of CRC-32 and CRC-32C, and reference values for longer input
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        assert_eq!(crc32c(b"123456789"), 0xE3069283);
+        assert_eq!((crc32(b""), crc32c(b"")), (0, 0));
+        assert_eq!(crc32(&counting(1000)), 0x74E3FB41);
+        assert_eq!(crc32c(&counting(1000)), 0x1A318E30);
+    }
+
+    #[test]
+    fn xxhash64_matches_the_reference() {
+        // From the reference implementation, with a seed of 0
+        let cases: [(&[u8], u64); 6] = [
+            (b"", 0xEF46DB3751D8E999),
+            (b"a", 0xD24EC4F1A98C6E5B),
+            (b"abc", 0x44BC2CF5AD770999),
+            (b"Nobody inspects the spammish repetition", 0xFBCEA83C8A378BF1),
+            (&counting(33), 0x0C535D1ACAFB8EAD),
+            (&counting(1000), 0x6EF436B00EBA4078),
+        ];
+        for (data, expected) in cases {
+            assert_eq!(xxhash64(data), expected, "{} bytes", data.len());
+        }
+    }
+
+    #[test]
+    fn xxhash64_of_pieces_matches_the_whole() {
+        let data = counting(1000);
+        for piece_len in [1, 7, 31, 32, 33, 100] {
+            let mut hasher = XxHash64::new();
+            for piece in data.chunks(piece_len) {
+                hasher.update(piece);
+            }
+            assert_eq!(hasher.finish(), xxhash64(&data), "pieces of {}", piece_len);
+        }
+    }
+
+    #[test]
+    fn blocks_verify_with_every_checksum() {
+        for checksum in [Checksum::None, Checksum::Crc32, Checksum::XxHash64] {
+            let mut bytes = Vec::new();
+            let mut writer = SstWriterBuilder::new().checksum(checksum).build_with_writer(&mut bytes);
+            for i in 0..1000 {
+                writer.add(format!("key{:05}", i).as_bytes(), &counting(i % 100)).unwrap();
+            }
+            writer.finish().unwrap();
+            let reader = SstReader::from_bytes(bytes).unwrap();
+            reader.verify().unwrap();
+            assert_eq!(reader.iter().count(), 1000, "{:?}", checksum);
+            assert_eq!(reader.get(b"key00999").unwrap(), Some(counting(99)), "{:?}", checksum);
+
+            // Each block's trailer ends with the tag of the checksum it was written with
+            let info = reader.index_entry(0).unwrap().unwrap();
+            let block = reader.read_raw_block(info.block_offset, info.block_size).unwrap();
+            let tag = match checksum {
+                Checksum::None => CHECKSUM_NONE,
+                Checksum::Crc32 => CHECKSUM_CRC32,
+                Checksum::XxHash64 => CHECKSUM_XXHASH64,
+            };
+            assert_eq!(block.last(), Some(&tag));
+        }
+    }
+
+    #[test]
+    fn corrupt_payloads_fail_their_checksum() {
+        for checksum in [Checksum::Crc32, Checksum::XxHash64] {
+            let mut block = counting(100);
+            append_trailer(&mut block, checksum);
+            assert_eq!(verify_trailer(&block).unwrap(), &counting(100)[..]);
+            for i in 0..100 {
+                let mut corrupt = block.clone();
+                corrupt[i] ^= 0x10;
+                let result = verify_trailer(&corrupt);
+                assert!(matches!(result, Err(SstError::ChecksumMismatch)), "{:?} byte {}", checksum, i);
+            }
+            // A changed checksum fails just like a changed payload, and a changed tag names no checksum
+            for i in 100..block.len() {
+                let mut corrupt = block.clone();
+                corrupt[i] ^= 0x10;
+                assert!(verify_trailer(&corrupt).is_err(), "{:?} byte {}", checksum, i);
+            }
+        }
+    }
+
+    #[test]
+    fn a_flipped_byte_anywhere_in_the_file_is_detected() {
+        let mut bytes = Vec::new();
+        let options = SstWriterBuilder::new().block_size(256).checksum(Checksum::Crc32);
+        let mut writer = options.build_with_writer(&mut bytes);
+        for i in 0..100 {
+            writer.add(format!("key{:03}", i).as_bytes(), &counting(i)).unwrap();
+        }
+        writer.finish().unwrap();
+
+        for i in 0..bytes.len() {
+            let mut corrupt = bytes.clone();
+            corrupt[i] ^= 0x01;
+            let result = SstReader::from_bytes(corrupt).and_then(|reader| reader.verify_file_checksum());
+            assert!(result.is_err(), "byte {} of {}", i, bytes.len());
+        }
+        SstReader::from_bytes(bytes).unwrap().verify_file_checksum().unwrap();
+    }
+}
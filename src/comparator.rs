@@ -0,0 +1,171 @@
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::sync::Arc;
+
+/// Defines the order of the keys in an SST. Writers reject keys added out of this order,
+/// and readers rely on it to route lookups through the index and within blocks, so a
+/// file must be read with the comparator it was written with.
+///
+/// Files written with a comparator other than `BytewiseComparator` record its name, and
+/// readers refuse files whose comparator's name differs from their own.
+pub trait Comparator: Send + Sync {
+    /// Names the order. It must change whenever the order does.
+    fn name(&self) -> &str;
+
+    /// Compares two keys.
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering;
+
+    /// Returns a key `>= start` and `< limit`, given `start < limit`, for the index to
+    /// store in place of a block's last key; shorter keys make for a smaller index.
+    /// Returns `start` unless overridden.
+    fn separator(&self, start: &[u8], limit: &[u8]) -> Vec<u8> {
+        let _ = limit;
+        start.to_vec()
+    }
+
+    /// Returns the form of `key` that the bloom filter and the blocks' hash indexes hash,
+    /// which must be the same for any two keys that compare equal. Returns `key` unless
+    /// overridden, which suits any order where only identical keys compare equal.
+    fn normalize<'a>(&self, key: &'a [u8]) -> Cow<'a, [u8]> {
+        Cow::Borrowed(key)
+    }
+}
+
+impl fmt::Debug for dyn Comparator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Comparator").field(&self.name()).finish()
+    }
+}
+
+impl<C: Comparator + ?Sized> Comparator for Arc<C> {
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        (**self).compare(a, b)
+    }
+
+    fn separator(&self, start: &[u8], limit: &[u8]) -> Vec<u8> {
+        (**self).separator(start, limit)
+    }
+
+    fn normalize<'a>(&self, key: &'a [u8]) -> Cow<'a, [u8]> {
+        (**self).normalize(key)
+    }
+}
+
+/// Orders keys lexicographically by their bytes. This is the default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BytewiseComparator;
+
+impl Comparator for BytewiseComparator {
+    fn name(&self) -> &str {
+        "sst.BytewiseComparator"
+    }
+
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        a.cmp(b)
+    }
+
+    fn separator(&self, start: &[u8], limit: &[u8]) -> Vec<u8> {
+        shortest_separator(start, limit)
+    }
+}
+
+/// Orders internal keys, built by `internal_key` from a user key and a sequence number,
+/// by user key ascending and then by sequence number descending, so every version of a
+/// key is stored together, newest first. Files written with it hold many versions of
+/// each user key for multi-version concurrency control; `SstReader::get_at` reads the
+/// version visible at a snapshot.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InternalKeyComparator;
+
+impl Comparator for InternalKeyComparator {
+    fn name(&self) -> &str {
+        "sst.InternalKeyComparator"
+    }
+
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        let (a_user_key, a_sequence) = split_internal_key(a);
+        let (b_user_key, b_sequence) = split_internal_key(b);
+        // Sequence numbers are big-endian, so their bytes compare like the numbers
+        a_user_key.cmp(b_user_key).then_with(|| b_sequence.cmp(a_sequence))
+    }
+
+    fn separator(&self, start: &[u8], limit: &[u8]) -> Vec<u8> {
+        let (start_user_key, _) = split_internal_key(start);
+        let (limit_user_key, _) = split_internal_key(limit);
+        // A shortened user key with the largest sequence number sorts before every version
+        // of that user key, so it stays below `limit` while being above `start`
+        let separator = shortest_separator(start_user_key, limit_user_key);
+        if separator.as_slice() != start_user_key {
+            return internal_key(&separator, u64::MAX);
+        }
+        start.to_vec()
+    }
+}
+
+/// Orders keys lexicographically by their bytes with ASCII letters folded to lowercase,
+/// for keys that are human-readable identifiers: `Apple < banana < Cherry`. Keys that
+/// differ only in the case of ASCII letters are equal, so a file holds at most one of
+/// them, and looking up any of them finds it; the key is stored as it was added.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CaseInsensitiveAsciiComparator;
+
+impl Comparator for CaseInsensitiveAsciiComparator {
+    fn name(&self) -> &str {
+        "sst.CaseInsensitiveAsciiComparator"
+    }
+
+    fn compare(&self, a: &[u8], b: &[u8]) -> Ordering {
+        a.iter().map(u8::to_ascii_lowercase).cmp(b.iter().map(u8::to_ascii_lowercase))
+    }
+
+    fn normalize<'a>(&self, key: &'a [u8]) -> Cow<'a, [u8]> {
+        match key.iter().any(u8::is_ascii_uppercase) {
+            true => Cow::Owned(key.to_ascii_lowercase()),
+            false => Cow::Borrowed(key),
+        }
+    }
+}
+
+/// Builds the internal key of version `sequence` of `user_key`, for files ordered by
+/// `InternalKeyComparator`: the user key followed by the sequence number as 8 big-endian bytes.
+pub fn internal_key(user_key: &[u8], sequence: u64) -> Vec<u8> {
+    let mut key = Vec::with_capacity(user_key.len() + 8);
+    key.extend_from_slice(user_key);
+    key.extend_from_slice(&sequence.to_be_bytes());
+    key
+}
+
+/// Splits an internal key built by `internal_key` into its user key and sequence number,
+/// or returns None if it is too short to be one.
+pub fn parse_internal_key(key: &[u8]) -> Option<(&[u8], u64)> {
+    let (user_key, sequence) = key.split_last_chunk::<8>()?;
+    Some((user_key, u64::from_be_bytes(*sequence)))
+}
+
+// Splits an internal key into its user key and sequence bytes. A key too short to hold
+// a sequence number is treated as an empty user key, keeping the order total.
+fn split_internal_key(key: &[u8]) -> (&[u8], &[u8]) {
+    key.split_at(key.len().saturating_sub(8))
+}
+
+// Returns a short key that is >= `start` and < `limit`, like LevelDB's FindShortestSeparator.
+// If `start` and `limit` differ at some byte that can be incremented while staying
+// below `limit`, the separator is `start` cut off just after that incremented byte.
+// Otherwise `start` itself is returned.
+fn shortest_separator(start: &[u8], limit: &[u8]) -> Vec<u8> {
+    let shared = start.iter().zip(limit).take_while(|(a, b)| a == b).count();
+    if shared < start.len() && shared < limit.len() {
+        let byte = start[shared];
+        if byte < 0xFF && byte + 1 < limit[shared] {
+            let mut separator = start[..=shared].to_vec();
+            separator[shared] += 1;
+            return separator;
+        }
+    }
+    start.to_vec()
+}
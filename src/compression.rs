@@ -0,0 +1,249 @@
+use std::borrow::Cow;
+use std::io;
+
+use crate::error::{Result, SstError};
+use crate::lz4;
+
+// Compression flags stored in the first byte of every data block. They differ from
+// RocksDB's compression types (where Snappy is 1); any mapping between the two has
+// to translate them, so new algorithms take the next free value.
+const FLAG_NONE: u8 = 0;
+const FLAG_ZSTD: u8 = 1;
+const FLAG_LZ4: u8 = 2;
+const FLAG_SNAPPY: u8 = 3;
+
+/// Compression applied to each data block before it is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Compression {
+    /// Blocks are stored as-is.
+    #[default]
+    None,
+    /// Blocks are compressed with zstd at the given level, from 1 (fastest) to 22 (smallest),
+    /// or 0 for zstd's default. Needs the `zstd` cargo feature (on by default); builds
+    /// without it fail with `SstError::UnsupportedCompression` when writing or reading such blocks.
+    Zstd { level: i32 },
+    /// Blocks are compressed with LZ4, which decompresses much faster than zstd.
+    Lz4,
+    /// Blocks are compressed with Snappy, the default of LevelDB and RocksDB.
+    Snappy,
+}
+
+// Compresses a serialised block and prefixes it with its compression flag
+// Format: [compression_flag: u8][payload]
+// LZ4 payloads start with the uncompressed length: [uncompressed_len: u32][lz4 block]
+// Snappy payloads are in the Snappy raw format, which records the length itself
+// Zstd payloads are a single zstd frame
+pub(crate) fn compress_block(block: &[u8], compression: Compression) -> Result<Vec<u8>> {
+    match compression {
+        Compression::None => {
+            let mut bytes = Vec::with_capacity(block.len() + 1);
+            bytes.push(FLAG_NONE);
+            bytes.extend_from_slice(block);
+            Ok(bytes)
+        }
+        Compression::Zstd { level } => {
+            let mut bytes = vec![FLAG_ZSTD];
+            zstd_compress(block, level, &mut bytes)?;
+            Ok(bytes)
+        }
+        // Both formats record the block's length in 32 bits, so a larger block, which only
+        // a huge inline value can make, is stored uncompressed rather than truncated
+        Compression::Lz4 | Compression::Snappy if block.len() > u32::MAX as usize => {
+            compress_block(block, Compression::None)
+        }
+        Compression::Lz4 => {
+            let compressed = lz4::compress(block);
+            let mut bytes = Vec::with_capacity(compressed.len() + 5);
+            bytes.push(FLAG_LZ4);
+            bytes.extend_from_slice(&(block.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&compressed);
+            Ok(bytes)
+        }
+        Compression::Snappy => {
+            let mut bytes = vec![0; snap::raw::max_compress_len(block.len()) + 1];
+            bytes[0] = FLAG_SNAPPY;
+            let len = snap::raw::Encoder::new().compress(block, &mut bytes[1..]).map_err(io::Error::other)?;
+            bytes.truncate(len + 1);
+            Ok(bytes)
+        }
+    }
+}
+
+// Reads the compression flag and returns the decompressed block payload
+pub(crate) fn decompress_block(buf: &[u8]) -> Result<Cow<'_, [u8]>> {
+    let Some((&flag, payload)) = buf.split_first() else {
+        return Err(SstError::CorruptBlock("missing compression flag".to_string()));
+    };
+
+    match flag {
+        FLAG_NONE => Ok(Cow::Borrowed(payload)),
+        FLAG_ZSTD => Ok(Cow::Owned(zstd_decompress(payload)?)),
+        FLAG_LZ4 => {
+            let (len, compressed) = payload
+                .split_first_chunk::<4>()
+                .ok_or_else(|| SstError::CorruptBlock("LZ4 block is truncated".to_string()))?;
+            Ok(Cow::Owned(lz4::decompress(compressed, u32::from_le_bytes(*len) as usize)?))
+        }
+        FLAG_SNAPPY => Ok(Cow::Owned(snappy_decompress(payload)?)),
+        _ => Err(SstError::CorruptBlock(format!("invalid compression flag {}", flag))),
+    }
+}
+
+// Decompresses a block in the Snappy raw format. Any error decoding it means the block is corrupt.
+pub(crate) fn snappy_decompress(payload: &[u8]) -> Result<Vec<u8>> {
+    snap::raw::Decoder::new()
+        .decompress_vec(payload)
+        .map_err(|e| SstError::CorruptBlock(format!("invalid Snappy data: {}", e)))
+}
+
+// Appends `block` compressed as a zstd frame to `out`
+#[cfg(feature = "zstd")]
+fn zstd_compress(block: &[u8], level: i32, out: &mut Vec<u8>) -> Result<()> {
+    zstd::stream::copy_encode(block, out, level)?;
+    Ok(())
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd_compress(_block: &[u8], _level: i32, _out: &mut Vec<u8>) -> Result<()> {
+    Err(SstError::UnsupportedCompression("zstd"))
+}
+
+// Decompresses a zstd frame. Any error decoding it means the block is corrupt.
+#[cfg(feature = "zstd")]
+pub(crate) fn zstd_decompress(payload: &[u8]) -> Result<Vec<u8>> {
+    zstd::stream::decode_all(payload).map_err(|e| SstError::CorruptBlock(format!("invalid zstd frame: {}", e)))
+}
+
+#[cfg(not(feature = "zstd"))]
+pub(crate) fn zstd_decompress(_payload: &[u8]) -> Result<Vec<u8>> {
+    Err(SstError::UnsupportedCompression("zstd"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::SstReader;
+    use crate::writer::SstWriterBuilder;
+
+    // Text-like values, which compress well
+    fn value(i: u32) -> Vec<u8> {
+        format!("value {} of a block that repeats itself, as text-heavy values tend to", i).into_bytes()
+    }
+
+    // Writes 1000 entries to an in-memory SST compressed with `compression`
+    fn build(compression: Compression) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut writer = SstWriterBuilder::new().compression(compression).build_with_writer(&mut bytes);
+        for i in 0..1000 {
+            writer.add(format!("key{:04}", i).as_bytes(), &value(i)).unwrap();
+        }
+        writer.finish().unwrap();
+        bytes
+    }
+
+    // Checks that an SST written by `build` with `compression` is smaller than one without,
+    // records the compression and reads back every entry
+    fn check_round_trip(compression: Compression) {
+        let bytes = build(compression);
+        assert!(bytes.len() < build(Compression::None).len() / 2, "{:?}", compression);
+        let reader = SstReader::from_bytes(bytes).unwrap();
+        assert_eq!(reader.properties().unwrap().compression, compression);
+        reader.verify().unwrap();
+        for i in (0..1000).step_by(37) {
+            assert_eq!(reader.get(format!("key{:04}", i).as_bytes()).unwrap(), Some(value(i)));
+        }
+        assert_eq!(reader.iter().count(), 1000);
+    }
+
+    #[test]
+    fn flags_tell_the_algorithms_apart() {
+        let block = value(0).repeat(50);
+        let mut compressions = vec![
+            (Compression::None, FLAG_NONE),
+            (Compression::Lz4, FLAG_LZ4),
+            (Compression::Snappy, FLAG_SNAPPY),
+        ];
+        if cfg!(feature = "zstd") {
+            compressions.push((Compression::Zstd { level: 3 }, FLAG_ZSTD));
+        }
+        for (compression, flag) in compressions {
+            let compressed = compress_block(&block, compression).unwrap();
+            assert_eq!(compressed[0], flag, "{:?}", compression);
+            assert_eq!(decompress_block(&compressed).unwrap(), &block[..], "{:?}", compression);
+        }
+        // The payload is only read as the flag says
+        let lz4 = compress_block(&block, Compression::Lz4).unwrap();
+        assert_ne!(decompress_block(&[&[FLAG_NONE], &lz4[1..]].concat()).unwrap(), &block[..]);
+        assert!(matches!(decompress_block(&[9, 0]), Err(SstError::CorruptBlock(_))));
+    }
+
+    #[test]
+    fn lz4_round_trips() {
+        let block = value(0).repeat(50);
+        let compressed = compress_block(&block, Compression::Lz4).unwrap();
+        assert!(compressed.len() < block.len() / 4);
+        check_round_trip(Compression::Lz4);
+    }
+
+    #[test]
+    fn corrupt_lz4_block_is_an_error() {
+        let mut compressed = compress_block(&value(0).repeat(50), Compression::Lz4).unwrap();
+        compressed.truncate(compressed.len() / 2);
+        assert!(matches!(decompress_block(&compressed), Err(SstError::CorruptBlock(_))));
+        assert!(matches!(decompress_block(&[FLAG_LZ4, 1, 0]), Err(SstError::CorruptBlock(_))));
+    }
+
+    #[test]
+    fn snappy_round_trips() {
+        let block = value(0).repeat(50);
+        let compressed = compress_block(&block, Compression::Snappy).unwrap();
+        assert!(compressed.len() < block.len() / 4);
+        check_round_trip(Compression::Snappy);
+    }
+
+    #[test]
+    fn snappy_blocks_are_in_the_raw_format() {
+        // Compressed by the reference Snappy encoder, as LevelDB and RocksDB store it
+        let input = b"abcabcabcabcabcabcabcabcabcabcabcabc, said the sstable, abcabcabcabc";
+        let compressed = [
+            68, 8, 97, 98, 99, 130, 3, 0, 124, 44, 32, 115, 97, 105, 100, 32, 116, 104, 101, 32, 115, 115, 116, 97, 98,
+            108, 101, 44, 32, 97, 98, 99, 97, 98, 99, 97, 98, 99, 97, 98, 99,
+        ];
+        assert_eq!(decompress_block(&[&[FLAG_SNAPPY], &compressed[..]].concat()).unwrap(), &input[..]);
+        assert_eq!(compress_block(input, Compression::Snappy).unwrap()[1..], compressed);
+    }
+
+    #[test]
+    fn corrupt_snappy_block_is_an_error() {
+        let mut compressed = compress_block(&value(0).repeat(50), Compression::Snappy).unwrap();
+        compressed.truncate(compressed.len() / 2);
+        assert!(matches!(decompress_block(&compressed), Err(SstError::CorruptBlock(_))));
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_round_trips() {
+        let block = value(0).repeat(50);
+        let compressed = compress_block(&block, Compression::Zstd { level: 3 }).unwrap();
+        assert_eq!(compressed[0], FLAG_ZSTD);
+        assert!(compressed.len() < block.len() / 4);
+        assert_eq!(decompress_block(&compressed).unwrap(), &block[..]);
+        check_round_trip(Compression::Zstd { level: 3 });
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn corrupt_zstd_block_is_an_error() {
+        let mut compressed = compress_block(&value(0).repeat(50), Compression::Zstd { level: 3 }).unwrap();
+        compressed.truncate(compressed.len() / 2);
+        assert!(matches!(decompress_block(&compressed), Err(SstError::CorruptBlock(_))));
+    }
+
+    #[cfg(not(feature = "zstd"))]
+    #[test]
+    fn zstd_needs_the_feature() {
+        let result = compress_block(b"block", Compression::Zstd { level: 3 });
+        assert!(matches!(result, Err(SstError::UnsupportedCompression("zstd"))));
+        assert!(matches!(decompress_block(&[FLAG_ZSTD, 0]), Err(SstError::UnsupportedCompression("zstd"))));
+    }
+}
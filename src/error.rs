@@ -0,0 +1,112 @@
+use std::fmt;
+use std::io;
+
+use crate::format::{FORMAT_VERSION, MAX_KEY_LEN};
+
+/// Errors returned when reading or writing SST files.
+#[derive(Debug)]
+pub enum SstError {
+    /// An underlying I/O operation failed.
+    Io(io::Error),
+    /// The file does not end with the SST magic number.
+    BadMagic,
+    /// The file's application magic is not the one the reader expected.
+    ApplicationMagicMismatch,
+    /// The file ended before a complete structure could be read.
+    UnexpectedEof,
+    /// The file was written with a format version this reader does not understand.
+    UnsupportedVersion(u32),
+    /// The index block could not be parsed.
+    CorruptIndex(String),
+    /// A data block could not be parsed.
+    CorruptBlock(String),
+    /// The properties block could not be parsed.
+    CorruptProperties(String),
+    /// A writer checkpoint could not be parsed or doesn't match its file.
+    CorruptCheckpoint(String),
+    /// A serialised `Manifest` could not be parsed.
+    CorruptManifest(String),
+    /// A bloom sidecar file could not be parsed or belongs to a different SST.
+    CorruptBloomSidecar(String),
+    /// A key was added that is less than the previous key.
+    KeysOutOfOrder,
+    /// A key was added that is equal to the previous key.
+    DuplicateKey,
+    /// A zero-length key was added.
+    EmptyKey,
+    /// A key longer than `MAX_KEY_LEN` bytes was added.
+    KeyTooLarge(usize),
+    /// A data block's checksum does not match its contents.
+    ChecksumMismatch,
+    /// The file's contents don't match the checksum of the whole file in its footer.
+    FileChecksumMismatch,
+    /// The file was written before file checksums were added, so it has none to verify.
+    NoFileChecksum,
+    /// The file's keys are ordered by a different comparator than the one it is read with.
+    ComparatorMismatch { found: String, expected: String },
+    /// The file uses a compression algorithm that is not available in this build.
+    UnsupportedCompression(&'static str),
+    /// A line of an imported text file is malformed or out of order.
+    InvalidInputLine { line: u64, reason: String },
+}
+
+/// A specialised `Result` type for SST operations.
+pub type Result<T> = std::result::Result<T, SstError>;
+
+impl fmt::Display for SstError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SstError::Io(e) => write!(f, "I/O error: {}", e),
+            SstError::BadMagic => write!(f, "Invalid SST file format: bad magic number"),
+            SstError::ApplicationMagicMismatch => write!(f, "SST file belongs to a different application"),
+            SstError::UnexpectedEof => write!(f, "Unexpected end of SST file"),
+            SstError::UnsupportedVersion(version) => write!(
+                f,
+                "Unsupported SST format version {} (this build reads versions 1 to {})",
+                version, FORMAT_VERSION
+            ),
+            SstError::CorruptIndex(msg) => write!(f, "Corrupt index block: {}", msg),
+            SstError::CorruptBlock(msg) => write!(f, "Corrupt data block: {}", msg),
+            SstError::CorruptProperties(msg) => write!(f, "Corrupt properties block: {}", msg),
+            SstError::CorruptCheckpoint(msg) => write!(f, "Corrupt writer checkpoint: {}", msg),
+            SstError::CorruptManifest(msg) => write!(f, "Corrupt SST manifest: {}", msg),
+            SstError::CorruptBloomSidecar(msg) => write!(f, "Corrupt bloom sidecar: {}", msg),
+            SstError::KeysOutOfOrder => write!(f, "Keys must be added in strictly ascending order"),
+            SstError::DuplicateKey => write!(f, "Key was added more than once"),
+            SstError::EmptyKey => write!(f, "Keys must not be empty"),
+            SstError::KeyTooLarge(len) => {
+                write!(f, "Key of {} bytes is longer than the maximum of {} bytes", len, MAX_KEY_LEN)
+            }
+            SstError::ChecksumMismatch => write!(f, "Block checksum mismatch"),
+            SstError::FileChecksumMismatch => write!(f, "File checksum mismatch"),
+            SstError::NoFileChecksum => write!(f, "SST file has no file checksum"),
+            SstError::ComparatorMismatch { found, expected } => {
+                write!(f, "SST file is ordered by comparator {}, not {}", found, expected)
+            }
+            SstError::UnsupportedCompression(name) => {
+                write!(f, "{} compression is not available in this build", name)
+            }
+            SstError::InvalidInputLine { line, reason } => {
+                write!(f, "Invalid input on line {}: {}", line, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SstError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SstError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SstError {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::UnexpectedEof => SstError::UnexpectedEof,
+            _ => SstError::Io(e),
+        }
+    }
+}
\ No newline at end of file
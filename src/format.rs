@@ -0,0 +1,205 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::error::{Result, SstError};
+
+/// Magic number stored as the very last 8 bytes of every SST file.
+pub const MAGIC: u64 = 0xDEADBEEFCAFEBABE;
+
+/// Version of the on-disk format, recorded in the footer just before the magic number.
+/// Readers reject files with a version newer than the one they were built with.
+///
+/// - Version 1 added restart points to the end of every data block.
+/// - Version 2 added a trailer with an optional checksum to every data block.
+/// - Version 3 added a leading compression flag to every data block.
+/// - Version 4 added a bloom filter region referenced from the footer.
+/// - Version 5 added the first key of the file to the footer.
+/// - Version 6 prefix-compressed keys within data blocks.
+/// - Version 7 added a value-type byte to every entry so deletions can be recorded as tombstones.
+/// - Version 8 added the total number of entries to the footer.
+/// - Version 9 added blob values, stored outside the data blocks and referenced by pointer.
+/// - Version 10 added the cumulative entry count to every index entry.
+/// - Version 11 encoded entry key and value lengths as LEB128 varints.
+/// - Version 12 added an optional hash index to the end of data blocks for point lookups.
+/// - Version 13 added a properties block referenced from the footer.
+/// - Version 14 added a copy of the magic number to the start of the file.
+/// - Version 15 added partitioned indexes, with a type byte at the start of the index block.
+/// - Version 16 added an optional application magic to the footer.
+/// - Version 17 added an xxHash64 of the whole file to the footer.
+/// - Version 18 added the first key of every data block to its index entry.
+/// - Version 19 added sparse indexes, whose entries leave out the keys of unsampled blocks.
+pub const FORMAT_VERSION: u32 = 19;
+
+/// The longest key an SST can hold. Keys are stored with 32-bit lengths in the index,
+/// footer and properties block. Values have no such limit: entries store their lengths
+/// as varints, and the writer cuts a block before its entries pass the 4 GiB that its
+/// 32-bit restart points can address, so a large value only ever ends a block.
+pub const MAX_KEY_LEN: usize = u32::MAX as usize;
+
+// Checks that a key of `len` bytes can be written, so its length is never truncated
+pub(crate) fn check_key_len(len: usize) -> Result<()> {
+    if len == 0 {
+        return Err(SstError::EmptyKey);
+    }
+    if len > MAX_KEY_LEN {
+        return Err(SstError::KeyTooLarge(len));
+    }
+    Ok(())
+}
+
+// Value-type byte stored before each entry's value since version 7
+pub(crate) const VALUE_TYPE_VALUE: u8 = 0;
+pub(crate) const VALUE_TYPE_TOMBSTONE: u8 = 1;
+// Since version 9: the value is a [blob_offset: u64][blob_len: u64] pointer
+pub(crate) const VALUE_TYPE_BLOB: u8 = 2;
+
+// Since version 12 a data block may end with a hash index, marked by the high bit of
+// its restart count. Each bucket holds the restart interval of the keys hashing to it.
+// Format: [restarts][bucket: u8]...[num_buckets: u16][num_restarts | HASH_INDEX_FLAG: u32]
+pub(crate) const HASH_INDEX_FLAG: u32 = 1 << 31;
+pub(crate) const HASH_BUCKET_EMPTY: u8 = 0xFF;
+// Keys from more than one restart interval hash to the bucket
+pub(crate) const HASH_BUCKET_COLLISION: u8 = 0xFE;
+
+// Since version 14 every file starts with the magic number: [magic_number: u64]
+pub(crate) const HEADER_SIZE: u64 = 8;
+
+// Since version 15 the index block the footer points to starts with its type:
+// a flat index of every data block, or the top level of a partitioned index whose
+// entries point to index partitions in the flat format (without the type byte)
+pub(crate) const INDEX_TYPE_FLAT: u8 = 0;
+pub(crate) const INDEX_TYPE_PARTITIONED: u8 = 1;
+
+// The fixed tail of every footer: [format_version: u32][magic_number: u64]
+const TAIL_SIZE: u64 = 12;
+
+// Since version 17 the tail is preceded by a checksum of the file, covering every
+// byte but the checksum's own. This is the checksum's distance from the end of the file.
+pub(crate) const FILE_CHECKSUM_END: u64 = TAIL_SIZE + 8;
+
+// The footer that ends every SST file
+// Format: [first_key][first_key_len: u32]                                       (version 5+)
+//         [index_block_offset: u64][index_block_size: u64]
+//         [bloom_offset: u64][bloom_size: u64]                                 (version 4+)
+//         [num_entries: u64]                                                   (version 8+)
+//         [properties_offset: u64][properties_size: u64]                       (version 13+)
+//         [application_magic: 8 bytes]                                         (version 16+)
+//         [file_checksum: u64]                                                 (version 17+)
+//         [format_version: u32][magic_number: u64]
+#[derive(Debug, Default)]
+pub(crate) struct Footer {
+    pub(crate) version: u32,
+    pub(crate) index_offset: u64,
+    pub(crate) index_size: u64,
+    pub(crate) bloom_offset: u64,
+    pub(crate) bloom_size: u64,
+    pub(crate) first_key: Option<Vec<u8>>,
+    pub(crate) num_entries: Option<u64>,
+    pub(crate) properties_offset: u64,
+    pub(crate) properties_size: u64,
+    // Set by the application that wrote the file to tell its files apart; all zeros if unset
+    pub(crate) application_magic: [u8; 8],
+    // xxHash64 of the file without the checksum itself, the tail included
+    pub(crate) file_checksum: u64,
+    // Size of the footer itself in bytes; only set when read from a file
+    pub(crate) len: u64,
+}
+
+impl Footer {
+    // Serialises the footer in the current format version
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let first_key = self.first_key.as_deref().unwrap_or_default();
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(first_key);
+        bytes.extend_from_slice(&(first_key.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.index_offset.to_le_bytes());
+        bytes.extend_from_slice(&self.index_size.to_le_bytes());
+        bytes.extend_from_slice(&self.bloom_offset.to_le_bytes());
+        bytes.extend_from_slice(&self.bloom_size.to_le_bytes());
+        bytes.extend_from_slice(&self.num_entries.unwrap_or_default().to_le_bytes());
+        bytes.extend_from_slice(&self.properties_offset.to_le_bytes());
+        bytes.extend_from_slice(&self.properties_size.to_le_bytes());
+        bytes.extend_from_slice(&self.application_magic);
+        bytes.extend_from_slice(&self.file_checksum.to_le_bytes());
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&MAGIC.to_le_bytes());
+        bytes
+    }
+
+    // Reads and validates the footer from the end of an SST file
+    pub(crate) fn read_from<R: Read + Seek>(file: &mut R) -> Result<Footer> {
+        // Files shorter than the fixed tail (e.g. from an interrupted write)
+        // can't be valid, and seeking before their start would fail confusingly
+        let file_len = file.seek(SeekFrom::End(0))?;
+        let read_back = |file: &mut R, end_offset: u64, len: u64| -> Result<Vec<u8>> {
+            if end_offset > file_len {
+                return Err(SstError::UnexpectedEof);
+            }
+            file.seek(SeekFrom::Start(file_len - end_offset))?;
+            let mut buf = vec![0u8; len as usize];
+            file.read_exact(&mut buf)?;
+            Ok(buf)
+        };
+
+        let tail = read_back(file, TAIL_SIZE, TAIL_SIZE)?;
+        let magic = u64::from_le_bytes(tail[4..12].try_into().unwrap());
+        if magic != MAGIC {
+            return Err(SstError::BadMagic);
+        }
+        let version = u32::from_le_bytes(tail[0..4].try_into().unwrap());
+        if version == 0 || version > FORMAT_VERSION {
+            return Err(SstError::UnsupportedVersion(version));
+        }
+
+        // Read the handles to the index (and bloom filter since version 4),
+        // followed by the entry count since version 8, the properties
+        // handle since version 13, the application magic since version 16 and the
+        // file checksum since version 17
+        let num_handles = match version {
+            17.. => 9,
+            16.. => 8,
+            13.. => 7,
+            8.. => 5,
+            4.. => 4,
+            _ => 2,
+        };
+        let handles_end = TAIL_SIZE + num_handles * 8;
+        let handles = read_back(file, handles_end, num_handles * 8)?;
+        let handle = |i: usize| u64::from_le_bytes(handles[i * 8..i * 8 + 8].try_into().unwrap());
+
+        let mut footer = Footer {
+            version,
+            index_offset: handle(0),
+            index_size: handle(1),
+            ..Footer::default()
+        };
+        if version >= 4 {
+            footer.bloom_offset = handle(2);
+            footer.bloom_size = handle(3);
+        }
+        if version >= 8 {
+            footer.num_entries = Some(handle(4));
+        }
+        if version >= 13 {
+            footer.properties_offset = handle(5);
+            footer.properties_size = handle(6);
+        }
+        if version >= 16 {
+            footer.application_magic = handles[56..64].try_into().unwrap();
+        }
+        if version >= 17 {
+            footer.file_checksum = handle(8);
+        }
+
+        // Since version 5 the handles are preceded by [first_key][first_key_len: u32]
+        if version >= 5 {
+            let len_buf = read_back(file, handles_end + 4, 4)?;
+            let first_key_len = u32::from_le_bytes(len_buf.try_into().unwrap()) as u64;
+            footer.first_key = Some(read_back(file, handles_end + 4 + first_key_len, first_key_len)?);
+            footer.len = handles_end + 4 + first_key_len;
+        } else {
+            footer.len = handles_end;
+        }
+
+        Ok(footer)
+    }
+}
\ No newline at end of file
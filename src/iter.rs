@@ -0,0 +1,717 @@
+use std::ops::Bound;
+use std::sync::Arc;
+
+use crate::error::{Result, SstError};
+use crate::reader::{Entry, IndexEntryMeta, RawValue, SstReader};
+
+// A predicate over a key and its value, deciding whether the pair is returned
+type ValueFilter<'a> = Box<dyn Fn(&[u8], &[u8]) -> bool + 'a>;
+// Called with the bytes of the file scanned so far and the file's length
+type Progress<'a> = Box<dyn FnMut(u64, u64) + 'a>;
+
+/// Iterates over the key-value pairs of an SST in sorted key order,
+/// optionally restricted to a key range. Tombstones are skipped.
+pub struct SstIterator<'a> {
+    reader: &'a SstReader,
+    next_block: usize,
+    block: Arc<Vec<u8>>,
+    offset: usize,
+    end: usize,
+    // Key of the entry before `offset`, needed to rebuild prefix-compressed keys
+    prev_key: Vec<u8>,
+    start_bound: Bound<Vec<u8>>,
+    end_bound: Bound<Vec<u8>>,
+    // Whether the iterator still has to seek to the start bound before returning an entry
+    needs_seek: bool,
+    // Whether to ask the OS to read the next block while the current one is consumed
+    prefetch: bool,
+    // Key-value pairs this returns false for are skipped
+    filter: Option<ValueFilter<'a>>,
+    // Whether corrupt blocks are skipped rather than ending the iteration with an error
+    lenient: bool,
+    // The position in the index of each block skipped as corrupt, and why
+    skipped_blocks: Vec<(usize, SstError)>,
+    progress: Option<Progress<'a>>,
+    finished: bool,
+}
+
+impl<'a> SstIterator<'a> {
+    pub(crate) fn new(reader: &'a SstReader) -> Self {
+        Self::with_range(reader, Bound::Unbounded, Bound::Unbounded)
+    }
+
+    pub(crate) fn with_range(reader: &'a SstReader, start: Bound<&[u8]>, end: Bound<&[u8]>) -> Self {
+        SstIterator {
+            reader,
+            next_block: 0,
+            block: Arc::default(),
+            offset: 0,
+            end: 0,
+            prev_key: Vec::new(),
+            start_bound: start.map(|key| key.to_vec()),
+            end_bound: end.map(|key| key.to_vec()),
+            needs_seek: true,
+            prefetch: false,
+            filter: None,
+            lenient: false,
+            skipped_blocks: Vec::new(),
+            progress: None,
+            finished: false,
+        }
+    }
+
+    /// Enables or disables readahead (off by default). When enabled, loading a block
+    /// asks the OS to start reading the next one in the background, overlapping I/O
+    /// with decoding during large scans. The hint is given with `posix_fadvise`, so it
+    /// only takes effect on Linux, Android and FreeBSD, and only for readers reading
+    /// the file itself: on other platforms (including macOS and Windows), and for
+    /// readers from `open_mmap`, `open_in_memory` or `from_bytes`, it's a no-op.
+    pub fn prefetch(mut self, enabled: bool) -> Self {
+        self.prefetch = enabled;
+        self
+    }
+
+    /// Skips the key-value pairs for which `predicate` returns false, given the key and
+    /// the value. Values are checked while still in the block, so those that are skipped
+    /// are never copied out of it (except blob values, which must be read to be checked).
+    pub fn filter_values(mut self, predicate: impl Fn(&[u8], &[u8]) -> bool + 'a) -> Self {
+        self.filter = Some(Box::new(predicate));
+        self
+    }
+
+    /// Enables or disables lenient mode (off by default), for tools recovering what they
+    /// can from a damaged file. A block that fails its checksum or can't be decoded is
+    /// then skipped and iteration continues with the next block in the index, rather than
+    /// ending with an error. Entries of a block that were returned before it turned out
+    /// to be corrupt are kept. Errors reading the index or the file itself still end the
+    /// iteration. `skipped_blocks` reports the blocks skipped so far.
+    pub fn lenient(mut self, enabled: bool) -> Self {
+        self.lenient = enabled;
+        self
+    }
+
+    /// Calls `progress` with `(bytes_processed, total_bytes)` each time the scan reads a
+    /// data block, so interactive tools can report how far a long scan has got.
+    /// `bytes_processed` is the offset in the file of the end of that block, and
+    /// `total_bytes` the file's length; a scan of a range starts partway through the file.
+    /// A scan that runs to the end of the file calls it once more with
+    /// `(total_bytes, total_bytes)`. No lock is held while `progress` runs, so it may use
+    /// the reader.
+    pub fn on_progress(mut self, progress: impl FnMut(u64, u64) + 'a) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Returns the position in the index of each block skipped so far in lenient mode,
+    /// along with the error that made it unreadable.
+    pub fn skipped_blocks(&self) -> &[(usize, SstError)] {
+        &self.skipped_blocks
+    }
+
+    // Loads the next data block, returning false once every block has been read
+    // or the remaining blocks lie past the end of the range
+    fn load_next_block(&mut self) -> Result<bool> {
+        loop {
+            if self.next_block >= self.reader.num_blocks() {
+                return Ok(false);
+            }
+            // Every key in a span of the index is greater than the previous block's index key,
+            // so once that key reaches the end bound the rest of the file is out of range. This
+            // stops a scan whose end falls on a span boundary without reading the next block.
+            if let Bound::Included(end) | Bound::Excluded(end) = &self.end_bound
+                && let Some(prev_block) = self.next_block.checked_sub(1)
+                && self.reader.starts_span(self.next_block)?
+                && self.reader.comparator().compare(self.reader.index_key(prev_block)?, end).is_ge()
+            {
+                return Ok(false);
+            }
+            let block_idx = self.next_block;
+            self.next_block += 1;
+            let block = self.read_block(block_idx);
+            if let Some(progress) = &mut self.progress
+                && let Some(meta) = self.reader.index_entry(block_idx)?
+            {
+                progress(meta.block_offset + meta.block_size, self.reader.file_len());
+            }
+            let block = match block {
+                Ok(block) => block,
+                Err(e) => {
+                    self.skip_block(block_idx, e)?;
+                    continue;
+                }
+            };
+            if self.prefetch {
+                self.reader.prefetch_block(self.next_block);
+            }
+
+            (self.block, self.end) = block;
+            self.offset = 4;
+            self.prev_key.clear();
+            return Ok(true);
+        }
+    }
+
+    // Reads the data block at `block_idx`, returning it along with the offset where its
+    // entries end. Entries start after the 4-byte num_entries header and end at the restart array.
+    fn read_block(&self, block_idx: usize) -> Result<(Arc<Vec<u8>>, usize)> {
+        let block = self.reader.read_block_at(block_idx)?;
+        let end = SstReader::split_block(&block, self.reader.version())?.entries.len();
+        Ok((block, end))
+    }
+
+    // In lenient mode, records that the block at `block_idx` is corrupt and moves past the
+    // rest of it if it's the current block. Otherwise, or for errors other than corruption
+    // of the block, returns the error.
+    fn skip_block(&mut self, block_idx: usize, e: SstError) -> Result<()> {
+        if !self.lenient || !matches!(e, SstError::ChecksumMismatch | SstError::CorruptBlock(_)) {
+            return Err(e);
+        }
+        self.skipped_blocks.push((block_idx, e));
+        self.offset = self.end;
+        Ok(())
+    }
+
+    /// Positions the iterator at the first entry whose key is `>= key`, jumping straight
+    /// to the block that could contain it. Later calls to `next` continue forward from
+    /// there across block boundaries. Seeking never moves before the start of a range.
+    pub fn seek(&mut self, key: &[u8]) -> Result<()> {
+        let comparator = self.reader.comparator();
+        let below_start = match &self.start_bound {
+            Bound::Included(start) => comparator.compare(key, start).is_lt(),
+            Bound::Excluded(start) => comparator.compare(key, start).is_le(),
+            Bound::Unbounded => false,
+        };
+
+        self.needs_seek = false;
+        self.finished = false;
+        let result = if below_start {
+            let start = self.start_bound.clone();
+            self.seek_to(start.as_ref().map(|key| key.as_slice()))
+        } else {
+            self.seek_to(Bound::Included(key))
+        };
+        if result.is_err() {
+            self.finished = true;
+        }
+        result
+    }
+
+    // Positions the iterator at the first entry within the start bound,
+    // using the index to jump straight to the block that could contain it
+    fn seek_to(&mut self, start: Bound<&[u8]>) -> Result<()> {
+        self.next_block = self.reader.seek_block(start)?;
+        self.offset = 0;
+        self.end = 0;
+
+        let version = self.reader.version();
+        // The entry is in the first block loaded, or else starts the next one, except
+        // in a span of a sparse index, where it may be in any of the span's blocks
+        while self.load_next_block()? {
+            while self.offset < self.end {
+                let next = match SstReader::read_entry_into(&self.block, self.offset, &mut self.prev_key, version) {
+                    Ok((_, next)) => next,
+                    Err(e) => {
+                        self.skip_block(self.next_block - 1, e)?;
+                        break;
+                    }
+                };
+                let in_bound = match start {
+                    Bound::Included(start) => self.reader.comparator().compare(&self.prev_key, start).is_ge(),
+                    Bound::Excluded(start) => self.reader.comparator().compare(&self.prev_key, start).is_gt(),
+                    Bound::Unbounded => true,
+                };
+                // The entry is decoded again by `next_entry`. Its own key shares the prefix
+                // its predecessor's does, so leaving it in `prev_key` rebuilds it the same way.
+                if in_bound {
+                    return Ok(());
+                }
+                self.offset = next;
+            }
+        }
+        Ok(())
+    }
+
+    fn past_end(&self, key: &[u8]) -> bool {
+        match &self.end_bound {
+            Bound::Included(end) => self.reader.comparator().compare(key, end).is_gt(),
+            Bound::Excluded(end) => self.reader.comparator().compare(key, end).is_ge(),
+            Bound::Unbounded => false,
+        }
+    }
+
+    fn fail<T>(&mut self, e: SstError) -> Option<Result<T>> {
+        // Stop iterating after an error
+        self.finished = true;
+        Some(Err(e))
+    }
+
+    // Returns the next entry within the range, including tombstones
+    pub(crate) fn next_entry(&mut self) -> Option<Result<(Vec<u8>, Entry)>> {
+        if self.finished {
+            return None;
+        }
+
+        if self.needs_seek {
+            self.needs_seek = false;
+            let start = self.start_bound.clone();
+            if let Err(e) = self.seek_to(start.as_ref().map(|key| key.as_slice())) {
+                return self.fail(e);
+            }
+        }
+
+        loop {
+            while self.offset >= self.end {
+                match self.load_next_block() {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        self.finished = true;
+                        if let Some(progress) = &mut self.progress
+                            && self.next_block >= self.reader.num_blocks()
+                        {
+                            progress(self.reader.file_len(), self.reader.file_len());
+                        }
+                        return None;
+                    }
+                    Err(e) => return self.fail(e),
+                }
+            }
+
+            // Keys are rebuilt in place, so only those that are returned get copied
+            let version = self.reader.version();
+            let decoded = SstReader::read_entry_into(&self.block, self.offset, &mut self.prev_key, version);
+            let (value, next) = match decoded {
+                Ok(entry) => entry,
+                Err(e) => match self.skip_block(self.next_block - 1, e) {
+                    Ok(()) => continue,
+                    Err(e) => return self.fail(e),
+                },
+            };
+            let key = &self.prev_key;
+            if self.past_end(key) {
+                self.finished = true;
+                return None;
+            }
+            // Check inline values before copying them, and blob values once they're read
+            let rejected = match (&self.filter, value) {
+                (Some(filter), RawValue::Inline(value)) => !filter(key, value),
+                _ => false,
+            };
+            let entry = match rejected {
+                true => None,
+                false => match self.reader.resolve(value) {
+                    Ok(Entry::Value(value)) if self.filter.as_ref().is_some_and(|filter| !filter(key, &value)) => None,
+                    Ok(entry) => Some(entry),
+                    Err(e) => return self.fail(e),
+                },
+            };
+            self.offset = next;
+            if let Some(entry) = entry {
+                return Some(Ok((self.prev_key.clone(), entry)));
+            }
+        }
+    }
+}
+
+impl Iterator for SstIterator<'_> {
+    type Item = Result<(Vec<u8>, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.next_entry()? {
+                Ok((key, Entry::Value(value))) => return Some(Ok((key, value))),
+                Ok((_, Entry::Tombstone)) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Iterates over every entry of an SST in sorted key order, including tombstones.
+pub struct SstEntryIterator<'a> {
+    inner: SstIterator<'a>,
+}
+
+impl<'a> SstEntryIterator<'a> {
+    pub(crate) fn new(reader: &'a SstReader) -> Self {
+        SstEntryIterator { inner: SstIterator::new(reader) }
+    }
+}
+
+impl Iterator for SstEntryIterator<'_> {
+    type Item = Result<(Vec<u8>, Entry)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next_entry()
+    }
+}
+
+/// Iterates over the key-value pairs of an SST in descending key order. Tombstones are skipped.
+pub struct SstRevIterator<'a> {
+    reader: &'a SstReader,
+    // Number of blocks not yet decoded; they are visited from the back of the index
+    remaining_blocks: usize,
+    // Entries of the current block, popped from the end
+    entries: Vec<(Vec<u8>, Entry)>,
+    finished: bool,
+}
+
+impl<'a> SstRevIterator<'a> {
+    pub(crate) fn new(reader: &'a SstReader) -> Self {
+        let remaining_blocks = reader.num_blocks();
+        SstRevIterator {
+            reader,
+            remaining_blocks,
+            entries: Vec::new(),
+            finished: false,
+        }
+    }
+}
+
+impl Iterator for SstRevIterator<'_> {
+    type Item = Result<(Vec<u8>, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            while !self.finished && self.entries.is_empty() {
+                if self.remaining_blocks == 0 {
+                    self.finished = true;
+                    break;
+                }
+                self.remaining_blocks -= 1;
+                match self.reader.read_block_entries(self.remaining_blocks) {
+                    Ok(entries) => self.entries = entries,
+                    Err(e) => {
+                        // Stop iterating after an error
+                        self.finished = true;
+                        return Some(Err(e));
+                    }
+                }
+            }
+            match self.entries.pop()? {
+                (key, Entry::Value(value)) => return Some(Ok((key, value))),
+                (_, Entry::Tombstone) => continue,
+            }
+        }
+    }
+}
+/// A data block of an SST, yielded by `SstReader::blocks`. Nothing is read from the
+/// file until the block's entries or raw bytes are asked for, so blocks can be
+/// skipped or copied whole based on their index entry alone.
+pub struct Block<'a> {
+    reader: &'a SstReader,
+    block_idx: usize,
+    meta: IndexEntryMeta<'a>,
+}
+
+impl<'a> Block<'a> {
+    /// Returns a key `>=` every key in the block and `<` every key in the next block.
+    /// For the final block, and in files written before index keys were shortened,
+    /// this is the block's last key. In a sparse index, blocks share the key of their
+    /// span, which is only `<` every key in the next span.
+    pub fn last_key(&self) -> &'a [u8] {
+        self.meta.last_key
+    }
+
+    /// Returns the block's offset and size in the file, as recorded in the index.
+    pub fn meta(&self) -> IndexEntryMeta<'a> {
+        self.meta
+    }
+
+    /// Reads and decodes every entry of the block in key order, including tombstones.
+    pub fn entries(&self) -> Result<Vec<(Vec<u8>, Entry)>> {
+        self.reader.read_block_entries(self.block_idx)
+    }
+
+    /// Returns the block exactly as stored, still compressed and with its trailer,
+    /// after verifying its checksum.
+    pub fn raw_bytes(&self) -> Result<Vec<u8>> {
+        self.reader.read_raw_block(self.meta.block_offset, self.meta.block_size)
+    }
+}
+
+/// Iterates over the data blocks of an SST in key order.
+pub struct SstBlockIterator<'a> {
+    reader: &'a SstReader,
+    next_block: usize,
+    finished: bool,
+}
+
+impl<'a> SstBlockIterator<'a> {
+    pub(crate) fn new(reader: &'a SstReader) -> Self {
+        SstBlockIterator {
+            reader,
+            next_block: 0,
+            finished: false,
+        }
+    }
+}
+
+impl<'a> Iterator for SstBlockIterator<'a> {
+    type Item = Result<Block<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished || self.next_block >= self.reader.num_blocks() {
+            return None;
+        }
+        let block_idx = self.next_block;
+        self.next_block += 1;
+        match self.reader.index_entry(block_idx) {
+            Ok(meta) => meta.map(|meta| {
+                Ok(Block {
+                    reader: self.reader,
+                    block_idx,
+                    meta,
+                })
+            }),
+            Err(e) => {
+                // Stop iterating after an error
+                self.finished = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::{Bound, RangeBounds};
+    use std::time::Instant;
+
+    use crate::checksum::Checksum;
+    use crate::error::SstError;
+    use crate::reader::SstReader;
+    use crate::test_util::{TempPath, fruit, keys};
+    use crate::writer::SstWriterBuilder;
+
+    #[test]
+    fn range_returns_keys_between_the_bounds() {
+        let reader = fruit();
+        let range = |start, end| keys(reader.range(start, end));
+        assert_eq!(range(Bound::Included(&b"c"[..]), Bound::Excluded(&b"e"[..])), ["cherry", "date"]);
+        assert_eq!(range(Bound::Included(&b"banana"[..]), Bound::Included(&b"date"[..])), ["banana", "cherry", "date"]);
+        assert_eq!(range(Bound::Excluded(&b"banana"[..]), Bound::Excluded(&b"date"[..])), ["cherry"]);
+        assert_eq!(range(Bound::Unbounded, Bound::Excluded(&b"banana"[..])), ["apple"]);
+        assert_eq!(range(Bound::Excluded(&b"date"[..]), Bound::Unbounded), ["elderberry"]);
+        assert_eq!(range(Bound::Unbounded, Bound::Unbounded).len(), 5);
+        // Start past the end, and a range that is empty because its bounds meet
+        assert!(range(Bound::Included(&b"e"[..]), Bound::Excluded(&b"c"[..])).is_empty());
+        assert!(range(Bound::Excluded(&b"cherry"[..]), Bound::Excluded(&b"cherry"[..])).is_empty());
+        assert!(range(Bound::Included(&b"z"[..]), Bound::Unbounded).is_empty());
+    }
+
+    // Asks the OS to drop the file's cached pages, so a scan has to read it from disk
+    #[cfg(target_os = "linux")]
+    fn evict(path: &std::path::Path) {
+        use std::os::unix::io::AsRawFd;
+
+        let file = std::fs::File::open(path).unwrap();
+        unsafe {
+            libc::posix_fadvise(file.as_raw_fd(), 0, 0, libc::POSIX_FADV_DONTNEED);
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn evict(_path: &std::path::Path) {}
+
+    // Scans a multi-megabyte file from a cold cache with prefetch off and on. Both scans
+    // must return the same entries; the timings are printed, not asserted, as they
+    // depend on the disk (run with --nocapture to see them).
+    #[test]
+    fn prefetch_scans_return_the_same_entries() {
+        let path = TempPath::new("prefetch.sst");
+        let mut writer = SstWriterBuilder::new().block_size(64 * 1024).build(&path).unwrap();
+        for i in 0..50_000u32 {
+            writer.add(format!("key{:08}", i).as_bytes(), &[i as u8; 200]).unwrap();
+        }
+        writer.finish().unwrap();
+        assert!(std::fs::metadata(&path).unwrap().len() > 8 << 20);
+
+        let mut sums = Vec::new();
+        for prefetch in [false, true, false, true] {
+            evict(&path);
+            let reader = SstReader::open(&path).unwrap();
+            let start = Instant::now();
+            let mut sum = 0u64;
+            for entry in reader.iter().prefetch(prefetch) {
+                let (_, value) = entry.unwrap();
+                sum += value[0] as u64;
+            }
+            eprintln!("prefetch {}: {:?}", prefetch, start.elapsed());
+            sums.push(sum);
+        }
+        assert!(sums.iter().all(|&sum| sum == sums[0]));
+    }
+
+    #[test]
+    fn reverse_iteration_returns_keys_in_descending_order() {
+        assert_eq!(keys(fruit().iter_rev()), ["elderberry", "date", "cherry", "banana", "apple"]);
+
+        // Across blocks, with tombstones skipped
+        let mut bytes = Vec::new();
+        let mut writer = SstWriterBuilder::new().block_size(64).build_with_writer(&mut bytes);
+        for i in 0..200u32 {
+            let key = format!("key{:05}", i);
+            match i % 5 {
+                0 => writer.delete(key.as_bytes()).unwrap(),
+                _ => writer.add(key.as_bytes(), b"value").unwrap(),
+            }
+        }
+        writer.finish().unwrap();
+        let reader = SstReader::from_bytes(bytes).unwrap();
+        assert!(reader.num_blocks() > 10, "{} blocks", reader.num_blocks());
+        let mut forward = keys(reader.iter());
+        forward.reverse();
+        assert_eq!(forward.len(), 160);
+        assert_eq!(keys(reader.iter_rev()), forward);
+    }
+
+    #[test]
+    fn ranges_bounded_on_block_boundaries_return_the_right_keys() {
+        for options in [
+            SstWriterBuilder::new().block_size(64),
+            SstWriterBuilder::new().block_size(64).index_sample_interval(3),
+        ] {
+            let mut bytes = Vec::new();
+            let mut writer = options.build_with_writer(&mut bytes);
+            for i in 0..300u32 {
+                writer.add(format!("key{:05}", i * 2).as_bytes(), b"value").unwrap();
+            }
+            writer.finish().unwrap();
+            let reader = SstReader::from_bytes(bytes).unwrap();
+            assert!(reader.num_blocks() > 20, "{} blocks", reader.num_blocks());
+            let all = keys(reader.iter());
+
+            // Each block's first and last keys, and its index key, which may lie between blocks
+            let mut bounds = Vec::new();
+            for block in reader.blocks() {
+                let block = block.unwrap();
+                let entries = block.entries().unwrap();
+                bounds.push(entries.first().unwrap().0.clone());
+                bounds.push(entries.last().unwrap().0.clone());
+                bounds.push(block.last_key().to_vec());
+            }
+            for bound in &bounds {
+                let key = std::str::from_utf8(bound).unwrap();
+                for (start, end) in [
+                    (Bound::Included(key), Bound::Unbounded),
+                    (Bound::Excluded(key), Bound::Unbounded),
+                    (Bound::Unbounded, Bound::Included(key)),
+                    (Bound::Unbounded, Bound::Excluded(key)),
+                    (Bound::Included(key), Bound::Included(key)),
+                ] {
+                    let expected: Vec<_> = all.iter().filter(|key| (start, end).contains(&key.as_str())).collect();
+                    let range = keys(reader.range(start.map(str::as_bytes), end.map(str::as_bytes)));
+                    assert_eq!(range.iter().collect::<Vec<_>>(), expected, "{:?} to {:?}", start, end);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn block_entries_concatenate_to_the_flat_iteration() {
+        for options in [
+            SstWriterBuilder::new().block_size(64),
+            SstWriterBuilder::new().block_size(64).index_partition_size(128),
+        ] {
+            let mut bytes = Vec::new();
+            let mut writer = options.build_with_writer(&mut bytes);
+            for i in 0..300u32 {
+                let key = format!("key{:05}", i);
+                match i % 4 {
+                    0 => writer.delete(key.as_bytes()).unwrap(),
+                    _ => writer.add(key.as_bytes(), format!("{}", i).as_bytes()).unwrap(),
+                }
+            }
+            writer.finish().unwrap();
+            let reader = SstReader::from_bytes(bytes).unwrap();
+
+            let blocks: Vec<_> = reader.blocks().map(Result::unwrap).collect();
+            assert_eq!(blocks.len(), reader.num_blocks());
+            assert!(blocks.len() > 20, "{} blocks", blocks.len());
+            let index = reader.index_entries().unwrap();
+            assert_eq!(blocks.iter().map(|block| block.meta()).collect::<Vec<_>>(), index);
+            let concatenated: Vec<_> = blocks.iter().flat_map(|block| block.entries().unwrap()).collect();
+            let flat: Vec<_> = reader.iter_entries().map(Result::unwrap).collect();
+            assert_eq!(flat.len(), 300);
+            assert_eq!(concatenated, flat);
+        }
+    }
+
+    #[test]
+    fn filter_values_keeps_only_the_matching_pairs() {
+        let reader = fruit();
+        let contains_fruit = |_: &[u8], value: &[u8]| value.windows(5).any(|window| window == b"fruit");
+        assert_eq!(keys(reader.iter().filter_values(contains_fruit)), ["apple", "banana", "cherry", "date"]);
+        let range = reader.range(Bound::Included(&b"b"[..]), Bound::Unbounded);
+        assert_eq!(keys(range.filter_values(contains_fruit)), ["banana", "cherry", "date"]);
+        // The key is passed too
+        assert_eq!(keys(reader.iter().filter_values(|key, _| key.len() > 5)), ["banana", "cherry", "elderberry"]);
+        assert!(keys(reader.iter().filter_values(|_, _| false)).is_empty());
+    }
+
+    #[test]
+    fn lenient_iteration_skips_a_block_with_a_bad_checksum() {
+        let mut bytes = Vec::new();
+        let options = SstWriterBuilder::new().block_size(128).checksum(Checksum::Crc32);
+        let mut writer = options.build_with_writer(&mut bytes);
+        for i in 0..200 {
+            writer.add(format!("key{:04}", i).as_bytes(), format!("value{}", i).as_bytes()).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let reader = SstReader::from_bytes(bytes.clone()).unwrap();
+        let bad_block = reader.num_blocks() / 2;
+        let mut expected = Vec::new();
+        let (mut bad_meta, mut entries_before) = (None, 0);
+        for (block_idx, block) in reader.blocks().enumerate() {
+            let block = block.unwrap();
+            match block_idx == bad_block {
+                true => (bad_meta, entries_before) = (Some(block.meta()), expected.len()),
+                false => expected.extend(block.entries().unwrap().into_iter().map(|(key, _)| key)),
+            }
+        }
+        // The byte before the trailer's checksum type is the last of the checksum
+        let bad_meta = bad_meta.unwrap();
+        bytes[(bad_meta.block_offset + bad_meta.block_size) as usize - 2] ^= 0xff;
+        let reader = SstReader::from_bytes(bytes).unwrap();
+
+        let strict: Vec<_> = reader.iter().collect();
+        assert!(matches!(strict.last(), Some(Err(SstError::ChecksumMismatch))));
+        assert_eq!(strict.iter().filter(|entry| entry.is_ok()).count(), entries_before);
+
+        let mut lenient = reader.iter().lenient(true);
+        let salvaged: Vec<_> = lenient.by_ref().map(|entry| entry.unwrap().0).collect();
+        assert_eq!(salvaged, expected);
+        assert_eq!(lenient.skipped_blocks().len(), 1);
+        let (block_idx, e) = &lenient.skipped_blocks()[0];
+        assert!(*block_idx == bad_block && matches!(e, SstError::ChecksumMismatch));
+    }
+
+    #[test]
+    fn scans_report_progress_for_every_block() {
+        let mut bytes = Vec::new();
+        let mut writer = SstWriterBuilder::new().block_size(128).build_with_writer(&mut bytes);
+        for i in 0..300 {
+            writer.add(format!("key{:04}", i).as_bytes(), b"value").unwrap();
+        }
+        writer.finish().unwrap();
+        let reader = SstReader::from_bytes(bytes).unwrap();
+        let total = reader.file_len();
+        let block_ends: Vec<_> = reader
+            .blocks()
+            .map(|block| block.unwrap().meta())
+            .map(|meta| (meta.block_offset + meta.block_size, total))
+            .collect();
+
+        let mut calls = Vec::new();
+        assert_eq!(reader.iter().on_progress(|processed, total| calls.push((processed, total))).count(), 300);
+        assert_eq!(calls[..block_ends.len()], block_ends);
+        assert_eq!(calls[block_ends.len()..], [(total, total)]);
+
+        // A range ending before the last block stops short of the end of the file
+        let mut calls = Vec::new();
+        let range = reader.range(Bound::Included(&b"key0100"[..]), Bound::Excluded(&b"key0200"[..]));
+        assert_eq!(range.on_progress(|processed, total| calls.push((processed, total))).count(), 100);
+        assert!(!calls.is_empty() && calls.iter().all(|call| block_ends.contains(call)));
+    }
+}
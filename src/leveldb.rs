@@ -0,0 +1,427 @@
+use std::cmp::Ordering;
+use std::fs::File;
+use std::path::Path;
+
+use crate::checksum;
+use crate::compression;
+use crate::error::{Result, SstError};
+use crate::reader::{Entry, read_exact_at};
+use crate::varint;
+
+// LevelDB tables end with a fixed-size footer:
+// [metaindex_handle][index_handle][padding to 40 bytes][magic: u64]
+// where each handle is [offset: varint][size: varint]
+const FOOTER_SIZE: u64 = 48;
+const LEVELDB_MAGIC: u64 = 0xdb4775248b80fb57;
+
+// Every block is followed by a trailer: [compression_type: u8][masked crc32c: u32]
+const BLOCK_TRAILER_SIZE: u64 = 5;
+const COMPRESSION_NONE: u8 = 0;
+const COMPRESSION_SNAPPY: u8 = 1;
+const COMPRESSION_ZSTD: u8 = 2;
+const CRC_MASK_DELTA: u32 = 0xa282ead8;
+
+// Keys in a table are internal keys: [user_key][tag: u64], where the tag packs the
+// sequence number into its high 56 bits and the value type into its low byte
+const TAG_SIZE: usize = 8;
+const TYPE_DELETION: u8 = 0;
+const TYPE_VALUE: u8 = 1;
+// Sorts before every real entry for the same user key
+const MAX_SEQUENCE_TAG: u64 = (((1 << 56) - 1) << 8) | TYPE_VALUE as u64;
+
+// The position and size of a block, not counting its trailer
+#[derive(Debug, Clone, Copy)]
+struct BlockHandle {
+    offset: u64,
+    size: u64,
+}
+
+impl BlockHandle {
+    fn decode(buf: &mut &[u8]) -> Option<BlockHandle> {
+        Some(BlockHandle { offset: varint::read(buf)?, size: varint::read(buf)? })
+    }
+}
+
+/// Reads a table file written by LevelDB (`.ldb`, or `.sst` from older versions).
+///
+/// Tables from a LevelDB database store several versions of a key; lookups and
+/// iteration see only the newest version in the table, and a deletion is reported
+/// as a tombstone. Blocks compressed with Snappy are supported, and every block's
+/// CRC32C is verified as it is read. Filter blocks are ignored.
+pub struct LdbReader {
+    file: File,
+    file_len: u64,
+    // Each data block's separator key, an internal key >= every key in the block,
+    // with the block's handle
+    index: Vec<(Vec<u8>, BlockHandle)>,
+}
+
+impl LdbReader {
+    /// Opens a LevelDB table file and loads its index.
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+        if file_len < FOOTER_SIZE {
+            return Err(SstError::BadMagic);
+        }
+        let mut footer = [0; FOOTER_SIZE as usize];
+        read_exact_at(&file, &mut footer, file_len - FOOTER_SIZE)?;
+        let magic = u64::from_le_bytes(footer[40..].try_into().unwrap());
+        if magic != LEVELDB_MAGIC {
+            return Err(SstError::BadMagic);
+        }
+
+        let corrupt_footer = || SstError::CorruptIndex("invalid LevelDB footer".to_string());
+        let mut handles = &footer[..40];
+        let _metaindex = BlockHandle::decode(&mut handles).ok_or_else(corrupt_footer)?;
+        let index_handle = BlockHandle::decode(&mut handles).ok_or_else(corrupt_footer)?;
+
+        let mut reader = LdbReader { file, file_len, index: Vec::new() };
+        let index_block = reader.read_block(index_handle)?;
+        let end = restarts_offset(&index_block)?;
+        let mut offset = 0;
+        let mut prev_key = Vec::new();
+        while offset < end {
+            let (key, mut value, next) = read_entry(&index_block, offset, end, &prev_key)?;
+            let handle = BlockHandle::decode(&mut value)
+                .ok_or_else(|| SstError::CorruptIndex("invalid block handle in LevelDB index".to_string()))?;
+            reader.index.push((key.clone(), handle));
+            prev_key = key;
+            offset = next;
+        }
+        Ok(reader)
+    }
+
+    /// Searches for a key and returns its newest value in the table.
+    /// Keys whose newest entry is a deletion are reported as missing.
+    pub fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        match self.get_entry(key)? {
+            Some(Entry::Value(value)) => Ok(Some(value)),
+            Some(Entry::Tombstone) | None => Ok(None),
+        }
+    }
+
+    /// Searches for a key and returns its newest entry in the table, which is a
+    /// tombstone if the key was deleted.
+    pub fn get_entry(&self, key: &[u8]) -> Result<Option<Entry>> {
+        let mut target = key.to_vec();
+        target.extend_from_slice(&MAX_SEQUENCE_TAG.to_le_bytes());
+
+        // The first block whose separator is >= the target holds the first entry
+        // >= it, unless the target falls between the block's last key and the
+        // separator, in which case that entry starts the next block
+        let first_block = self
+            .index
+            .partition_point(|(separator, _)| compare_internal_keys(separator, &target) == Ordering::Less);
+        for (_, handle) in &self.index[first_block..] {
+            let block = self.read_block(*handle)?;
+            let Some((internal_key, value)) = seek_in_block(&block, &target)? else {
+                continue;
+            };
+            let (user_key, tag) = split_internal_key(&internal_key);
+            if user_key != key {
+                return Ok(None);
+            }
+            return entry_for_tag(tag, value).map(Some);
+        }
+        Ok(None)
+    }
+
+    /// Returns an iterator over the newest entry of every key in the table, in
+    /// sorted key order, including tombstones.
+    pub fn iter(&self) -> LdbIterator<'_> {
+        LdbIterator {
+            reader: self,
+            next_block: 0,
+            block: Vec::new(),
+            offset: 0,
+            end: 0,
+            prev_key: Vec::new(),
+            last_user_key: None,
+            finished: false,
+        }
+    }
+
+    // Reads a block, verifying its checksum and decompressing it. A corrupt handle can
+    // claim any size, so one reaching past the end of the file is rejected before a
+    // buffer is allocated for it.
+    fn read_block(&self, handle: BlockHandle) -> Result<Vec<u8>> {
+        let len = handle.size.checked_add(BLOCK_TRAILER_SIZE).ok_or(SstError::UnexpectedEof)?;
+        if handle.offset.checked_add(len).is_none_or(|end| end > self.file_len) {
+            return Err(SstError::UnexpectedEof);
+        }
+        let mut buf = vec![0; len as usize];
+        read_exact_at(&self.file, &mut buf, handle.offset)?;
+
+        // The checksum covers the contents and the compression type
+        let (covered, stored) = buf.split_at(handle.size as usize + 1);
+        let stored = u32::from_le_bytes(stored.try_into().unwrap());
+        let stored = stored.wrapping_sub(CRC_MASK_DELTA).rotate_left(15);
+        if checksum::crc32c(covered) != stored {
+            return Err(SstError::ChecksumMismatch);
+        }
+
+        buf.truncate(handle.size as usize + 1);
+        match buf.pop() {
+            Some(COMPRESSION_NONE) => Ok(buf),
+            Some(COMPRESSION_SNAPPY) => compression::snappy_decompress(&buf),
+            Some(COMPRESSION_ZSTD) => compression::zstd_decompress(&buf),
+            other => Err(SstError::CorruptBlock(format!(
+                "invalid LevelDB compression type {}",
+                other.unwrap_or_default()
+            ))),
+        }
+    }
+}
+
+/// Iterates over the newest entry of every key in a LevelDB table, in sorted key order.
+pub struct LdbIterator<'a> {
+    reader: &'a LdbReader,
+    next_block: usize,
+    block: Vec<u8>,
+    // Offset of the next entry in `block`
+    offset: usize,
+    // Offset of the block's restart array, where its entries end
+    end: usize,
+    // Internal key of the entry before `offset`, needed to rebuild prefix-compressed keys
+    prev_key: Vec<u8>,
+    // The last user key returned, so older versions of it are skipped
+    last_user_key: Option<Vec<u8>>,
+    finished: bool,
+}
+
+impl LdbIterator<'_> {
+    // Returns the next internal key and its value, reading blocks as needed
+    fn next_internal(&mut self) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+        while self.offset >= self.end {
+            let Some((_, handle)) = self.reader.index.get(self.next_block) else {
+                return Ok(None);
+            };
+            self.block = self.reader.read_block(*handle)?;
+            self.next_block += 1;
+            self.end = restarts_offset(&self.block)?;
+            self.offset = 0;
+            self.prev_key.clear();
+        }
+
+        let (key, value, next) = read_entry(&self.block, self.offset, self.end, &self.prev_key)?;
+        check_internal_key(&key)?;
+        self.offset = next;
+        self.prev_key.clone_from(&key);
+        Ok(Some((key, value.to_vec())))
+    }
+}
+
+impl Iterator for LdbIterator<'_> {
+    type Item = Result<(Vec<u8>, Entry)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished {
+            let (internal_key, value) = match self.next_internal() {
+                Ok(Some(entry)) => entry,
+                Ok(None) => break,
+                Err(e) => {
+                    // Stop iterating after an error
+                    self.finished = true;
+                    return Some(Err(e));
+                }
+            };
+
+            // Versions of a key are sorted newest first, so only the first one counts
+            let (user_key, tag) = split_internal_key(&internal_key);
+            if self.last_user_key.as_deref() == Some(user_key) {
+                continue;
+            }
+            self.last_user_key = Some(user_key.to_vec());
+            let entry = entry_for_tag(tag, &value).map(|entry| (user_key.to_vec(), entry));
+            if entry.is_err() {
+                self.finished = true;
+            }
+            return Some(entry);
+        }
+        self.finished = true;
+        None
+    }
+}
+
+// Returns the offset of a block's restart array, where its entries end
+// Block Format: [entry]...[restart: u32]...[num_restarts: u32]
+fn restarts_offset(block: &[u8]) -> Result<usize> {
+    let corrupt = || SstError::CorruptBlock("LevelDB block restart array is truncated".to_string());
+    let (rest, num_restarts) = block.split_last_chunk::<4>().ok_or_else(corrupt)?;
+    (u32::from_le_bytes(*num_restarts) as usize)
+        .checked_mul(4)
+        .and_then(|len| rest.len().checked_sub(len))
+        .ok_or_else(corrupt)
+}
+
+// Returns the offset of the entry at restart point `i`, whose key is stored in full
+fn restart(block: &[u8], restarts_offset: usize, i: usize) -> usize {
+    let start = restarts_offset + i * 4;
+    u32::from_le_bytes(block[start..start + 4].try_into().unwrap()) as usize
+}
+
+// Decodes the entry at `offset`, rebuilding its key from `prev_key`, the key of the
+// preceding entry. Returns the key, the value and the offset of the next entry.
+// Entry Format: [shared_len: varint][unshared_len: varint][value_len: varint][unshared key bytes][value]
+fn read_entry<'a>(block: &'a [u8], offset: usize, end: usize, prev_key: &[u8]) -> Result<(Vec<u8>, &'a [u8], usize)> {
+    let truncated = || SstError::CorruptBlock(format!("LevelDB entry at offset {} is truncated", offset));
+
+    let mut rest = block.get(offset..end).ok_or_else(truncated)?;
+    let shared = varint::read(&mut rest).ok_or_else(truncated)? as usize;
+    let unshared = varint::read(&mut rest).ok_or_else(truncated)? as usize;
+    let value_len = varint::read(&mut rest).ok_or_else(truncated)? as usize;
+    let prefix = prev_key.get(..shared).ok_or_else(truncated)?;
+    let (unshared_bytes, rest) = rest.split_at_checked(unshared).ok_or_else(truncated)?;
+    let (value, rest) = rest.split_at_checked(value_len).ok_or_else(truncated)?;
+
+    let mut key = Vec::with_capacity(shared + unshared);
+    key.extend_from_slice(prefix);
+    key.extend_from_slice(unshared_bytes);
+    Ok((key, value, end - rest.len()))
+}
+
+// Returns the first entry in a data block whose internal key is >= `target`
+fn seek_in_block<'a>(block: &'a [u8], target: &[u8]) -> Result<Option<(Vec<u8>, &'a [u8])>> {
+    let end = restarts_offset(block)?;
+    let num_restarts = (block.len() - 4 - end) / 4;
+
+    // Binary search for the last restart point whose key is < target
+    let (mut lo, mut hi) = (0, num_restarts);
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        let (key, _, _) = read_entry(block, restart(block, end, mid), end, &[])?;
+        if compare_internal_keys(&key, target) == Ordering::Less {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+
+    let mut offset = lo.checked_sub(1).map_or(0, |i| restart(block, end, i));
+    let mut prev_key = Vec::new();
+    while offset < end {
+        let (key, value, next) = read_entry(block, offset, end, &prev_key)?;
+        if compare_internal_keys(&key, target) != Ordering::Less {
+            check_internal_key(&key)?;
+            return Ok(Some((key, value)));
+        }
+        prev_key = key;
+        offset = next;
+    }
+    Ok(None)
+}
+
+fn check_internal_key(key: &[u8]) -> Result<()> {
+    if key.len() < TAG_SIZE {
+        return Err(SstError::CorruptBlock(format!("LevelDB internal key is only {} bytes", key.len())));
+    }
+    Ok(())
+}
+
+// Splits an internal key into its user key and tag. Keys too short to hold a tag
+// are treated as a user key with a zero tag.
+fn split_internal_key(key: &[u8]) -> (&[u8], u64) {
+    match key.split_last_chunk::<TAG_SIZE>() {
+        Some((user_key, tag)) => (user_key, u64::from_le_bytes(*tag)),
+        None => (key, 0),
+    }
+}
+
+// Orders internal keys by user key, then by sequence number from newest to oldest
+fn compare_internal_keys(a: &[u8], b: &[u8]) -> Ordering {
+    let (a_user_key, a_tag) = split_internal_key(a);
+    let (b_user_key, b_tag) = split_internal_key(b);
+    a_user_key.cmp(b_user_key).then(b_tag.cmp(&a_tag))
+}
+
+// Turns an entry's value into an `Entry` according to the value type in its tag
+fn entry_for_tag(tag: u64, value: &[u8]) -> Result<Entry> {
+    match tag as u8 {
+        TYPE_VALUE => Ok(Entry::Value(value.to_vec())),
+        TYPE_DELETION => Ok(Entry::Tombstone),
+        other => Err(SstError::CorruptBlock(format!("invalid LevelDB value type {}", other))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::test_util::TempPath;
+
+    // Tables written by LevelDB 1.22 with 1 KiB blocks and a bloom filter, holding
+    // key000 to key099 with every tenth key rewritten and every seventh key deleted.
+    // Every version of each key is in the table, newest first.
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/leveldb").join(name)
+    }
+
+    fn key(i: u32) -> Vec<u8> {
+        format!("key{:03}", i).into_bytes()
+    }
+
+    // The newest entry the fixtures hold for key `i`
+    fn expected(i: u32) -> Entry {
+        match i {
+            _ if i.is_multiple_of(7) => Entry::Tombstone,
+            _ if i.is_multiple_of(10) => Entry::Value(format!("rewritten {}", i).into_bytes()),
+            _ => {
+                let value = format!("value {} of the LevelDB fixture", i);
+                Entry::Value(format!("{}, {}", value, value).into_bytes())
+            }
+        }
+    }
+
+    #[test]
+    fn reads_tables_written_by_leveldb() {
+        for (name, compression) in [("uncompressed.ldb", COMPRESSION_NONE), ("snappy.ldb", COMPRESSION_SNAPPY)] {
+            let reader = LdbReader::open(&fixture(name)).unwrap();
+            assert!(reader.index.len() > 5, "{} has {} blocks", name, reader.index.len());
+            // The compression type follows each block
+            let bytes = fs::read(fixture(name)).unwrap();
+            for (_, handle) in &reader.index {
+                assert_eq!(bytes[(handle.offset + handle.size) as usize], compression, "{}", name);
+            }
+            for i in 0..100 {
+                assert_eq!(reader.get_entry(&key(i)).unwrap(), Some(expected(i)), "{} key {}", name, i);
+                let value = match expected(i) {
+                    Entry::Value(value) => Some(value),
+                    Entry::Tombstone => None,
+                };
+                assert_eq!(reader.get(&key(i)).unwrap(), value);
+            }
+            for missing in [&b"key"[..], b"key0995", b"key100", b"a", b"z"] {
+                assert_eq!(reader.get_entry(missing).unwrap(), None, "{}", name);
+            }
+
+            let entries: Vec<_> = reader.iter().map(Result::unwrap).collect();
+            assert_eq!(entries, (0..100).map(|i| (key(i), expected(i))).collect::<Vec<_>>(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn corrupt_tables_are_errors() {
+        let bytes = fs::read(fixture("snappy.ldb")).unwrap();
+        let path = TempPath::new("corrupt.ldb");
+
+        // A flipped byte in the first data block fails its CRC32C
+        let mut corrupt = bytes.clone();
+        corrupt[10] ^= 0x01;
+        fs::write(&path, &corrupt).unwrap();
+        let reader = LdbReader::open(&path).unwrap();
+        assert!(matches!(reader.get(&key(1)), Err(SstError::ChecksumMismatch)));
+
+        // An index entry claiming a huge block is rejected without reading it
+        let mut reader = LdbReader::open(&fixture("snappy.ldb")).unwrap();
+        reader.index[0].1.size = u64::MAX / 2;
+        assert!(matches!(reader.get(&key(1)), Err(SstError::UnexpectedEof)));
+        reader.index[0].1 = BlockHandle { offset: bytes.len() as u64 - 4, size: 10 };
+        assert!(matches!(reader.get(&key(1)), Err(SstError::UnexpectedEof)));
+
+        fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+        assert!(matches!(LdbReader::open(&path), Err(SstError::BadMagic)));
+    }
+}
@@ -0,0 +1,200 @@
+use crate::error::{Result, SstError};
+
+// An implementation of the LZ4 block format: a series of sequences, each a token
+// byte (literal length in the high nibble, match length - 4 in the low nibble),
+// optional extra literal length bytes, the literals, then a 2-byte match offset
+// and optional extra match length bytes. The last sequence has literals only.
+
+const MIN_MATCH: usize = 4;
+// The last match must start at least 12 bytes before the end of the input
+const MF_LIMIT: usize = 12;
+// and the last 5 bytes are always literals
+const LAST_LITERALS: usize = 5;
+const MAX_OFFSET: usize = 65535;
+const HASH_LOG: u32 = 12;
+
+// Compresses `input` into a single LZ4 block
+pub(crate) fn compress(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len() / 2 + 16);
+    // Position + 1 of the last occurrence of each hashed 4-byte sequence (0 means empty)
+    let mut table = vec![0usize; 1 << HASH_LOG];
+    let mut anchor = 0;
+    let mut pos = 0;
+
+    let match_limit = input.len().saturating_sub(MF_LIMIT);
+    while pos < match_limit {
+        let sequence = u32::from_le_bytes(input[pos..pos + MIN_MATCH].try_into().unwrap());
+        let hash = (sequence.wrapping_mul(2654435761) >> (32 - HASH_LOG)) as usize;
+        let candidate = table[hash].checked_sub(1);
+        table[hash] = pos + 1;
+
+        if let Some(candidate) = candidate
+            && pos - candidate <= MAX_OFFSET
+            && input[candidate..candidate + MIN_MATCH] == input[pos..pos + MIN_MATCH]
+        {
+            let max_end = input.len() - LAST_LITERALS;
+            let mut len = MIN_MATCH;
+            while pos + len < max_end && input[candidate + len] == input[pos + len] {
+                len += 1;
+            }
+            write_sequence(&mut out, &input[anchor..pos], Some((pos - candidate, len)));
+            pos += len;
+            anchor = pos;
+            continue;
+        }
+        pos += 1;
+    }
+
+    write_sequence(&mut out, &input[anchor..], None);
+    out
+}
+
+// Decompresses a single LZ4 block that is expected to expand to `expected_len` bytes
+pub(crate) fn decompress(mut input: &[u8], expected_len: usize) -> Result<Vec<u8>> {
+    let corrupt = || SstError::CorruptBlock("invalid LZ4 data".to_string());
+
+    // A sequence expands at most 255-fold, so don't trust a wildly larger declared length
+    let mut out = Vec::with_capacity(expected_len.min(input.len().saturating_mul(255)));
+    loop {
+        let (&token, rest) = input.split_first().ok_or_else(corrupt)?;
+        input = rest;
+
+        let mut literal_len = (token >> 4) as usize;
+        if literal_len == 15 {
+            literal_len += read_length(&mut input).ok_or_else(corrupt)?;
+        }
+        let (literals, rest) = input.split_at_checked(literal_len).ok_or_else(corrupt)?;
+        if out.len() + literal_len > expected_len {
+            return Err(corrupt());
+        }
+        out.extend_from_slice(literals);
+        input = rest;
+
+        // The last sequence ends after its literals
+        if input.is_empty() {
+            break;
+        }
+
+        let (offset, rest) = input.split_at_checked(2).ok_or_else(corrupt)?;
+        let offset = u16::from_le_bytes(offset.try_into().unwrap()) as usize;
+        input = rest;
+        let mut match_len = (token & 0x0F) as usize;
+        if match_len == 15 {
+            match_len += read_length(&mut input).ok_or_else(corrupt)?;
+        }
+        match_len += MIN_MATCH;
+
+        if offset == 0 || offset > out.len() || out.len() + match_len > expected_len {
+            return Err(corrupt());
+        }
+        // Matches may overlap the bytes they produce, so copy one byte at a time
+        let start = out.len() - offset;
+        for i in 0..match_len {
+            out.push(out[start + i]);
+        }
+    }
+
+    if out.len() != expected_len {
+        return Err(corrupt());
+    }
+    Ok(out)
+}
+
+// Writes a sequence of literals followed by an optional (offset, length) match
+fn write_sequence(out: &mut Vec<u8>, literals: &[u8], matched: Option<(usize, usize)>) {
+    let literal_len = literals.len();
+    let match_len = matched.map_or(0, |(_, len)| len - MIN_MATCH);
+    out.push(((literal_len.min(15) as u8) << 4) | match_len.min(15) as u8);
+    if literal_len >= 15 {
+        write_length(out, literal_len - 15);
+    }
+    out.extend_from_slice(literals);
+
+    if let Some((offset, _)) = matched {
+        out.extend_from_slice(&(offset as u16).to_le_bytes());
+        if match_len >= 15 {
+            write_length(out, match_len - 15);
+        }
+    }
+}
+
+// Lengths beyond a nibble continue in bytes of 255, ending with a byte below 255
+fn write_length(out: &mut Vec<u8>, mut len: usize) {
+    while len >= 255 {
+        out.push(255);
+        len -= 255;
+    }
+    out.push(len as u8);
+}
+
+fn read_length(input: &mut &[u8]) -> Option<usize> {
+    let mut len = 0usize;
+    loop {
+        let (&byte, rest) = input.split_first()?;
+        *input = rest;
+        len = len.checked_add(byte as usize)?;
+        if byte != 255 {
+            return Some(len);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Inputs covering empty and short blocks, long literal runs, long matches, and
+    // matches at the largest offset
+    fn inputs() -> Vec<Vec<u8>> {
+        let mut state = 0x2545F4914F6CDD1Du64;
+        let random: Vec<u8> = (0..70_000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect();
+        let mut far_match = random[..MAX_OFFSET].to_vec();
+        far_match.extend_from_slice(&random[..100]);
+        vec![
+            Vec::new(),
+            b"a".to_vec(),
+            b"abcdabcdabcd".to_vec(),
+            vec![0; 100_000],
+            b"the quick brown fox jumps over the lazy dog. ".repeat(300),
+            random,
+            far_match,
+        ]
+    }
+
+    #[test]
+    fn round_trips() {
+        for input in inputs() {
+            let compressed = compress(&input);
+            assert_eq!(decompress(&compressed, input.len()).unwrap(), input, "{} bytes", input.len());
+        }
+        assert!(compress(&[0; 100_000]).len() < 1000);
+    }
+
+    #[test]
+    fn decompresses_the_reference_encoder_output() {
+        // Compressed by the reference LZ4 block encoder
+        let input = b"abcabcabcabcabcabcabcabcabcabcabcabc, said the sstable, abcabcabcabc";
+        let compressed = [
+            63, 97, 98, 99, 3, 0, 14, 242, 5, 44, 32, 115, 97, 105, 100, 32, 116, 104, 101, 32, 115, 115, 116, 97, 98,
+            108, 101, 44, 32, 53, 0, 96, 97, 98, 99, 97, 98, 99,
+        ];
+        assert_eq!(decompress(&compressed, input.len()).unwrap(), input);
+    }
+
+    #[test]
+    fn corrupt_input_is_an_error() {
+        let compressed = compress(&b"the quick brown fox jumps over the lazy dog. ".repeat(30));
+        for len in 0..compressed.len() {
+            assert!(decompress(&compressed[..len], 1350).is_err(), "truncated to {}", len);
+        }
+        // A match reaching back before the start of the output
+        assert!(decompress(&[0x10, b'a', 5, 0, 0x00], 10).is_err());
+    }
+}
@@ -1,69 +1,206 @@
-use std::path::Path;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 
-use crate::{reader::SstReader, writer::SstWriter};
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand};
 
+use crate::{error::SstError, reader::SstReader, writer::SstWriter};
+
+#[cfg(feature = "tokio")]
+pub mod async_reader;
+mod base64;
+pub mod bloom;
+pub mod cache;
+pub mod checksum;
+pub mod comparator;
+pub mod compression;
+pub mod error;
+pub mod format;
+pub mod iter;
+pub mod leveldb;
+mod lz4;
+pub mod manifest;
+pub mod merge;
+pub mod parallel;
+pub mod properties;
 pub mod reader;
+pub mod set;
+pub mod sorting;
+#[cfg(test)]
+mod test_util;
+mod varint;
 pub mod writer;
 
-fn main() -> std::io::Result<()> {
-    let sst_path = Path::new("example.sst");
-
-    // === Writing the SST file ===
-    println!("Writing SST file...");
-    let mut writer = SstWriter::new(sst_path)?;
-    
-    // Add data in sorted order
-    writer.add(b"apple", b"A fruit that grows on trees.")?;
-    writer.add(b"banana", b"An elongated, edible fruit.")?;
-    writer.add(b"cherry", b"A small, round stone fruit.")?;
-    writer.add(b"date", b"A sweet, dark brown oval fruit.")?;
-    writer.add(b"elderberry", b"A dark purple berry.")?;
-    
-    writer.finish()?;
-    println!("SST file 'example.sst' created.");
-
-    // === Reading from the SST file ===
-    println!("\nReading from SST file...");
-    let mut reader = SstReader::open(sst_path)?;
-
-    // --- Test Case 1: Key exists ---
-    let key_to_find = b"cherry";
-    match reader.get(key_to_find)? {
-        Some(value) => {
-            println!(
-                "Found key '{}': '{}'",
-                String::from_utf8_lossy(key_to_find),
-                String::from_utf8_lossy(&value)
-            );
+/// Reads, checks and builds SST files.
+#[derive(Parser)]
+#[command(name = "sst", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the value stored for a key
+    Get {
+        file: PathBuf,
+        /// The key, used as given unless --hex or --base64 says how it is encoded
+        key: String,
+        /// The key is hex encoded
+        #[arg(long, conflicts_with = "base64")]
+        hex: bool,
+        /// The key is base64 encoded
+        #[arg(long)]
+        base64: bool,
+    },
+    /// Print every entry as JSON
+    Dump { file: PathBuf },
+    /// Check the file for corruption
+    Verify { file: PathBuf },
+    /// Build an SST from sorted key<TAB>value lines
+    Build {
+        #[arg(value_name = "INPUT.TSV")]
+        input: PathBuf,
+        #[arg(value_name = "OUTPUT.SST")]
+        output: PathBuf,
+    },
+    /// Print the file's properties and layout
+    Info { file: PathBuf },
+}
+
+// Why a command couldn't run to completion
+enum CliError {
+    // The arguments to the named subcommand are invalid, for the reason given
+    Usage(&'static str, String),
+    Sst(SstError),
+}
+
+impl From<SstError> for CliError {
+    fn from(e: SstError) -> Self {
+        CliError::Sst(e)
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(e: io::Error) -> Self {
+        CliError::Sst(e.into())
+    }
+}
+
+fn main() -> ExitCode {
+    // Invalid arguments print the usage and exit with code 2
+    let cli = Cli::parse();
+    match run(cli.command) {
+        Ok(code) => code,
+        Err(CliError::Usage(name, reason)) => {
+            // Building the command names its subcommands' usage after the binary
+            let mut command = Cli::command();
+            command.build();
+            let subcommand = command.find_subcommand_mut(name).expect("usage errors name a subcommand");
+            subcommand.error(ErrorKind::InvalidValue, reason).exit()
+        }
+        Err(CliError::Sst(e)) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
         }
-        None => println!("Key '{}' not found.", String::from_utf8_lossy(key_to_find)),
     }
-    
-    // --- Test Case 2: Key does not exist ---
-    let key_to_find_2 = b"fig";
-     match reader.get(key_to_find_2)? {
-        Some(value) => {
-             println!(
-                "Found key '{}': '{}'",
-                String::from_utf8_lossy(key_to_find_2),
-                String::from_utf8_lossy(&value)
-            );
+}
+
+fn run(command: Command) -> Result<ExitCode, CliError> {
+    match command {
+        Command::Get { file, key, hex, base64 } => {
+            let invalid_key = |encoding: &str| CliError::Usage("get", format!("{} is not valid {}", key, encoding));
+            let key = if hex {
+                decode_hex(&key).ok_or_else(|| invalid_key("hex"))?
+            } else if base64 {
+                base64::decode(&key).ok_or_else(|| invalid_key("base64"))?
+            } else {
+                key.as_bytes().to_vec()
+            };
+            get(&file, &key)
+        }
+        Command::Dump { file } => {
+            SstReader::open(&file)?.to_json_writer(io::stdout().lock())?;
+            Ok(ExitCode::SUCCESS)
         }
-        None => println!("Key '{}' not found.", String::from_utf8_lossy(key_to_find_2)),
+        Command::Verify { file } => verify(&file),
+        Command::Build { input, output } => build(&input, &output),
+        Command::Info { file } => info(&file),
     }
-    
-     // --- Test Case 3: First key ---
-    let key_to_find_3 = b"apple";
-     match reader.get(key_to_find_3)? {
+}
+
+// Prints the value stored for a key, failing if the key isn't in the file
+fn get(path: &Path, key: &[u8]) -> Result<ExitCode, CliError> {
+    match SstReader::open(path)?.get(key)? {
         Some(value) => {
-             println!(
-                "Found key '{}': '{}'",
-                String::from_utf8_lossy(key_to_find_3),
-                String::from_utf8_lossy(&value)
-            );
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(&value)?;
+            stdout.write_all(b"\n")?;
+            Ok(ExitCode::SUCCESS)
+        }
+        None => {
+            eprintln!("key not found");
+            Ok(ExitCode::FAILURE)
         }
-        None => println!("Key '{}' not found.", String::from_utf8_lossy(key_to_find_3)),
     }
+}
+
+// Scrubs the file and checks its file checksum, if it was written with one
+fn verify(path: &Path) -> Result<ExitCode, CliError> {
+    let reader = SstReader::open(path)?;
+    reader.verify()?;
+    match reader.verify_file_checksum() {
+        Ok(()) | Err(SstError::NoFileChecksum) => {}
+        Err(e) => return Err(e.into()),
+    }
+    println!("OK");
+    Ok(ExitCode::SUCCESS)
+}
+
+fn build(input: &Path, output: &Path) -> Result<ExitCode, CliError> {
+    let stats = SstWriter::from_csv(input, output, b'\t')?;
+    println!(
+        "Wrote {} entries in {} data blocks ({} bytes)",
+        stats.entries, stats.data_blocks, stats.total_bytes
+    );
+    Ok(ExitCode::SUCCESS)
+}
+
+fn info(path: &Path) -> Result<ExitCode, CliError> {
+    let reader = SstReader::open(path)?;
+    let escape = |bytes: Option<&[u8]>| bytes.map_or("-".to_string(), |bytes| bytes.escape_ascii().to_string());
+
+    println!("format version:    {}", reader.version());
+    println!("file size:         {}", std::fs::metadata(path)?.len());
+    println!("data blocks:       {}", reader.index_entries()?.len());
+    println!("first key:         {}", escape(reader.first_key()));
+    println!("last key:          {}", escape(reader.last_key()));
+    if let Some(len) = reader.len() {
+        println!("entries:           {}", len);
+    }
+    if let Some(properties) = reader.properties() {
+        println!("total key bytes:   {}", properties.total_key_bytes);
+        println!("total value bytes: {}", properties.total_value_bytes);
+        println!("compression:       {:?}", properties.compression);
+        println!("checksum:          {:?}", properties.checksum);
+    }
+    if let Some(magic) = reader.application_magic() {
+        println!("application magic: {}", magic.escape_ascii());
+    }
+    for (name, value) in reader.user_metadata() {
+        println!("user.{}: {}", name, value.escape_ascii());
+    }
+    Ok(ExitCode::SUCCESS)
+}
 
-    Ok(())
-}
\ No newline at end of file
+// Decodes a string of hex digit pairs, returning None if it isn't one
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,190 @@
+use crate::error::{Result, SstError};
+use crate::properties::Properties;
+use crate::reader::{read_bytes, read_u32, read_u64};
+
+// Bumped whenever the serialised manifest changes
+const MANIFEST_VERSION: u32 = 1;
+
+/// The block boundaries and file-level metadata of an SST, returned by
+/// `SstReader::export_manifest`. A manifest is small next to the file it describes, so
+/// tooling can keep those of many files in memory, or cache them with `to_bytes`, to
+/// route lookups to files and blocks without opening a reader for each.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Manifest {
+    /// Format version of the file.
+    pub version: u32,
+    /// The smallest key in the file, or None if the file is empty.
+    pub first_key: Option<Vec<u8>>,
+    /// Number of entries in the file, tombstones included, or None for files written
+    /// before format version 8, which don't record it.
+    pub num_entries: Option<u64>,
+    /// Every data block of the file, in key order.
+    pub blocks: Vec<ManifestBlock>,
+    /// The file's properties block, or None for files written before format version 13.
+    pub properties: Option<Properties>,
+}
+
+/// A data block listed in a `Manifest`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestBlock {
+    /// A key `>=` every key in the block and `<` every key in the next block, as
+    /// recorded in the file's index. For the final block this is the file's last key.
+    pub last_key: Vec<u8>,
+    /// Offset of the block from the start of the file.
+    pub block_offset: u64,
+    /// Size of the block in bytes as stored, including its compression flag and trailer.
+    pub block_size: u64,
+}
+
+// Serialised as [manifest_version: u32][version: u32][first_key][num_entries][properties]
+//               [num_blocks: u64]([key_len: u32][last_key][block_offset: u64][block_size: u64])...
+// where first_key and properties are [present: u8]([len: u32][bytes]) and
+// num_entries is [present: u8]([num_entries: u64])
+impl Manifest {
+    /// Serialises the manifest for caching; `from_bytes` reads it back.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let put_optional = |bytes: &mut Vec<u8>, value: Option<&[u8]>| match value {
+            Some(value) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+                bytes.extend_from_slice(value);
+            }
+            None => bytes.push(0),
+        };
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&MANIFEST_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&self.version.to_le_bytes());
+        put_optional(&mut bytes, self.first_key.as_deref());
+        match self.num_entries {
+            Some(num_entries) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&num_entries.to_le_bytes());
+            }
+            None => bytes.push(0),
+        }
+        put_optional(&mut bytes, self.properties.as_ref().map(Properties::to_bytes).as_deref());
+        bytes.extend_from_slice(&(self.blocks.len() as u64).to_le_bytes());
+        for block in &self.blocks {
+            bytes.extend_from_slice(&(block.last_key.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&block.last_key);
+            bytes.extend_from_slice(&block.block_offset.to_le_bytes());
+            bytes.extend_from_slice(&block.block_size.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Parses a manifest serialised by `to_bytes`, failing with
+    /// `SstError::CorruptManifest` if it is truncated or from an unknown manifest version.
+    pub fn from_bytes(mut buf: &[u8]) -> Result<Manifest> {
+        let truncated = || SstError::CorruptManifest("manifest is truncated".to_string());
+        let read_present = |buf: &mut &[u8]| -> Result<bool> {
+            match read_bytes(buf, 1).ok_or_else(truncated)?[0] {
+                0 => Ok(false),
+                1 => Ok(true),
+                flag => Err(SstError::CorruptManifest(format!("invalid presence flag {}", flag))),
+            }
+        };
+        let read_optional = |buf: &mut &[u8]| -> Result<Option<Vec<u8>>> {
+            if !read_present(buf)? {
+                return Ok(None);
+            }
+            let len = read_u32(buf).ok_or_else(truncated)? as usize;
+            Ok(Some(read_bytes(buf, len).ok_or_else(truncated)?.to_vec()))
+        };
+
+        let manifest_version = read_u32(&mut buf).ok_or_else(truncated)?;
+        if manifest_version != MANIFEST_VERSION {
+            return Err(SstError::CorruptManifest(format!(
+                "manifest was saved by manifest version {}, not {}",
+                manifest_version, MANIFEST_VERSION
+            )));
+        }
+        let version = read_u32(&mut buf).ok_or_else(truncated)?;
+        let first_key = read_optional(&mut buf)?;
+        let num_entries = match read_present(&mut buf)? {
+            true => Some(read_u64(&mut buf).ok_or_else(truncated)?),
+            false => None,
+        };
+        let properties = read_optional(&mut buf)?
+            .map(|properties| Properties::from_bytes(&properties))
+            .transpose()?;
+
+        let num_blocks = read_u64(&mut buf).ok_or_else(truncated)?;
+        // Each block takes at least 20 bytes, so don't trust a larger count than the buffer holds
+        if num_blocks > buf.len() as u64 / 20 {
+            return Err(truncated());
+        }
+        let mut blocks = Vec::with_capacity(num_blocks as usize);
+        for _ in 0..num_blocks {
+            let key_len = read_u32(&mut buf).ok_or_else(truncated)? as usize;
+            blocks.push(ManifestBlock {
+                last_key: read_bytes(&mut buf, key_len).ok_or_else(truncated)?.to_vec(),
+                block_offset: read_u64(&mut buf).ok_or_else(truncated)?,
+                block_size: read_u64(&mut buf).ok_or_else(truncated)?,
+            });
+        }
+        if !buf.is_empty() {
+            return Err(SstError::CorruptManifest(format!("{} unexpected bytes after the blocks", buf.len())));
+        }
+
+        Ok(Manifest {
+            version,
+            first_key,
+            num_entries,
+            blocks,
+            properties,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::SstReader;
+    use crate::test_util::FRUIT;
+    use crate::writer::SstWriterBuilder;
+
+    fn exported(entries: &[(&str, &str)]) -> Manifest {
+        let mut bytes = Vec::new();
+        let mut writer = SstWriterBuilder::new()
+            .block_size(64)
+            .user_metadata(vec![("source".to_string(), b"test".to_vec())])
+            .build_with_writer(&mut bytes);
+        for (key, value) in entries {
+            writer.add(key.as_bytes(), value.as_bytes()).unwrap();
+        }
+        writer.finish().unwrap();
+        SstReader::from_bytes(bytes).unwrap().export_manifest().unwrap()
+    }
+
+    #[test]
+    fn manifests_round_trip() {
+        let manifest = exported(&FRUIT);
+        assert!(manifest.blocks.len() > 1);
+        assert_eq!(manifest.first_key.as_deref(), Some(FRUIT[0].0.as_bytes()));
+        assert_eq!(manifest.num_entries, Some(FRUIT.len() as u64));
+        assert!(manifest.properties.is_some());
+        assert_eq!(Manifest::from_bytes(&manifest.to_bytes()).unwrap(), manifest);
+
+        // Empty files, and the optional fields of older files
+        for manifest in [exported(&[]), Manifest::default()] {
+            assert_eq!(Manifest::from_bytes(&manifest.to_bytes()).unwrap(), manifest);
+        }
+    }
+
+    #[test]
+    fn damaged_manifests_are_refused() {
+        let bytes = exported(&FRUIT).to_bytes();
+        for len in 0..bytes.len() {
+            let result = Manifest::from_bytes(&bytes[..len]);
+            assert!(matches!(result, Err(SstError::CorruptManifest(_))), "{} bytes: {:?}", len, result);
+        }
+        let mut extended = bytes.clone();
+        extended.push(0);
+        assert!(matches!(Manifest::from_bytes(&extended), Err(SstError::CorruptManifest(_))));
+        let mut future = bytes.clone();
+        future[..4].copy_from_slice(&(MANIFEST_VERSION + 1).to_le_bytes());
+        assert!(matches!(Manifest::from_bytes(&future), Err(SstError::CorruptManifest(_))));
+    }
+}
@@ -0,0 +1,414 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, VecDeque};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::comparator::Comparator;
+use crate::error::Result;
+use crate::reader::{Entry, SstReader};
+use crate::writer::SstWriter;
+
+// The current entry of one input in a k-way merge
+struct HeapEntry<V> {
+    key: Vec<u8>,
+    value: V,
+    source: usize,
+    // The order of the keys, shared by every entry
+    comparator: Arc<dyn Comparator>,
+}
+
+// BinaryHeap is a max-heap, so order by smallest key first and,
+// for equal keys, by the input listed last (the newest) first
+impl<V> Ord for HeapEntry<V> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.comparator.compare(&other.key, &self.key).then(self.source.cmp(&other.source))
+    }
+}
+
+impl<V> PartialOrd for HeapEntry<V> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<V> PartialEq for HeapEntry<V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<V> Eq for HeapEntry<V> {}
+
+// Merges several entry streams, each sorted by `comparator`, into one sorted stream.
+// When a key appears in more than one input, only the entry from the input listed
+// last is returned.
+pub(crate) struct MergingIterator<I, V> {
+    inputs: Vec<I>,
+    comparator: Arc<dyn Comparator>,
+    heap: BinaryHeap<HeapEntry<V>>,
+    last_key: Option<Vec<u8>>,
+    started: bool,
+}
+
+impl<I, V> MergingIterator<I, V>
+where
+    I: Iterator<Item = Result<(Vec<u8>, V)>>,
+{
+    pub(crate) fn new(inputs: Vec<I>, comparator: Arc<dyn Comparator>) -> Self {
+        MergingIterator {
+            inputs,
+            comparator,
+            heap: BinaryHeap::new(),
+            last_key: None,
+            started: false,
+        }
+    }
+
+    // Pushes the next entry of `source` onto the heap, if it has one
+    fn advance(&mut self, source: usize) -> Result<()> {
+        if let Some(entry) = self.inputs[source].next() {
+            let (key, value) = entry?;
+            let comparator = Arc::clone(&self.comparator);
+            self.heap.push(HeapEntry { key, value, source, comparator });
+        }
+        Ok(())
+    }
+
+    fn next_entry(&mut self) -> Result<Option<(Vec<u8>, V)>> {
+        if !self.started {
+            self.started = true;
+            for source in 0..self.inputs.len() {
+                self.advance(source)?;
+            }
+        }
+
+        while let Some(entry) = self.heap.pop() {
+            self.advance(entry.source)?;
+
+            // The newest copy of a key is popped first, so skip any older ones
+            if self
+                .last_key
+                .as_ref()
+                .is_some_and(|last_key| self.comparator.compare(last_key, &entry.key).is_eq())
+            {
+                continue;
+            }
+            self.last_key = Some(entry.key.clone());
+            return Ok(Some((entry.key, entry.value)));
+        }
+        Ok(None)
+    }
+}
+
+impl<I, V> Iterator for MergingIterator<I, V>
+where
+    I: Iterator<Item = Result<(Vec<u8>, V)>>,
+{
+    type Item = Result<(Vec<u8>, V)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_entry().transpose()
+    }
+}
+
+// Where one input of `merge` has reached: either an entry of a decoded block, or the
+// start of a block not read yet, known only to hold keys greater than `key`
+struct Position {
+    key: Vec<u8>,
+    block_start: bool,
+    source: usize,
+}
+
+// BinaryHeap is a max-heap, so order by smallest key first. For equal keys, a block start
+// comes first, so any copy of the key in that block is decoded before the key is written,
+// and then the input listed last (the newest) comes first.
+impl Ord for Position {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .key
+            .cmp(&self.key)
+            .then(self.block_start.cmp(&other.block_start))
+            .then(self.source.cmp(&other.source))
+    }
+}
+
+impl PartialOrd for Position {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Position {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Position {}
+
+// The progress of one input through `merge`
+struct MergeInput<'a> {
+    reader: &'a SstReader,
+    // Whether its blocks can be copied into the output unchanged
+    copyable: bool,
+    next_block: usize,
+    // Entries of the current decoded block that haven't been merged yet
+    entries: VecDeque<(Vec<u8>, Entry)>,
+    // Last key of the block read last
+    last_block_key: Vec<u8>,
+}
+
+/// Merges several SST files into a single sorted SST at `output`.
+///
+/// When the same key appears in more than one input, the entry from the input
+/// listed later in `inputs` wins and only that copy is written. A tombstone
+/// shadows older values for its key and is itself written to the output, so
+/// it keeps hiding values in files that were not part of this merge.
+///
+/// A data block whose keys can't overlap any other input's remaining keys is copied
+/// into the output as stored, without decompressing or re-encoding it, so merging
+/// mostly disjoint files is far cheaper than rewriting them. Blocks are only copied
+/// from inputs in the current format written with the output's compression and
+/// checksum, and never when they hold blob values; other blocks are merged entry by entry.
+pub fn merge(inputs: &[&Path], output: &Path) -> Result<()> {
+    let readers = inputs
+        .iter()
+        .map(|path| SstReader::open(path))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut writer = SstWriter::new(output)?;
+    let mut inputs: Vec<MergeInput> = readers
+        .iter()
+        .map(|reader| MergeInput {
+            reader,
+            copyable: writer.can_copy_blocks_from(reader),
+            next_block: 0,
+            entries: VecDeque::new(),
+            last_block_key: Vec::new(),
+        })
+        .collect();
+
+    let mut heap = BinaryHeap::new();
+    for (source, input) in inputs.iter().enumerate() {
+        if let Some(first_key) = input.reader.first_key() {
+            heap.push(Position {
+                key: first_key.to_vec(),
+                block_start: true,
+                source,
+            });
+        }
+    }
+
+    let mut last_key: Option<Vec<u8>> = None;
+    while let Some(position) = heap.pop() {
+        let input = &mut inputs[position.source];
+        let reader = input.reader;
+
+        if position.block_start {
+            let block_idx = input.next_block;
+            input.next_block += 1;
+            // Every other input's remaining keys are at least the key of the next position,
+            // so a block whose index key sorts before it overlaps none of them
+            let index_key = reader.index_key(block_idx)?;
+            let disjoint = heap.peek().is_none_or(|next| index_key < next.key.as_slice());
+            let raw_block = if input.copyable && disjoint {
+                reader.read_raw_block_at(block_idx)?
+            } else {
+                None
+            };
+            match raw_block {
+                Some(block) => {
+                    input.last_block_key = block.keys.last().cloned().unwrap_or_default();
+                    writer.copy_block(&block)?;
+                }
+                None => {
+                    input.entries = reader.read_block_entries(block_idx)?.into();
+                    input.last_block_key = input.entries.back().map(|(key, _)| key.clone()).unwrap_or_default();
+                }
+            }
+        } else if let Some((key, entry)) = input.entries.pop_front() {
+            // The newest copy of a key is popped first, so skip any older ones
+            if last_key.as_ref() != Some(&key) {
+                match entry {
+                    Entry::Value(value) => writer.add(&key, &value)?,
+                    Entry::Tombstone => writer.delete(&key)?,
+                }
+                last_key = Some(key);
+            }
+        }
+
+        // Queue the input's next entry, or the start of its next block
+        if let Some((key, _)) = input.entries.front() {
+            heap.push(Position {
+                key: key.clone(),
+                block_start: false,
+                source: position.source,
+            });
+        } else if input.next_block < reader.num_blocks() {
+            // The next block's keys are greater than the previous block's index key, unless
+            // both are in a span of a sparse index, whose blocks share their index key
+            let key = match reader.starts_span(input.next_block)? {
+                true => reader.index_key(input.next_block - 1)?.to_vec(),
+                false => input.last_block_key.clone(),
+            };
+            heap.push(Position {
+                key,
+                block_start: true,
+                source: position.source,
+            });
+        }
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{TempPath, strings, write_sst};
+
+    fn key(i: u32) -> Vec<u8> {
+        format!("key{:05}", i).into_bytes()
+    }
+
+    fn value(i: u32, source: &str) -> Vec<u8> {
+        format!("{} {:0>100}", source, i).into_bytes()
+    }
+
+    // Writes keys `range`, filtered by `keep`, to an SST with the default options, each
+    // with a value naming `source`
+    fn write_keys(path: &Path, source: &str, range: std::ops::Range<u32>, keep: impl Fn(u32) -> bool) {
+        let mut writer = SstWriter::new(path).unwrap();
+        for i in range.filter(|&i| keep(i)) {
+            writer.add(&key(i), &value(i, source)).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    // Returns the data blocks of the SST at `path` as stored
+    fn raw_blocks(path: &Path) -> Vec<Vec<u8>> {
+        let reader = SstReader::open(path).unwrap();
+        let entries = reader.index_entries().unwrap();
+        entries.iter().map(|entry| reader.read_raw_block(entry.block_offset, entry.block_size).unwrap()).collect()
+    }
+
+    #[test]
+    fn later_inputs_win_and_duplicates_are_dropped() {
+        let (a, b, c) = (TempPath::new("merge-a.sst"), TempPath::new("merge-b.sst"), TempPath::new("merge-c.sst"));
+        write_sst(&a, &[("apple", "a"), ("cherry", "a"), ("elderberry", "a")]);
+        write_sst(&b, &[("banana", "b"), ("cherry", "b"), ("date", "b")]);
+        write_sst(&c, &[("cherry", "c"), ("elderberry", "c"), ("fig", "c")]);
+        let output = TempPath::new("merge-out.sst");
+        merge(&[&a, &b, &c], &output).unwrap();
+
+        let reader = SstReader::open(&output).unwrap();
+        let expected = [
+            ("apple", "a"),
+            ("banana", "b"),
+            ("cherry", "c"),
+            ("date", "b"),
+            ("elderberry", "c"),
+            ("fig", "c"),
+        ];
+        let expected: Vec<_> = expected.iter().map(|&(k, v)| (k.to_string(), v.to_string())).collect();
+        assert_eq!(strings(reader.iter()), expected);
+
+        // Listing the inputs the other way round reverses the precedence
+        merge(&[&c, &b, &a], &output).unwrap();
+        let reader = SstReader::open(&output).unwrap();
+        assert_eq!(reader.get(b"cherry").unwrap(), Some(b"a".to_vec()));
+        assert_eq!(reader.get(b"elderberry").unwrap(), Some(b"a".to_vec()));
+        assert_eq!(reader.iter().count(), 6);
+    }
+
+    #[test]
+    fn disjoint_inputs_are_copied_block_for_block() {
+        let (a, b) = (TempPath::new("passthrough-a.sst"), TempPath::new("passthrough-b.sst"));
+        write_keys(&a, "a", 0..1000, |_| true);
+        write_keys(&b, "b", 1000..2000, |_| true);
+        let output = TempPath::new("passthrough-out.sst");
+        // The inputs' order doesn't matter when no key is in both
+        merge(&[&b, &a], &output).unwrap();
+
+        let expected = [raw_blocks(&a), raw_blocks(&b)].concat();
+        assert!(expected.len() > 20);
+        assert_eq!(raw_blocks(&output), expected);
+        let reader = SstReader::open(&output).unwrap();
+        reader.verify().unwrap();
+        assert_eq!(reader.iter().count(), 2000);
+        assert_eq!(reader.get(&key(1500)).unwrap(), Some(value(1500, "b")));
+
+        // A lone input comes out as it went in
+        merge(&[&a], &output).unwrap();
+        assert_eq!(std::fs::read(&output).unwrap(), std::fs::read(&a).unwrap());
+    }
+
+    #[test]
+    fn overlapping_inputs_are_merged_entry_by_entry() {
+        let (a, b) = (TempPath::new("overlap-a.sst"), TempPath::new("overlap-b.sst"));
+        // Only keys 1000 to 1999 overlap: blocks of `a` before them and of `b` after them
+        // can still be copied
+        write_keys(&a, "a", 0..2000, |i| i < 1000 || i % 2 == 0);
+        write_keys(&b, "b", 1000..3000, |i| i >= 2000 || i % 3 == 0);
+        let output = TempPath::new("overlap-out.sst");
+        merge(&[&a, &b], &output).unwrap();
+
+        let reader = SstReader::open(&output).unwrap();
+        reader.verify().unwrap();
+        let expected: Vec<_> = (0..3000)
+            .filter_map(|i| match i {
+                _ if i >= 2000 || (i >= 1000 && i % 3 == 0) => Some((key(i), value(i, "b"))),
+                _ if i < 1000 || i % 2 == 0 => Some((key(i), value(i, "a"))),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(reader.iter().map(Result::unwrap).collect::<Vec<_>>(), expected);
+
+        let blocks = raw_blocks(&output);
+        assert_eq!(blocks.first(), raw_blocks(&a).first());
+        assert_eq!(blocks.last(), raw_blocks(&b).last());
+    }
+
+    #[test]
+    fn tombstones_in_newer_inputs_hide_older_values() {
+        let (older, newer) = (TempPath::new("tombstone-older.sst"), TempPath::new("tombstone-newer.sst"));
+        write_keys(&older, "older", 0..1000, |_| true);
+        // Every tenth key is deleted in the newer file, which also overwrites a few others
+        let mut writer = SstWriter::new(&newer).unwrap();
+        for i in (0..1000).filter(|i| i % 5 == 0) {
+            match i % 10 {
+                0 => writer.delete(&key(i)).unwrap(),
+                _ => writer.add(&key(i), &value(i, "newer")).unwrap(),
+            }
+        }
+        writer.finish().unwrap();
+        let expected: Vec<_> = (0..1000)
+            .filter(|i| i % 10 != 0)
+            .map(|i| (key(i), value(i, if i % 5 == 0 { "newer" } else { "older" })))
+            .collect();
+
+        let output = TempPath::new("tombstone-out.sst");
+        merge(&[&older, &newer], &output).unwrap();
+        let reader = SstReader::open(&output).unwrap();
+        reader.verify().unwrap();
+        assert_eq!(reader.iter().map(Result::unwrap).collect::<Vec<_>>(), expected);
+        for i in (0..1000).step_by(10) {
+            assert_eq!(reader.get(&key(i)).unwrap(), None, "key {}", i);
+            // The tombstone is kept, so it goes on hiding the key in files outside the merge
+            assert_eq!(reader.get_entry(&key(i)).unwrap(), Some(Entry::Tombstone), "key {}", i);
+        }
+
+        let readers = [SstReader::open(&older).unwrap(), SstReader::open(&newer).unwrap()];
+        let comparator = Arc::clone(readers[0].comparator_ref());
+        let merged = MergingIterator::new(readers.iter().map(SstReader::iter_entries).collect(), comparator);
+        let (mut values, mut tombstones) = (Vec::new(), Vec::new());
+        for entry in merged {
+            match entry.unwrap() {
+                (key, Entry::Value(value)) => values.push((key, value)),
+                (key, Entry::Tombstone) => tombstones.push(key),
+            }
+        }
+        assert_eq!(values, expected);
+        assert_eq!(tombstones, (0..1000).step_by(10).map(key).collect::<Vec<_>>());
+    }
+}
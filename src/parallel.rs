@@ -0,0 +1,233 @@
+use std::fs;
+use std::num::NonZeroUsize;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+
+use crate::error::Result;
+use crate::reader::{Entry, SstReader};
+use crate::writer::{SstWriter, SstWriterBuilder, WriteStats};
+
+// Distinguishes the shard files of builders in the same process
+static NEXT_SHARD_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Builds an SST from sorted input on several threads at once.
+///
+/// The input is split into shards covering consecutive key ranges, and each shard is
+/// written to a temporary SST on its own thread. The shards are then concatenated into
+/// the output: since their ranges are disjoint and in order, each data block is copied
+/// as stored and only the index, bloom filter and properties are rebuilt, with every
+/// block's offset rebased onto its place in the output. Blocks holding blob values
+/// can't be copied, as blob pointers only make sense within their own file, so they
+/// are rewritten entry by entry. Shard files are deleted once the build is done.
+pub struct ParallelSstBuilder {
+    path: PathBuf,
+    options: SstWriterBuilder,
+    threads: usize,
+    temp_dir: PathBuf,
+}
+
+impl ParallelSstBuilder {
+    /// Creates a builder for the given path with the default `SstWriter` options.
+    pub fn new(path: &Path) -> Self {
+        Self::with_options(path, SstWriterBuilder::new())
+    }
+
+    /// Creates a builder for the given path that writes the output with `options`.
+    pub fn with_options(path: &Path, options: SstWriterBuilder) -> Self {
+        ParallelSstBuilder {
+            path: path.to_path_buf(),
+            options,
+            threads: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            temp_dir: std::env::temp_dir(),
+        }
+    }
+
+    /// Sets how many shards `build` splits its input into, each written on its own
+    /// thread (the number of available CPUs by default).
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Sets the directory shard files are written to (the system temporary directory
+    /// by default).
+    pub fn temp_dir(mut self, dir: &Path) -> Self {
+        self.temp_dir = dir.to_path_buf();
+        self
+    }
+
+    /// Writes `entries`, which must be sorted by key with no key repeated, splitting
+    /// them into equal shards, one per thread.
+    pub fn build<K, V>(self, entries: &[(K, V)]) -> Result<WriteStats>
+    where
+        K: AsRef<[u8]> + Sync,
+        V: AsRef<[u8]> + Sync,
+    {
+        let chunk_size = entries.len().div_ceil(self.threads).max(1);
+        let shards = entries
+            .chunks(chunk_size)
+            .map(|chunk| chunk.iter().map(|(key, value)| (key.as_ref(), value.as_ref())))
+            .collect();
+        self.build_shards(shards)
+    }
+
+    /// Writes each shard on its own thread, then concatenates them in order. Every
+    /// shard must be sorted by key, and all of its keys must be greater than those of
+    /// the shards before it; otherwise `SstError::KeysOutOfOrder` is returned.
+    pub fn build_shards<I, K, V>(self, shards: Vec<I>) -> Result<WriteStats>
+    where
+        I: IntoIterator<Item = (K, V)> + Send,
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        let paths: Vec<PathBuf> = (0..shards.len()).map(|_| self.shard_path()).collect();
+        let result = self.write_shards(&paths, shards).and_then(|()| self.concatenate(&paths));
+        for path in &paths {
+            let _ = fs::remove_file(path);
+        }
+        result
+    }
+
+    fn shard_path(&self) -> PathBuf {
+        self.temp_dir.join(format!(
+            "sst-shard-{}-{}.sst",
+            process::id(),
+            NEXT_SHARD_ID.fetch_add(1, Ordering::Relaxed)
+        ))
+    }
+
+    // Writes each shard to its path on its own thread. Shards are read back once and
+    // then deleted, so they skip the bloom filter (rebuilt from the copied blocks' keys),
+    // its sidecar and the sync to disk, but keep the output's block options so their
+    // blocks can be copied.
+    fn write_shards<I, K, V>(&self, paths: &[PathBuf], shards: Vec<I>) -> Result<()>
+    where
+        I: IntoIterator<Item = (K, V)> + Send,
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        thread::scope(|scope| {
+            let workers: Vec<_> = paths
+                .iter()
+                .zip(shards)
+                .map(|(path, shard)| {
+                    let options = self.options.clone().bloom_bits_per_key(0).bloom_sidecar(false).sync_on_finish(false);
+                    scope.spawn(move || -> Result<()> {
+                        let mut writer = options.build(path)?;
+                        for (key, value) in shard {
+                            writer.add(key.as_ref(), value.as_ref())?;
+                        }
+                        writer.finish()?;
+                        Ok(())
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .try_for_each(|worker| worker.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+        })
+    }
+
+    // Writes the output from the shards in order, copying their blocks where possible
+    fn concatenate(&self, paths: &[PathBuf]) -> Result<WriteStats> {
+        let mut writer = self.options.clone().build(&self.path)?;
+        for path in paths {
+            let reader = SstReader::open_with_comparator(path, self.options.comparator_ref().clone())?;
+            copy_blocks(&reader, &mut writer)?;
+        }
+        writer.finish()
+    }
+}
+
+// Appends every block of `reader` to `writer`, as stored when the writer can take it
+// that way and entry by entry otherwise
+fn copy_blocks(reader: &SstReader, writer: &mut SstWriter) -> Result<()> {
+    let copyable = writer.can_copy_blocks_from(reader);
+    for block_idx in 0..reader.num_blocks() {
+        let raw_block = if copyable { reader.read_raw_block_at(block_idx)? } else { None };
+        if let Some(block) = raw_block {
+            writer.copy_block(&block)?;
+            continue;
+        }
+        for (key, entry) in reader.read_block_entries(block_idx)? {
+            match entry {
+                Entry::Value(value) => writer.add(&key, &value)?,
+                Entry::Tombstone => writer.delete(&key)?,
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bloom;
+    use crate::error::SstError;
+    use crate::test_util::TempPath;
+
+    #[test]
+    fn shards_leave_no_files_behind() {
+        let temp_dir = std::env::temp_dir().join(format!("sst-test-{}-shards", process::id()));
+        fs::create_dir_all(&temp_dir).unwrap();
+        let path = TempPath::new("parallel-sidecar.sst");
+        let sidecar = bloom::sidecar_path(&path);
+        let entries: Vec<_> = (0..1000).map(|i| (format!("key{:04}", i), format!("value{}", i))).collect();
+
+        let options = SstWriterBuilder::new().block_size(256).bloom_sidecar(true);
+        let stats = ParallelSstBuilder::with_options(&path, options).threads(4).temp_dir(&temp_dir).build(&entries);
+        // Only the output has a sidecar; the shards, and any sidecars of theirs, are gone
+        let leftovers = fs::read_dir(&temp_dir).unwrap().count();
+        fs::remove_dir_all(&temp_dir).unwrap();
+        assert_eq!(stats.unwrap().entries, 1000);
+        assert_eq!(leftovers, 0);
+
+        let reader = SstReader::open_with_bloom(&path, &sidecar).unwrap();
+        fs::remove_file(&sidecar).unwrap();
+        reader.verify().unwrap();
+        assert_eq!(reader.get(b"key0500").unwrap(), Some(b"value500".to_vec()));
+    }
+
+    #[test]
+    fn parallel_builds_read_the_same_as_single_threaded_ones() {
+        let path = TempPath::new("parallel.sst");
+        let single_path = TempPath::new("parallel-single.sst");
+        // Every fifth value is long enough to be a blob, so blocks holding one are rewritten
+        let entries: Vec<_> = (0..500)
+            .map(|i| (format!("key{:04}", i), format!("value{}", i).repeat(if i % 5 == 0 { 20 } else { 1 })))
+            .collect();
+
+        for blob_threshold in [0, 64] {
+            let options = SstWriterBuilder::new().block_size(256).blob_threshold(blob_threshold);
+            let mut writer = options.clone().build(&single_path).unwrap();
+            for (key, value) in &entries {
+                writer.add(key.as_bytes(), value.as_bytes()).unwrap();
+            }
+            writer.finish().unwrap();
+            let single = SstReader::open(&single_path).unwrap();
+            let expected: Vec<_> = single.iter_entries().map(Result::unwrap).collect();
+            assert_eq!(expected.len(), entries.len());
+
+            for threads in [1, 2, 3, 8, entries.len() + 10] {
+                let stats = ParallelSstBuilder::with_options(&path, options.clone()).threads(threads).build(&entries);
+                assert_eq!(stats.unwrap().entries, entries.len() as u64, "{} threads", threads);
+                let reader = SstReader::open(&path).unwrap();
+                reader.verify().unwrap();
+                let actual: Vec<_> = reader.iter_entries().map(Result::unwrap).collect();
+                assert_eq!(actual, expected, "{} threads, blob threshold {}", threads, blob_threshold);
+                assert_eq!(reader.get(b"key0250").unwrap(), Some(entries[250].1.clone().into_bytes()));
+            }
+        }
+    }
+
+    #[test]
+    fn overlapping_shards_are_keys_out_of_order() {
+        let path = TempPath::new("parallel-overlap.sst");
+        let shards = vec![vec![("a", "1"), ("c", "3")], vec![("b", "2"), ("d", "4")]];
+        let result = ParallelSstBuilder::new(&path).build_shards(shards);
+        assert!(matches!(result, Err(SstError::KeysOutOfOrder)), "{:?}", result);
+    }
+}
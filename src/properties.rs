@@ -0,0 +1,223 @@
+use crate::checksum::Checksum;
+use crate::compression::Compression;
+use crate::error::{Result, SstError};
+
+// Names of the properties in the properties block
+const NUM_ENTRIES: &str = "sst.num_entries";
+const MIN_KEY: &str = "sst.min_key";
+const MAX_KEY: &str = "sst.max_key";
+const TOTAL_KEY_BYTES: &str = "sst.total_key_bytes";
+const TOTAL_VALUE_BYTES: &str = "sst.total_value_bytes";
+const COMPRESSION: &str = "sst.compression";
+const CHECKSUM: &str = "sst.checksum";
+const COMPARATOR: &str = "sst.comparator";
+// Prefix of the names of user metadata, keeping them apart from the crate's own properties
+const USER_PREFIX: &str = "user.";
+
+/// Metadata describing an SST file, recorded in its properties block when it is written.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Properties {
+    /// Number of entries in the file, tombstones included.
+    pub num_entries: u64,
+    /// The smallest key in the file, or None if the file is empty.
+    pub min_key: Option<Vec<u8>>,
+    /// The largest key in the file, or None if the file is empty.
+    pub max_key: Option<Vec<u8>>,
+    /// Total length of every key.
+    pub total_key_bytes: u64,
+    /// Total length of every value, including values stored as blobs. Tombstones have no value.
+    pub total_value_bytes: u64,
+    /// Compression the data blocks were written with.
+    pub compression: Compression,
+    /// Checksum the data blocks were written with.
+    pub checksum: Checksum,
+    /// Name of the comparator the keys are ordered by, or None for the default
+    /// `BytewiseComparator`.
+    pub comparator: Option<String>,
+    /// Application-defined metadata set with `SstWriterBuilder::user_metadata`, sorted by name.
+    pub user_metadata: Vec<(String, Vec<u8>)>,
+}
+
+impl Properties {
+    // Serialises the properties as a list of named values, sorted by name.
+    // Readers skip names they don't know, so properties can be added without a new format version.
+    // Format: [num_properties: u32]([name_len: u32][name][value_len: u32][value])...
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let compression = match self.compression {
+            Compression::None => "none".to_string(),
+            Compression::Zstd { level } => format!("zstd:{}", level),
+            Compression::Lz4 => "lz4".to_string(),
+            Compression::Snappy => "snappy".to_string(),
+        };
+        let checksum = match self.checksum {
+            Checksum::None => "none",
+            Checksum::Crc32 => "crc32",
+            Checksum::XxHash64 => "xxhash64",
+        };
+
+        let mut properties: Vec<(String, Vec<u8>)> = vec![
+            (CHECKSUM.to_string(), checksum.as_bytes().to_vec()),
+            (COMPRESSION.to_string(), compression.into_bytes()),
+            (NUM_ENTRIES.to_string(), self.num_entries.to_le_bytes().to_vec()),
+            (TOTAL_KEY_BYTES.to_string(), self.total_key_bytes.to_le_bytes().to_vec()),
+            (TOTAL_VALUE_BYTES.to_string(), self.total_value_bytes.to_le_bytes().to_vec()),
+        ];
+        if let (Some(min_key), Some(max_key)) = (&self.min_key, &self.max_key) {
+            properties.push((MAX_KEY.to_string(), max_key.clone()));
+            properties.push((MIN_KEY.to_string(), min_key.clone()));
+        }
+        if let Some(comparator) = &self.comparator {
+            properties.push((COMPARATOR.to_string(), comparator.as_bytes().to_vec()));
+        }
+        for (name, value) in &self.user_metadata {
+            properties.push((format!("{}{}", USER_PREFIX, name), value.clone()));
+        }
+        properties.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&(properties.len() as u32).to_le_bytes());
+        for (name, value) in properties {
+            bytes.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&value);
+        }
+        bytes
+    }
+
+    // Parses a properties block, leaving properties it doesn't hold at their defaults
+    pub(crate) fn from_bytes(mut buf: &[u8]) -> Result<Properties> {
+        let corrupt = |msg: &str| SstError::CorruptProperties(msg.to_string());
+        let truncated = || corrupt("properties block is truncated");
+
+        let mut properties = Properties::default();
+        let num_properties = read_u32(&mut buf).ok_or_else(truncated)?;
+        for _ in 0..num_properties {
+            let name = read_field(&mut buf).ok_or_else(truncated)?;
+            let value = read_field(&mut buf).ok_or_else(truncated)?;
+
+            let invalid = || corrupt(&format!("invalid value for {}", String::from_utf8_lossy(name)));
+            let as_u64 = || value.try_into().map(u64::from_le_bytes).map_err(|_| invalid());
+            let name = std::str::from_utf8(name).unwrap_or_default();
+            if let Some(name) = name.strip_prefix(USER_PREFIX) {
+                properties.user_metadata.push((name.to_string(), value.to_vec()));
+                continue;
+            }
+            match name {
+                NUM_ENTRIES => properties.num_entries = as_u64()?,
+                MIN_KEY => properties.min_key = Some(value.to_vec()),
+                MAX_KEY => properties.max_key = Some(value.to_vec()),
+                TOTAL_KEY_BYTES => properties.total_key_bytes = as_u64()?,
+                TOTAL_VALUE_BYTES => properties.total_value_bytes = as_u64()?,
+                COMPRESSION => {
+                    properties.compression = match value {
+                        b"none" => Compression::None,
+                        b"lz4" => Compression::Lz4,
+                        b"snappy" => Compression::Snappy,
+                        _ => {
+                            let level = value.strip_prefix(b"zstd:").ok_or_else(invalid)?;
+                            let level = std::str::from_utf8(level).ok().and_then(|level| level.parse().ok());
+                            Compression::Zstd { level: level.ok_or_else(invalid)? }
+                        }
+                    }
+                }
+                COMPARATOR => {
+                    let comparator = std::str::from_utf8(value).map_err(|_| invalid())?;
+                    properties.comparator = Some(comparator.to_string());
+                }
+                CHECKSUM => {
+                    properties.checksum = match value {
+                        b"none" => Checksum::None,
+                        b"crc32" => Checksum::Crc32,
+                        b"xxhash64" => Checksum::XxHash64,
+                        _ => return Err(invalid()),
+                    }
+                }
+                // Written by a newer version of this library
+                _ => {}
+            }
+        }
+        Ok(properties)
+    }
+}
+
+fn read_u32(buf: &mut &[u8]) -> Option<u32> {
+    let (bytes, rest) = buf.split_at_checked(4)?;
+    *buf = rest;
+    Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+// Reads a [len: u32][bytes] field from the front of `buf`
+fn read_field<'a>(buf: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let len = read_u32(buf)? as usize;
+    let (bytes, rest) = buf.split_at_checked(len)?;
+    *buf = rest;
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::{Entry, SstReader};
+    use crate::writer::SstWriterBuilder;
+
+    #[test]
+    fn written_properties_match_a_scan_of_the_file() {
+        let mut bytes = Vec::new();
+        let mut writer = SstWriterBuilder::new()
+            .block_size(256)
+            .blob_threshold(200)
+            .compression(Compression::Snappy)
+            .checksum(Checksum::Crc32)
+            .build_with_writer(&mut bytes);
+        for i in 0..1000usize {
+            let key = format!("key{:05}", i);
+            match i % 10 {
+                0 => writer.delete(key.as_bytes()).unwrap(),
+                5 => writer.add(key.as_bytes(), &vec![b'x'; 300 + i]).unwrap(),
+                _ => writer.add(key.as_bytes(), &vec![b'y'; i % 17]).unwrap(),
+            }
+        }
+        writer.finish().unwrap();
+        let reader = SstReader::from_bytes(bytes).unwrap();
+
+        let mut scanned = Properties {
+            compression: Compression::Snappy,
+            checksum: Checksum::Crc32,
+            ..Properties::default()
+        };
+        for entry in reader.iter_entries() {
+            let (key, entry) = entry.unwrap();
+            scanned.num_entries += 1;
+            scanned.total_key_bytes += key.len() as u64;
+            if let Entry::Value(value) = entry {
+                scanned.total_value_bytes += value.len() as u64;
+            }
+            scanned.min_key.get_or_insert_with(|| key.clone());
+            scanned.max_key = Some(key);
+        }
+        assert_eq!(reader.properties(), Some(&scanned));
+        assert_eq!(Properties::from_bytes(&scanned.to_bytes()).unwrap(), scanned);
+    }
+
+    #[test]
+    fn user_metadata_round_trips() {
+        let mut bytes = Vec::new();
+        let metadata = vec![
+            ("schema_version".to_string(), b"2".to_vec()),
+            ("owner".to_string(), b"billing".to_vec()),
+            ("schema_version".to_string(), b"3".to_vec()),
+        ];
+        let mut writer = SstWriterBuilder::new().user_metadata(metadata).build_with_writer(&mut bytes);
+        writer.add(b"key", b"value").unwrap();
+        writer.finish().unwrap();
+
+        let reader = SstReader::from_bytes(bytes).unwrap();
+        // Sorted by name, with the last value set for a name
+        let expected = [("owner".to_string(), b"billing".to_vec()), ("schema_version".to_string(), b"3".to_vec())];
+        assert_eq!(reader.user_metadata(), expected);
+        assert_eq!(reader.get(b"key").unwrap(), Some(b"value".to_vec()));
+        let properties = reader.properties().unwrap();
+        assert_eq!(Properties::from_bytes(&properties.to_bytes()).unwrap(), *properties);
+    }
+}
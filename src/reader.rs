@@ -1,110 +1,3194 @@
-use std::fs::File;
-use std::io::{self, Read, Seek, SeekFrom};
-use std::path::Path;
+use std::borrow::Cow;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Cursor, Read, Seek, SeekFrom, Write};
+use std::ops::Bound;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::thread;
+
+use memmap2::Mmap;
+
+use crate::base64;
+use crate::bloom;
+use crate::cache::{BlockCache, CacheStats};
+use crate::checksum::{self, XxHash64};
+use crate::comparator::{self, BytewiseComparator, Comparator, InternalKeyComparator};
+use crate::compression;
+use crate::error::{Result, SstError};
+use crate::iter::{SstBlockIterator, SstEntryIterator, SstIterator, SstRevIterator};
+use crate::format::{
+    FILE_CHECKSUM_END, Footer, HASH_BUCKET_COLLISION, HASH_BUCKET_EMPTY, HASH_INDEX_FLAG, HEADER_SIZE, INDEX_TYPE_FLAT,
+    INDEX_TYPE_PARTITIONED, MAGIC, VALUE_TYPE_BLOB, VALUE_TYPE_TOMBSTONE, VALUE_TYPE_VALUE,
+};
+use crate::manifest::{Manifest, ManifestBlock};
+use crate::properties::Properties;
+use crate::varint;
+
+/// The entry stored for a key: either a value or a tombstone recording its deletion.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Entry {
+    Value(Vec<u8>),
+    Tombstone,
+}
+
+// A value as stored in a data block
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum RawValue<'a> {
+    Inline(&'a [u8]),
+    // A large value stored outside the data blocks (version 9+)
+    Blob { offset: u64, len: u64 },
+    Tombstone,
+}
+
+// A data block read to be copied into another file unchanged
+pub(crate) struct RawBlock {
+    // The block as stored, with its compression flag and trailer
+    pub(crate) bytes: Vec<u8>,
+    pub(crate) keys: Vec<Vec<u8>>,
+    // Total length of the block's values
+    pub(crate) value_bytes: u64,
+}
+
+// A decoded block entry: its key, its stored value and the offset of the next entry
+pub(crate) type DecodedEntry<'a> = (Vec<u8>, RawValue<'a>, usize);
+
+// A decoded data block split into its parts
+pub(crate) struct BlockParts<'a> {
+    // The entries, including the num_entries header
+    pub(crate) entries: &'a [u8],
+    // Offsets of the restart points within `entries`
+    pub(crate) restarts: Vec<usize>,
+    // Buckets mapping key hashes to restart intervals (version 12+, when written)
+    pub(crate) hash_index: Option<&'a [u8]>,
+}
 
 // A deserialized representation of an index entry
 #[derive(Debug)]
 struct IndexEntryInfo {
+    // A separator between this block's keys and the next block's; for the
+    // final block (and in files written before separators were shortened)
+    // this is the block's exact last key. Blocks a sparse index doesn't sample
+    // share the key of their span, which only separates it from the next span.
     last_key: Vec<u8>,
     block_offset: u64,
     block_size: u64,
+    // Number of entries in this block and all blocks before it (version 10+, otherwise 0)
+    cumulative_entries: u64,
+    // The block's exact first key (version 18+), except for blocks a sparse index doesn't sample
+    first_key: Option<Vec<u8>>,
+}
+
+/// A data block as recorded in the index of an SST, returned by `SstReader::index_entries`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexEntryMeta<'a> {
+    /// A key `>=` every key in the block and `<` every key in the next block. For the
+    /// final block, and in files written before index keys were shortened, this is the
+    /// block's last key. In a sparse index, blocks share the key of their span, which
+    /// is only `<` every key in the next span.
+    pub last_key: &'a [u8],
+    /// Offset of the block from the start of the file.
+    pub block_offset: u64,
+    /// Size of the block in bytes as stored, including its compression flag and trailer.
+    pub block_size: u64,
+    /// The block's first key, or None for blocks a sparse index doesn't sample and for
+    /// files written before format version 18, which don't record it.
+    pub first_key: Option<&'a [u8]>,
+}
+
+impl<'a> From<&'a IndexEntryInfo> for IndexEntryMeta<'a> {
+    fn from(info: &'a IndexEntryInfo) -> Self {
+        IndexEntryMeta {
+            last_key: &info.last_key,
+            block_offset: info.block_offset,
+            block_size: info.block_size,
+            first_key: info.first_key.as_deref(),
+        }
+    }
+}
+
+// A partition of the index, covering a run of consecutive data blocks. Partitioned
+// indexes (version 15+) are read one partition at a time as lookups reach them; a flat
+// index is held as a single partition, loaded when the file is opened unless it's opened lazily.
+#[derive(Debug)]
+struct IndexPartition {
+    // The index key of the partition's last block
+    last_key: Vec<u8>,
+    offset: u64,
+    size: u64,
+    // Number of entries in this partition's blocks and all blocks before them
+    cumulative_entries: u64,
+    // Position of the partition's first block among all blocks
+    first_block: usize,
+    entries: OnceLock<Vec<IndexEntryInfo>>,
+}
+
+// Where the bytes of an SST file are read from
+enum Backend {
+    // The file, and the offset and length of the SST within it when it was opened.
+    // Offsets into the SST are rebased onto `start` when reading the file.
+    File { file: File, start: u64, len: u64 },
+    Mmap(Mmap),
+    Memory(Vec<u8>),
+    // The header of a file and its metadata from `start` to its end at `len`, without the
+    // data blocks and blobs between them, which fail to read. `AsyncSstReader` reads the
+    // blocks itself and uses a reader over this backend for the rest.
+    #[cfg(feature = "tokio")]
+    Tail { header: Vec<u8>, tail: Vec<u8>, start: u64, len: u64 },
+}
+
+impl Backend {
+    fn file(file: File) -> Result<Backend> {
+        let len = file.metadata()?.len();
+        Ok(Backend::File { file, start: 0, len })
+    }
+
+    // Reads the SST occupying `len` bytes from `start` in a larger file
+    fn region(file: File, start: u64, len: u64) -> Result<Backend> {
+        let file_len = file.metadata()?.len();
+        if start.checked_add(len).is_none_or(|end| end > file_len) {
+            return Err(SstError::UnexpectedEof);
+        }
+        Ok(Backend::File { file, start, len })
+    }
+
+    fn read_footer(&mut self) -> Result<Footer> {
+        match self {
+            Backend::File { file, start, len } => {
+                Footer::read_from(&mut FileRegion { file, start: *start, len: *len, pos: 0 })
+            }
+            Backend::Mmap(mmap) => Footer::read_from(&mut Cursor::new(&mmap[..])),
+            Backend::Memory(bytes) => Footer::read_from(&mut Cursor::new(&bytes[..])),
+            #[cfg(feature = "tokio")]
+            Backend::Tail { tail, .. } => Footer::read_from(&mut Cursor::new(&tail[..])),
+        }
+    }
+
+    // Length of the SST in bytes
+    fn len(&self) -> u64 {
+        match self {
+            Backend::File { len, .. } => *len,
+            Backend::Mmap(mmap) => mmap.len() as u64,
+            Backend::Memory(bytes) => bytes.len() as u64,
+            #[cfg(feature = "tokio")]
+            Backend::Tail { len, .. } => *len,
+        }
+    }
+
+    // Reads `len` bytes starting at `offset`. Positioned reads leave the file
+    // cursor alone, so concurrent reads through a shared reader don't interfere.
+    // Sizes come from the file itself, so a range past its end is rejected before
+    // a buffer is allocated for it, however large a corrupt size claims to be.
+    fn read(&self, offset: u64, len: u64) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.read_into(offset, len, &mut buf)?;
+        Ok(buf)
+    }
+
+    // Reads like `read`, appending the bytes to `buf`
+    fn read_into(&self, offset: u64, len: u64, buf: &mut Vec<u8>) -> Result<()> {
+        if offset.checked_add(len).is_none_or(|end| end > self.len()) {
+            return Err(SstError::UnexpectedEof);
+        }
+        match self {
+            Backend::File { file, start: region_start, .. } => {
+                let len = usize::try_from(len)
+                    .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Read is too large for this platform"))?;
+                let start = buf.len();
+                buf.resize(start + len, 0);
+                read_exact_at(file, &mut buf[start..], region_start + offset)?;
+            }
+            Backend::Mmap(mmap) => buf.extend_from_slice(read_slice(mmap, offset, len)?),
+            Backend::Memory(bytes) => buf.extend_from_slice(read_slice(bytes, offset, len)?),
+            #[cfg(feature = "tokio")]
+            Backend::Tail { header, tail, start, .. } => match offset.checked_sub(*start) {
+                Some(offset) => buf.extend_from_slice(read_slice(tail, offset, len)?),
+                None => buf.extend_from_slice(read_slice(header, offset, len)?),
+            },
+        }
+        Ok(())
+    }
+
+    // The whole file, for backends that hold it in memory
+    fn bytes(&self) -> Option<&[u8]> {
+        match self {
+            Backend::File { .. } => None,
+            Backend::Mmap(mmap) => Some(mmap),
+            Backend::Memory(bytes) => Some(bytes),
+            #[cfg(feature = "tokio")]
+            Backend::Tail { .. } => None,
+        }
+    }
+
+    // Whether the data blocks can be read, which they can from every backend but `Tail`
+    fn has_blocks(&self) -> bool {
+        match self {
+            #[cfg(feature = "tokio")]
+            Backend::Tail { .. } => false,
+            _ => true,
+        }
+    }
+}
+
+// Reads the part of a file an SST occupies as if it were the whole file, with
+// positioned reads that leave the file cursor alone
+struct FileRegion<'a> {
+    file: &'a File,
+    start: u64,
+    len: u64,
+    pos: u64,
+}
+
+impl Read for FileRegion<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(usize::try_from(self.len.saturating_sub(self.pos)).unwrap_or(usize::MAX));
+        read_exact_at(self.file, &mut buf[..len], self.start + self.pos)?;
+        self.pos += len as u64;
+        Ok(len)
+    }
+}
+
+impl Seek for FileRegion<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::End(delta) => self.len.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+        };
+        self.pos = pos.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Seek before the start of the SST"))?;
+        Ok(self.pos)
+    }
+}
+
+// Hints to the OS that a byte range of the file will be read soon, so it can start
+// reading it in the background. The hint is advisory, so failures are ignored.
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+fn advise_willneed(file: &File, offset: u64, len: u64) {
+    use std::os::unix::io::AsRawFd;
+
+    // Ranges past what `off_t` can hold, on 32-bit targets, go without the hint
+    if let (Ok(offset), Ok(len)) = (libc::off_t::try_from(offset), libc::off_t::try_from(len)) {
+        unsafe {
+            libc::posix_fadvise(file.as_raw_fd(), offset, len, libc::POSIX_FADV_WILLNEED);
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+fn advise_willneed(_file: &File, _offset: u64, _len: u64) {}
+
+// Borrows `len` bytes starting at `offset` from an in-memory file
+fn read_slice(bytes: &[u8], offset: u64, len: u64) -> Result<&[u8]> {
+    let start = usize::try_from(offset).map_err(|_| SstError::UnexpectedEof)?;
+    let len = usize::try_from(len).map_err(|_| SstError::UnexpectedEof)?;
+    let end = start.checked_add(len).ok_or(SstError::UnexpectedEof)?;
+    bytes.get(start..end).ok_or(SstError::UnexpectedEof)
+}
+
+#[cfg(unix)]
+pub(crate) fn read_exact_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<()> {
+    use std::os::unix::fs::FileExt;
+    file.read_exact_at(buf, offset)
+}
+
+#[cfg(windows)]
+pub(crate) fn read_exact_at(file: &File, mut buf: &mut [u8], mut offset: u64) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+    while !buf.is_empty() {
+        match file.seek_read(buf, offset) {
+            Ok(0) => return Err(io::ErrorKind::UnexpectedEof.into()),
+            Ok(n) => {
+                buf = &mut buf[n..];
+                offset += n as u64;
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+// Platforms without positioned reads fall back to seeking the shared file handle,
+// which is only safe while a single thread reads at a time
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn read_exact_at(mut file: &File, buf: &mut [u8], offset: u64) -> io::Result<()> {
+    use std::io::{Read, Seek, SeekFrom};
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(buf)
+}
+
+/// Streams out a single value, returned by `SstReader::get_value_reader`.
+pub struct ValueReader<'a> {
+    source: ValueSource<'a>,
+}
+
+enum ValueSource<'a> {
+    // A value held in memory, either borrowed from the file or copied out of its block
+    Bytes(Cursor<Cow<'a, [u8]>>),
+    // A blob read from the file as it is consumed
+    File { file: &'a File, offset: u64, remaining: u64 },
+}
+
+impl Read for ValueReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.source {
+            ValueSource::Bytes(cursor) => cursor.read(buf),
+            ValueSource::File { file, offset, remaining } => {
+                let len = buf.len().min(usize::try_from(*remaining).unwrap_or(usize::MAX));
+                read_exact_at(file, &mut buf[..len], *offset)?;
+                *offset += len as u64;
+                *remaining -= len as u64;
+                Ok(len)
+            }
+        }
+    }
+}
+
+// Identifies the contents of a file cheaply, so a file replaced at the same path can be
+// told apart from the one a reader opened. Files since version 17 checksum every byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Fingerprint {
+    len: u64,
+    index_offset: u64,
+    index_size: u64,
+    file_checksum: u64,
+}
+
+impl Fingerprint {
+    fn new(footer: &Footer, len: u64) -> Self {
+        Fingerprint {
+            len,
+            index_offset: footer.index_offset,
+            index_size: footer.index_size,
+            file_checksum: footer.file_checksum,
+        }
+    }
 }
 
 /// Reads from an SST file.
+///
+/// All lookups and iterators take `&self`, so a reader can be wrapped in an `Arc`
+/// and queried from many threads at once.
+/// Async code can share one reader the same way and run lookups on a blocking
+/// thread pool (e.g. tokio's `spawn_blocking`) so they don't stall the executor,
+/// or look keys up with `AsyncSstReader`, which needs the `tokio` feature.
 pub struct SstReader {
-    file: File,
-    index: Vec<IndexEntryInfo>,
+    backend: Backend,
+    index: Vec<IndexPartition>,
+    num_blocks: usize,
+    version: u32,
+    bloom: Option<Vec<u8>>,
+    first_key: Option<Vec<u8>>,
+    num_entries: Option<u64>,
+    properties: Option<Properties>,
+    application_magic: [u8; 8],
+    // The cache and the id this reader's blocks are cached under
+    block_cache: Option<(Arc<BlockCache>, u64)>,
+    // The path the reader was opened from, if any, checked by `is_stale`
+    path: Option<PathBuf>,
+    fingerprint: Fingerprint,
+    comparator: Arc<dyn Comparator>,
 }
 
 impl SstReader {
     /// Opens an SST file and loads its index.
-    pub fn open(path: &Path) -> io::Result<Self> {
-        let mut file = File::open(path)?;
+    pub fn open(path: &Path) -> Result<Self> {
+        let backend = Backend::file(File::open(path)?)?;
+        Self::from_backend(backend, false, Arc::new(BytewiseComparator), None).map(|reader| reader.with_path(path))
+    }
+
+    /// Opens an SST file written with `SstWriterBuilder::comparator` set to a comparator
+    /// other than the default, and loads its index. Lookups, range bounds and scans then
+    /// follow its order. Fails with `SstError::ComparatorMismatch` if the file was
+    /// written with a comparator of a different name; `open` and the other constructors
+    /// assume `BytewiseComparator` and fail the same way on such files.
+    pub fn open_with_comparator(path: &Path, comparator: impl Comparator + 'static) -> Result<Self> {
+        let backend = Backend::file(File::open(path)?)?;
+        Self::from_backend(backend, false, Arc::new(comparator), None).map(|reader| reader.with_path(path))
+    }
+
+    /// Opens an SST file and loads its index, taking the bloom filter from the sidecar at
+    /// `bloom_path` written by `SstWriterBuilder::bloom_sidecar` instead of from the file.
+    /// With the sidecar on fast storage, lookups of keys it rules out never touch the
+    /// file. If there is no sidecar at `bloom_path`, the file's own bloom filter is used
+    /// as with `open`. Fails with `SstError::CorruptBloomSidecar` if the sidecar was
+    /// written for a different file.
+    pub fn open_with_bloom(path: &Path, bloom_path: &Path) -> Result<Self> {
+        let sidecar = match fs::read(bloom_path) {
+            Ok(sidecar) => Some(sidecar),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
+        };
+        let backend = Backend::file(File::open(path)?)?;
+        Self::from_backend(backend, false, Arc::new(BytewiseComparator), sidecar).map(|reader| reader.with_path(path))
+    }
+
+    /// Opens the SST occupying `length` bytes from `offset` in the file at `path`, such
+    /// as one of several SSTs concatenated into a container file. Offsets recorded in the
+    /// SST are taken from `offset`, and its footer is read from the end of the region,
+    /// so the bytes around it are never read. Lookups behave exactly as with `open`.
+    /// As other regions of the file may change without affecting this one, `is_stale`
+    /// always reports false for the reader.
+    pub fn open_region(path: &Path, offset: u64, length: u64) -> Result<Self> {
+        let backend = Backend::region(File::open(path)?, offset, length)?;
+        Self::from_backend(backend, false, Arc::new(BytewiseComparator), None)
+    }
 
-        // Read footer to find the index
-        file.seek(SeekFrom::End(-24))?; // Footer is 3 * 8 bytes
-        let mut footer_buf = [0u8; 24];
-        file.read_exact(&mut footer_buf)?;
+    /// Opens an SST file without reading its index, which is read and parsed by the first
+    /// lookup or scan that needs it and kept from then on. Opening a huge file for a few
+    /// lookups is then close to instant; lookups behave exactly as with `open`.
+    ///
+    /// Only the top level of a partitioned index is read at open, as it is with `open`.
+    /// Files written before version 13 have no properties block to take the key range
+    /// from, so their index is loaded at open as usual.
+    pub fn open_lazy(path: &Path) -> Result<Self> {
+        let backend = Backend::file(File::open(path)?)?;
+        Self::from_backend(backend, true, Arc::new(BytewiseComparator), None).map(|reader| reader.with_path(path))
+    }
+
+    /// Opens an SST file by memory-mapping it, so blocks and the index are read
+    /// straight from the mapping instead of through `seek` and `read` syscalls.
+    ///
+    /// SST files are immutable: the file must not be modified or truncated while the
+    /// reader is open. On Unix, reading a part of the mapping that has since been
+    /// truncated away raises `SIGBUS`, which kills the process; on Windows, a mapped
+    /// file can't be truncated. A file replaced by renaming a new one over its path is
+    /// safe, as the mapping keeps the old file, but the reader goes on serving its
+    /// contents: check `is_stale` and reopen.
+    pub fn open_mmap(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        // Safety: SST files are never modified in place, which callers promise above
+        let backend = Backend::Mmap(unsafe { Mmap::map(&file)? });
+        Self::from_backend(backend, false, Arc::new(BytewiseComparator), None).map(|reader| reader.with_path(path))
+    }
+
+    /// Reads a whole SST file into memory and opens it from there, so lookups and scans
+    /// make no further system calls. For small files this is simpler and often faster than
+    /// a block cache or `open_mmap`, and it works on platforms without memory mapping.
+    pub fn open_in_memory(path: &Path) -> Result<Self> {
+        Self::from_bytes(fs::read(path)?).map(|reader| reader.with_path(path))
+    }
+
+    fn with_path(mut self, path: &Path) -> Self {
+        self.path = Some(path.to_path_buf());
+        self
+    }
+
+    /// Returns whether the file at the path the reader was opened from is no longer the
+    /// file it opened, as when a compaction renames a new file over it. The reader keeps
+    /// serving the file it opened, so a stale reader should be dropped and the path
+    /// reopened. The check compares the file's length and footer, so it reads only the
+    /// footer. Readers not opened from a path, like those from `from_bytes`, are never stale.
+    pub fn is_stale(&self) -> Result<bool> {
+        let Some(path) = &self.path else {
+            return Ok(false);
+        };
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(true),
+            Err(e) => return Err(e.into()),
+        };
+        let len = file.metadata()?.len();
+        if len != self.fingerprint.len {
+            return Ok(true);
+        }
+        match Footer::read_from(&mut file) {
+            Ok(footer) => Ok(Fingerprint::new(&footer, len) != self.fingerprint),
+            Err(SstError::Io(e)) => Err(SstError::Io(e)),
+            // Whatever is there now isn't a valid SST
+            Err(_) => Ok(true),
+        }
+    }
+
+    /// Opens an SST held entirely in memory, such as one downloaded over the network.
+    /// Lookups behave exactly as they do for a file on disk.
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self> {
+        Self::from_backend(Backend::Memory(bytes), false, Arc::new(BytewiseComparator), None)
+    }
+
+    fn from_backend(
+        mut backend: Backend,
+        lazy_index: bool,
+        comparator: Arc<dyn Comparator>,
+        bloom_sidecar: Option<Vec<u8>>,
+    ) -> Result<Self> {
+        // Files since version 14 also start with the magic number. Older files have no
+        // header, so a missing one is only an error once the footer gives the version.
+        let has_header = backend.read(0, HEADER_SIZE).is_ok_and(|header| header == MAGIC.to_le_bytes());
+        let footer = backend.read_footer()?;
+        let version = footer.version;
+        if version >= 14 && !has_header {
+            return Err(SstError::BadMagic);
+        }
+
+        // A corrupt footer can point the index anywhere, so check it lies before the
+        // footer rather than failing with an EOF partway through reading it
+        let data_len = backend.len() - footer.len;
+        if footer.index_offset.checked_add(footer.index_size).is_none_or(|end| end > data_len) {
+            return Err(SstError::CorruptIndex(format!(
+                "footer places the index block at offset {} with size {}, past the {} bytes before the footer",
+                footer.index_offset, footer.index_size, data_len
+            )));
+        }
+
+        // A sidecar's filter stands in for the file's own, which is then never read
+        let bloom = match bloom_sidecar {
+            Some(sidecar) => bloom::sidecar_from_bytes(&sidecar, footer.file_checksum)?,
+            None if footer.bloom_size > 0 => Some(backend.read(footer.bloom_offset, footer.bloom_size)?),
+            None => None,
+        };
+
+        let mut properties = None;
+        if version >= 13 {
+            let properties_buf = backend.read(footer.properties_offset, footer.properties_size)?;
+            properties = Some(Properties::from_bytes(&properties_buf)?);
+        }
+        // Lookups with the wrong order would quietly miss keys, so refuse the file instead
+        let file_comparator = properties.as_ref().and_then(|properties| properties.comparator.as_deref());
+        let file_comparator = file_comparator.unwrap_or(BytewiseComparator.name());
+        if file_comparator != comparator.name() {
+            return Err(SstError::ComparatorMismatch {
+                found: file_comparator.to_string(),
+                expected: comparator.name().to_string(),
+            });
+        }
+
+        // Read and parse the index block, or just the top level of a partitioned index
+        let (index, num_blocks) = match properties.as_ref().filter(|_| lazy_index) {
+            Some(properties) => Self::read_lazy_index(&backend, &footer, properties)?,
+            None => Self::parse_top_level_index(&backend.read(footer.index_offset, footer.index_size)?, version)?,
+        };
+
+        let fingerprint = Fingerprint::new(&footer, backend.len());
+        // Older files don't record the first key, so read it from the first block
+        let first_key = footer.first_key;
+        let mut reader = SstReader {
+            backend,
+            index,
+            num_blocks,
+            version,
+            bloom,
+            first_key,
+            num_entries: footer.num_entries,
+            properties,
+            application_magic: footer.application_magic,
+            block_cache: None,
+            path: None,
+            fingerprint,
+            comparator,
+        };
+        // Like the writer's, the first key counts tombstones, so lookups can skip keys before it.
+        // Without the blocks to read it from, lookups just can't.
+        if reader.first_key.is_none() && reader.num_blocks > 0 && reader.backend.has_blocks() {
+            let first_entry = reader.iter_entries().next().transpose()?;
+            reader.first_key = first_entry.map(|(key, _)| key);
+        }
+        // An empty file has no key range
+        if reader.num_blocks == 0 {
+            reader.first_key = None;
+        }
+
+        Ok(reader)
+    }
+
+    // Opens the SST whose header and metadata `AsyncSstReader` has read: `tail` holds the
+    // bytes from `start` to the end of the file, which is `len` bytes long
+    #[cfg(feature = "tokio")]
+    pub(crate) fn from_tail(header: Vec<u8>, tail: Vec<u8>, start: u64, len: u64) -> Result<Self> {
+        let backend = Backend::Tail { header, tail, start, len };
+        Self::from_backend(backend, false, Arc::new(BytewiseComparator), None)
+    }
+
+    // Returns the offset the metadata of a file starts at, given its footer and its
+    // index block: the bloom filter, index partitions, index, properties and footer
+    // take up everything from there to the end of the file
+    #[cfg(feature = "tokio")]
+    pub(crate) fn metadata_start(footer: &Footer, index_buf: &[u8]) -> Result<u64> {
+        let mut start = footer.index_offset;
+        if footer.bloom_size > 0 {
+            start = start.min(footer.bloom_offset);
+        }
+        if footer.version >= 13 {
+            start = start.min(footer.properties_offset);
+        }
+        // Partitions are written before the index block, and after any bloom filter
+        let (partitions, _) = Self::parse_top_level_index(index_buf, footer.version)?;
+        let partitions = partitions.iter().filter(|partition| partition.size > 0);
+        Ok(partitions.map(|partition| partition.offset).fold(start, u64::min))
+    }
 
-        let magic = u64::from_le_bytes(footer_buf[16..24].try_into().unwrap());
-        if magic != 0xDEADBEEFCAFEBABEu64 {
-            return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid SST file format"));
+    /// Checks that the file was written with the application magic `magic`, failing
+    /// with `SstError::ApplicationMagicMismatch` otherwise, including for files written
+    /// without one. Use it straight after opening to refuse files from other tools.
+    pub fn expect_application_magic(self, magic: [u8; 8]) -> Result<Self> {
+        if self.application_magic != magic {
+            return Err(SstError::ApplicationMagicMismatch);
         }
+        Ok(self)
+    }
+
+    /// Enables an LRU cache holding up to `capacity` decoded data blocks, so repeated
+    /// lookups into the same block skip the file read, checksum and decompression.
+    pub fn with_block_cache(self, capacity: usize) -> Self {
+        self.with_shared_block_cache(Arc::new(BlockCache::with_block_capacity(capacity)))
+    }
+
+    /// Caches decoded data blocks in `cache`, which other readers may share, so that
+    /// one byte budget covers the blocks cached for every file a process has open.
+    pub fn with_shared_block_cache(mut self, cache: Arc<BlockCache>) -> Self {
+        let file_id = cache.new_file_id();
+        self.block_cache = Some((cache, file_id));
+        self
+    }
+
+    /// Returns the block cache hit and miss counts, or None if no cache is enabled.
+    /// A shared cache counts the hits and misses of every reader using it.
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.block_cache.as_ref().map(|(cache, _)| cache.stats())
+    }
 
-        let index_offset = u64::from_le_bytes(footer_buf[0..8].try_into().unwrap());
-        let index_size = u64::from_le_bytes(footer_buf[8..16].try_into().unwrap());
+    // Parses the block the footer points to, returning the index partitions and the total
+    // number of data blocks. Since version 15 the block starts with its index type; a flat
+    // index is returned as one loaded partition.
+    // Top-Level Index Entry Format: [last_key_len: u32][last_key][partition_offset: u64][partition_size: u64]
+    //                               [cumulative_entries: u64][cumulative_blocks: u64]
+    fn parse_top_level_index(mut buf: &[u8], version: u32) -> Result<(Vec<IndexPartition>, usize)> {
+        let truncated = || SstError::CorruptIndex("index block is truncated".to_string());
 
-        // Read and parse the index block
-        file.seek(SeekFrom::Start(index_offset))?;
-        let mut index_buf = vec![0; index_size as usize];
-        file.read_exact(&mut index_buf)?;
-        
-        let index = Self::parse_index(&index_buf)?;
+        let index_type = if version >= 15 {
+            read_bytes(&mut buf, 1).ok_or_else(truncated)?[0]
+        } else {
+            INDEX_TYPE_FLAT
+        };
+        match index_type {
+            INDEX_TYPE_FLAT => {
+                let entries = Self::parse_index(buf, version)?;
+                let num_blocks = entries.len();
+                let Some(last) = entries.last() else {
+                    return Ok((Vec::new(), 0));
+                };
+                let partition = IndexPartition {
+                    last_key: last.last_key.clone(),
+                    offset: 0,
+                    size: 0,
+                    cumulative_entries: last.cumulative_entries,
+                    first_block: 0,
+                    entries: OnceLock::from(entries),
+                };
+                Ok((vec![partition], num_blocks))
+            }
+            INDEX_TYPE_PARTITIONED => {
+                let num_partitions = read_u32(&mut buf).ok_or_else(truncated)? as usize;
+                // Every entry needs at least a key length and four handles
+                if num_partitions > buf.len() / 36 {
+                    return Err(SstError::CorruptIndex(format!(
+                        "index declares {} partitions but only has {} bytes",
+                        num_partitions,
+                        buf.len()
+                    )));
+                }
+
+                let mut partitions = Vec::with_capacity(num_partitions);
+                let mut num_blocks = 0;
+                for _ in 0..num_partitions {
+                    let key_len = read_u32(&mut buf).ok_or_else(truncated)? as usize;
+                    let last_key = read_bytes(&mut buf, key_len).ok_or_else(truncated)?.to_vec();
+                    let offset = read_u64(&mut buf).ok_or_else(truncated)?;
+                    let size = read_u64(&mut buf).ok_or_else(truncated)?;
+                    let cumulative_entries = read_u64(&mut buf).ok_or_else(truncated)?;
+                    let cumulative_blocks = read_u64(&mut buf).ok_or_else(truncated)?;
+                    let first_block = num_blocks;
+                    num_blocks = usize::try_from(cumulative_blocks)
+                        .ok()
+                        .filter(|&blocks| blocks > first_block)
+                        .ok_or_else(|| SstError::CorruptIndex("index partition holds no blocks".to_string()))?;
+                    partitions.push(IndexPartition {
+                        last_key,
+                        offset,
+                        size,
+                        cumulative_entries,
+                        first_block,
+                        entries: OnceLock::new(),
+                    });
+                }
+                Ok((partitions, num_blocks))
+            }
+            other => Err(SstError::CorruptIndex(format!("unknown index type {}", other))),
+        }
+    }
+
+    // Reads only the start of the index block. A flat index becomes a single partition that
+    // isn't loaded yet, whose last key is the file's max key: the last block's index key is
+    // never shortened. The top level of a partitioned index is parsed as usual.
+    fn read_lazy_index(
+        backend: &Backend,
+        footer: &Footer,
+        properties: &Properties,
+    ) -> Result<(Vec<IndexPartition>, usize)> {
+        // The type byte (version 15+) and the flat index's entry count
+        let header_len = if footer.version >= 15 { 5 } else { 4 };
+        if footer.index_size < header_len {
+            return Err(SstError::CorruptIndex("index block is truncated".to_string()));
+        }
+        let header = backend.read(footer.index_offset, header_len)?;
+        if footer.version >= 15 && header[0] != INDEX_TYPE_FLAT {
+            let index_buf = backend.read(footer.index_offset, footer.index_size)?;
+            return Self::parse_top_level_index(&index_buf, footer.version);
+        }
 
-        Ok(SstReader { file, index })
+        let num_blocks = u32::from_le_bytes(header[header_len as usize - 4..].try_into().unwrap()) as usize;
+        if num_blocks == 0 {
+            return Ok((Vec::new(), 0));
+        }
+        let Some(max_key) = &properties.max_key else {
+            return Err(SstError::CorruptIndex("index has blocks but the file records no max key".to_string()));
+        };
+        let partition = IndexPartition {
+            last_key: max_key.clone(),
+            offset: footer.index_offset + header_len - 4,
+            size: footer.index_size - (header_len - 4),
+            cumulative_entries: footer.num_entries.unwrap_or_default(),
+            first_block: 0,
+            entries: OnceLock::new(),
+        };
+        Ok((vec![partition], num_blocks))
     }
 
-    fn parse_index(mut buf: &[u8]) -> io::Result<Vec<IndexEntryInfo>> {
-        let num_entries = u32::from_le_bytes(buf[0..4].try_into().unwrap());
-        buf = &buf[4..];
-        
-        let mut index = Vec::with_capacity(num_entries as usize);
+    // Index Entry Format: [last_key_len: u32][last_key][block_offset: u64][block_size: u64]
+    //                    [cumulative_entries: u64]                                          (version 10+)
+    //                    [first_key_len: u32][first_key]                                    (version 18+)
+    // Since version 19 a sparse index leaves both keys empty for the blocks it doesn't sample,
+    // which belong to the span started by the sampled block before them and share its index key.
+    fn parse_index(mut buf: &[u8], version: u32) -> Result<Vec<IndexEntryInfo>> {
+        let truncated = || SstError::CorruptIndex("index block is truncated".to_string());
+
+        let num_entries = read_u32(&mut buf).ok_or_else(truncated)? as usize;
+        // Every entry needs at least a key length, a block offset and a block size,
+        // so reject counts the buffer can't hold before allocating for them
+        if num_entries > buf.len() / 20 {
+            return Err(SstError::CorruptIndex(format!(
+                "index declares {} entries but only has {} bytes",
+                num_entries,
+                buf.len()
+            )));
+        }
+
+        let mut index: Vec<IndexEntryInfo> = Vec::with_capacity(num_entries);
         for _ in 0..num_entries {
-            let key_len = u32::from_le_bytes(buf[0..4].try_into().unwrap()) as usize;
-            buf = &buf[4..];
-            let last_key = buf[..key_len].to_vec();
-            buf = &buf[key_len..];
-
-            let block_offset = u64::from_le_bytes(buf[0..8].try_into().unwrap());
-            buf = &buf[8..];
-            let block_size = u64::from_le_bytes(buf[0..8].try_into().unwrap());
-            buf = &buf[8..];
-            
-            index.push(IndexEntryInfo { last_key, block_offset, block_size });
+            let key_len = read_u32(&mut buf).ok_or_else(truncated)? as usize;
+            let last_key = read_bytes(&mut buf, key_len).ok_or_else(truncated)?.to_vec();
+            let block_offset = read_u64(&mut buf).ok_or_else(truncated)?;
+            let block_size = read_u64(&mut buf).ok_or_else(truncated)?;
+            let cumulative_entries = if version >= 10 {
+                read_u64(&mut buf).ok_or_else(truncated)?
+            } else {
+                0
+            };
+            let first_key = if version >= 18 {
+                let key_len = read_u32(&mut buf).ok_or_else(truncated)? as usize;
+                Some(read_bytes(&mut buf, key_len).ok_or_else(truncated)?.to_vec())
+            } else {
+                None
+            };
+            let (last_key, first_key) = match index.last() {
+                Some(prev) if version >= 19 && last_key.is_empty() => (prev.last_key.clone(), None),
+                None if version >= 19 && last_key.is_empty() => {
+                    return Err(SstError::CorruptIndex("index starts with a block it doesn't sample".to_string()));
+                }
+                _ => (last_key, first_key),
+            };
+
+            index.push(IndexEntryInfo { last_key, block_offset, block_size, cumulative_entries, first_key });
         }
         Ok(index)
     }
 
     /// Searches for a key and returns the corresponding value.
-    pub fn get(&mut self, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
-        // Find the data block that might contain the key
-        // The first block whose last_key is >= our key is the one to search
-        let block_info = self.index.iter().find(|entry| &entry.last_key[..] >= key);
-        
-        if let Some(info) = block_info {
-            // Read the data block from the file
-            self.file.seek(SeekFrom::Start(info.block_offset))?;
-            let mut block_buf = vec![0; info.block_size as usize];
-            self.file.read_exact(&mut block_buf)?;
-            
-            // Search within the block
-            return Self::search_in_block(&block_buf, key);
+    /// Keys recorded as deleted are reported as missing; use `get_entry` to tell them apart.
+    pub fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        match self.get_entry(key)? {
+            Some(Entry::Value(value)) => Ok(Some(value)),
+            Some(Entry::Tombstone) | None => Ok(None),
         }
+    }
 
-        Ok(None)
+    /// Searches for a key and returns its entry, which is a tombstone if the key was deleted.
+    pub fn get_entry(&self, key: &[u8]) -> Result<Option<Entry>> {
+        let Some(block) = self.find_block(key)? else {
+            return Ok(None);
+        };
+        self.search_in_block(&block, key)?
+            .map(|value| self.resolve(value))
+            .transpose()
+    }
+
+    /// Returns the newest version of `user_key` with a sequence number `<= snapshot`, in a
+    /// file written with `InternalKeyComparator` and opened with `open_with_comparator`,
+    /// whose keys are built by `comparator::internal_key`. A version that is a tombstone
+    /// hides the older ones, so the key is reported as missing. Fails with
+    /// `SstError::ComparatorMismatch` if the reader uses any other comparator.
+    pub fn get_at(&self, user_key: &[u8], snapshot: u64) -> Result<Option<Vec<u8>>> {
+        if self.comparator.name() != InternalKeyComparator.name() {
+            return Err(SstError::ComparatorMismatch {
+                found: self.comparator.name().to_string(),
+                expected: InternalKeyComparator.name().to_string(),
+            });
+        }
+        // Versions are stored newest first, so the first entry at or after the snapshot's
+        // internal key is the newest visible version, if it's still the same user key
+        let target = comparator::internal_key(user_key, snapshot);
+        let entry = SstIterator::with_range(self, Bound::Included(&target), Bound::Unbounded).next_entry();
+        let Some((key, entry)) = entry.transpose()? else {
+            return Ok(None);
+        };
+        match (comparator::parse_internal_key(&key), entry) {
+            (Some((key, _)), Entry::Value(value)) if key == user_key => Ok(Some(value)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Searches for a key like `get`, but copies the value into `buf` rather than a new
+    /// vector, so a loop of lookups can reuse one allocation. `buf` is cleared first, and
+    /// holds the value if true is returned; tombstones are reported as missing.
+    pub fn get_into(&self, key: &[u8], buf: &mut Vec<u8>) -> Result<bool> {
+        buf.clear();
+        let Some(block) = self.find_block(key)? else {
+            return Ok(false);
+        };
+        match self.search_in_block(&block, key)? {
+            Some(RawValue::Inline(value)) => buf.extend_from_slice(value),
+            Some(RawValue::Blob { offset, len }) => self.backend.read_into(offset, len, buf)?,
+            Some(RawValue::Tombstone) | None => return Ok(false),
+        }
+        Ok(true)
     }
 
-    // Linear scan through the data block to find the key
-    fn search_in_block(mut buf: &[u8], search_key: &[u8]) -> io::Result<Option<Vec<u8>>> {
-        let num_entries = u32::from_le_bytes(buf[0..4].try_into().unwrap());
-        buf = &buf[4..];
+    /// Returns whether the file holds a value for `key`, without copying the value out.
+    /// Keys recorded as deleted are reported as missing, just like with `get`.
+    pub fn contains_key(&self, key: &[u8]) -> Result<bool> {
+        let Some(block) = self.find_block(key)? else {
+            return Ok(false);
+        };
+        Ok(matches!(
+            self.search_in_block(&block, key)?,
+            Some(RawValue::Inline(_) | RawValue::Blob { .. })
+        ))
+    }
 
-        for _ in 0..num_entries {
-            let key_len = u32::from_le_bytes(buf[0..4].try_into().unwrap()) as usize;
-            buf = &buf[4..];
-            let key = &buf[..key_len];
-            buf = &buf[key_len..];
-            
-            let val_len = u32::from_le_bytes(buf[0..4].try_into().unwrap()) as usize;
-            buf = &buf[4..];
-            
-            if key == search_key {
-                return Ok(Some(buf[..val_len].to_vec()));
+    /// Searches for a key like `get`, but borrows the value from the file instead of
+    /// copying it where possible.
+    ///
+    /// Readers opened with `open_mmap` or `from_bytes` return values straight out of the
+    /// mapped or in-memory file when the block holding them isn't compressed, bypassing
+    /// the block cache. Other readers, and values in compressed blocks, are copied out
+    /// just as `get` does.
+    pub fn get_ref(&self, key: &[u8]) -> Result<Option<Cow<'_, [u8]>>> {
+        let Some(bytes) = self.backend.bytes() else {
+            return Ok(self.get(key)?.map(Cow::Owned));
+        };
+        let Some(info) = self.find_block_info(key)? else {
+            return Ok(None);
+        };
+
+        let block = self.decode_block(read_slice(bytes, info.block_offset, info.block_size)?)?;
+        let block = match block {
+            Cow::Borrowed(block) => block,
+            Cow::Owned(block) => {
+                let entry = self.search_in_block(&block, key)?
+                    .map(|value| self.resolve(value))
+                    .transpose()?;
+                return match entry {
+                    Some(Entry::Value(value)) => Ok(Some(Cow::Owned(value))),
+                    Some(Entry::Tombstone) | None => Ok(None),
+                };
+            }
+        };
+        match self.search_in_block(block, key)? {
+            Some(RawValue::Inline(value)) => Ok(Some(Cow::Borrowed(value))),
+            Some(RawValue::Blob { offset, len }) => Ok(Some(Cow::Borrowed(read_slice(bytes, offset, len)?))),
+            Some(RawValue::Tombstone) | None => Ok(None),
+        }
+    }
+
+    /// Searches for a key like `get`, but returns a reader that streams the value out
+    /// instead of copying it into one buffer, so huge values needn't fit in memory.
+    ///
+    /// Only values that don't pass through a block buffer are actually streamed: blobs
+    /// (see `SstWriterBuilder::blob_threshold`), which are read from the file in pieces
+    /// as the reader is consumed, and values in uncompressed blocks of readers opened
+    /// with `open_mmap` or `from_bytes`, which are read straight out of the mapping.
+    /// Any other value is read with its block and copied out whole, just as `get` does.
+    pub fn get_value_reader(&self, key: &[u8]) -> Result<Option<ValueReader<'_>>> {
+        let Backend::File { file, start, .. } = &self.backend else {
+            let value = self.get_ref(key)?;
+            return Ok(value.map(|value| ValueReader { source: ValueSource::Bytes(Cursor::new(value)) }));
+        };
+        let Some(block) = self.find_block(key)? else {
+            return Ok(None);
+        };
+        let source = match self.search_in_block(&block, key)? {
+            Some(RawValue::Inline(value)) => ValueSource::Bytes(Cursor::new(Cow::Owned(value.to_vec()))),
+            Some(RawValue::Blob { offset, len }) => ValueSource::File {
+                file,
+                offset: start + offset,
+                remaining: len,
+            },
+            Some(RawValue::Tombstone) | None => return Ok(None),
+        };
+        Ok(Some(ValueReader { source }))
+    }
+
+    // Reads the data block that might contain `key`, or returns None if no block can
+    fn find_block(&self, key: &[u8]) -> Result<Option<Arc<Vec<u8>>>> {
+        match self.find_block_info(key)? {
+            Some(info) => Ok(Some(self.read_block(info.block_offset, info.block_size)?)),
+            None => Ok(None),
+        }
+    }
+
+    // Finds the index entry of the data block that might contain `key`
+    fn find_block_info(&self, key: &[u8]) -> Result<Option<&IndexEntryInfo>> {
+        // Skip the block read entirely when the bloom filter or the key range rules the key out
+        if !self.may_contain(key) {
+            return Ok(None);
+        }
+
+        // The first block whose last_key is >= our key is the one to search.
+        // A key past the last block's last_key has no block.
+        let block_idx = self.seek_block(Bound::Included(key))?;
+        if block_idx == self.num_blocks {
+            return Ok(None);
+        }
+        match self.block_for_key(key, block_idx)? {
+            Some(block_idx) => Ok(Some(self.block_info(block_idx)?)),
+            None => Ok(None),
+        }
+    }
+
+    // Given the first block whose index key is >= `key`, returns the position of the block
+    // that could contain it, or None if it falls before the block's first key and so
+    // between blocks. Within a span of a sparse index, whose blocks share an index key,
+    // the blocks are read in turn until one ends at or after the key.
+    fn block_for_key(&self, key: &[u8], mut block_idx: usize) -> Result<Option<usize>> {
+        let first_key = self.block_info(block_idx)?.first_key.as_deref();
+        if first_key.is_some_and(|first_key| self.comparator.compare(key, first_key).is_lt()) {
+            return Ok(None);
+        }
+        let span_end = self.span_end(block_idx)?;
+        while block_idx + 1 < span_end {
+            let block = self.read_block_at(block_idx)?;
+            if self.comparator.compare(key, &Self::last_key_in_block(&block, self.version)?).is_le() {
+                break;
+            }
+            block_idx += 1;
+        }
+        Ok(Some(block_idx))
+    }
+
+    // Returns whether the block at `block_idx` starts a span of the index: every block
+    // does, except those a sparse index (version 19+) doesn't sample
+    pub(crate) fn starts_span(&self, block_idx: usize) -> Result<bool> {
+        Ok(self.version < 19 || self.block_info(block_idx)?.first_key.is_some())
+    }
+
+    // Returns the position just past the last block of the span holding the block at `block_idx`
+    pub(crate) fn span_end(&self, block_idx: usize) -> Result<usize> {
+        let mut end = block_idx + 1;
+        while end < self.num_blocks && !self.starts_span(end)? {
+            end += 1;
+        }
+        Ok(end)
+    }
+
+    /// Looks up several keys at once and returns their values in the same order as `keys`.
+    /// The keys are visited in sorted order so each data block is read at most once,
+    /// turning clustered lookups into a single forward pass.
+    pub fn multi_get(&self, keys: &[&[u8]]) -> Result<Vec<Option<Vec<u8>>>> {
+        let groups = self.group_keys_by_block(keys)?;
+        let mut results = vec![None; keys.len()];
+        for (i, value) in self.lookup_groups(keys, &groups)? {
+            results[i] = Some(value);
+        }
+        Ok(results)
+    }
+
+    /// Looks up several keys at once like `multi_get`, spreading the work over up to
+    /// `threads` threads. The keys are grouped by the data block that could hold them
+    /// and the groups are split between the threads, which read and decode their blocks
+    /// concurrently. Readers opened from a file use positioned reads, so the threads
+    /// never contend for a shared file cursor. Values are returned in the order of `keys`.
+    pub fn multi_get_parallel(&self, keys: &[&[u8]], threads: usize) -> Result<Vec<Option<Vec<u8>>>> {
+        let groups = self.group_keys_by_block(keys)?;
+        let threads = threads.clamp(1, groups.len().max(1));
+        let chunk_size = groups.len().div_ceil(threads).max(1);
+        let found = thread::scope(|scope| {
+            let workers: Vec<_> = groups
+                .chunks(chunk_size)
+                .map(|chunk| scope.spawn(move || self.lookup_groups(keys, chunk)))
+                .collect();
+            workers
+                .into_iter()
+                .map(|worker| worker.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+                .collect::<Result<Vec<_>>>()
+        })?;
+
+        let mut results = vec![None; keys.len()];
+        for (i, value) in found.into_iter().flatten() {
+            results[i] = Some(value);
+        }
+        Ok(results)
+    }
+
+    // Groups the positions of `keys` by the data block that could hold each key, in block
+    // order. Keys the bloom filter rules out, or that sort outside the file's key range, are left out.
+    fn group_keys_by_block(&self, keys: &[&[u8]]) -> Result<Vec<(usize, Vec<usize>)>> {
+        let mut order: Vec<usize> = (0..keys.len()).collect();
+        order.sort_by(|&a, &b| self.comparator.compare(keys[a], keys[b]));
+
+        let mut groups: Vec<(usize, Vec<usize>)> = Vec::new();
+        for i in order {
+            let key = keys[i];
+            if !self.may_contain(key) {
+                continue;
+            }
+
+            // Keys only ever increase, so once one is past the last block every later one is too
+            let block_idx = self.seek_block(Bound::Included(key))?;
+            if block_idx == self.num_blocks {
+                break;
+            }
+            let Some(block_idx) = self.block_for_key(key, block_idx)? else {
+                continue;
+            };
+            match groups.last_mut() {
+                Some((idx, members)) if *idx == block_idx => members.push(i),
+                _ => groups.push((block_idx, vec![i])),
+            }
+        }
+        Ok(groups)
+    }
+
+    // Searches each group's block for its keys, reading every block once, and returns
+    // the values found along with the positions of their keys
+    fn lookup_groups(&self, keys: &[&[u8]], groups: &[(usize, Vec<usize>)]) -> Result<Vec<(usize, Vec<u8>)>> {
+        let mut found = Vec::new();
+        for (block_idx, members) in groups {
+            let block = self.read_block_at(*block_idx)?;
+            for &i in members {
+                if let Some(value) = self.search_in_block(&block, keys[i])?
+                    && let Entry::Value(value) = self.resolve(value)?
+                {
+                    found.push((i, value));
+                }
             }
-            
-            buf = &buf[val_len..];
         }
+        Ok(found)
+    }
 
+    /// Returns the entry with the largest key `<= key`, or None if every key is greater.
+    /// Deleted keys are skipped, so the result may come from an earlier block.
+    pub fn floor(&self, key: &[u8]) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+        // The floor lives in the block that could contain `key` or one before it
+        let block_idx = self.seek_block(Bound::Included(key))?;
+        let candidates = if block_idx < self.num_blocks { self.span_end(block_idx)? } else { self.num_blocks };
+        for block_idx in (0..candidates).rev() {
+            let entries = self.read_block_entries(block_idx)?;
+            let floor = entries.into_iter().rev().find_map(|(entry_key, entry)| match entry {
+                Entry::Value(value) if self.comparator.compare(&entry_key, key).is_le() => Some((entry_key, value)),
+                _ => None,
+            });
+            if floor.is_some() {
+                return Ok(floor);
+            }
+        }
         Ok(None)
     }
-}
\ No newline at end of file
+
+    /// Returns the entry with the smallest key `>= key`, or None if every key is smaller.
+    pub fn ceil(&self, key: &[u8]) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+        self.range(Bound::Included(key), Bound::Unbounded).next().transpose()
+    }
+
+    /// Returns the entry at position `n` in key order, counting tombstones, or None if
+    /// the file holds `n` or fewer entries. The owning block is found by binary search
+    /// over the per-block entry counts in the index, so only that block is scanned.
+    /// Files written before format version 10 don't record the counts and fall back
+    /// to reading blocks from the start.
+    pub fn nth(&self, n: u64) -> Result<Option<(Vec<u8>, Entry)>> {
+        let (block_idx, entries_before) = if self.version >= 10 {
+            let partition_idx = self.index.partition_point(|partition| partition.cumulative_entries <= n);
+            if partition_idx == self.index.len() {
+                return Ok(None);
+            }
+            let entries = self.partition(partition_idx)?;
+            let local_idx = entries.partition_point(|entry| entry.cumulative_entries <= n);
+            let entries_before = match local_idx.checked_sub(1) {
+                Some(prev) => entries[prev].cumulative_entries,
+                None => partition_idx.checked_sub(1).map_or(0, |prev| self.index[prev].cumulative_entries),
+            };
+            (self.index[partition_idx].first_block + local_idx, entries_before)
+        } else {
+            let mut entries_before = 0;
+            let mut found = None;
+            for block_idx in 0..self.num_blocks {
+                let block = self.read_block_at(block_idx)?;
+                let entries = Self::split_block(&block, self.version)?.entries;
+                let block_entries = u32::from_le_bytes(entries[..4].try_into().unwrap()) as u64;
+                if n < entries_before + block_entries {
+                    found = Some(block_idx);
+                    break;
+                }
+                entries_before += block_entries;
+            }
+            match found {
+                Some(block_idx) => (block_idx, entries_before),
+                None => return Ok(None),
+            }
+        };
+
+        let block = self.read_block_at(block_idx)?;
+        let entries = Self::split_block(&block, self.version)?.entries;
+        let mut remaining = n - entries_before;
+        let mut key = Vec::new();
+        let mut offset = 4;
+        while offset < entries.len() {
+            let (value, next) = Self::read_entry_into(entries, offset, &mut key, self.version)?;
+            if remaining == 0 {
+                return Ok(Some((key, self.resolve(value)?)));
+            }
+            remaining -= 1;
+            offset = next;
+        }
+        Err(SstError::CorruptBlock(format!(
+            "block {} holds fewer entries than the index records",
+            block_idx
+        )))
+    }
+
+    /// Returns the smallest key in the file, or None if the file is empty.
+    /// Tombstones count as keys here and in `last_key`.
+    pub fn first_key(&self) -> Option<&[u8]> {
+        self.first_key.as_deref()
+    }
+
+    /// Returns the largest key in the file, or None if the file is empty.
+    pub fn last_key(&self) -> Option<&[u8]> {
+        self.index.last().map(|partition| partition.last_key.as_slice())
+    }
+
+    /// Returns the number of entries in the file, tombstones included, or None for files
+    /// written before format version 8, which don't record it.
+    pub fn len(&self) -> Option<u64> {
+        self.num_entries
+    }
+
+    /// Returns the metadata recorded when the file was written, or None for files written
+    /// before format version 13, which don't have a properties block.
+    pub fn properties(&self) -> Option<&Properties> {
+        self.properties.as_ref()
+    }
+
+    /// Returns the application-defined metadata the file was written with, sorted by name.
+    /// Empty if none was set, or for files without a properties block.
+    pub fn user_metadata(&self) -> &[(String, Vec<u8>)] {
+        self.properties.as_ref().map_or(&[], |properties| &properties.user_metadata)
+    }
+
+    /// Returns the application magic the file was written with, or None if it has none.
+    pub fn application_magic(&self) -> Option<[u8; 8]> {
+        Some(self.application_magic).filter(|&magic| magic != [0; 8])
+    }
+
+    /// Returns the index entry of every data block, in key order, for tooling that
+    /// inspects the file's layout. A partitioned index is read in full.
+    pub fn index_entries(&self) -> Result<Vec<IndexEntryMeta<'_>>> {
+        let mut entries = Vec::with_capacity(self.num_blocks);
+        for partition_idx in 0..self.index.len() {
+            entries.extend(self.partition(partition_idx)?.iter().map(IndexEntryMeta::from));
+        }
+        Ok(entries)
+    }
+
+    /// Returns the index entry of the data block at position `block_idx`, or None if it
+    /// isn't less than `num_blocks`. Its keys borrow from the reader's parsed index, so
+    /// walking the index this way allocates nothing beyond loading the partitions of a
+    /// partitioned index.
+    pub fn index_entry(&self, block_idx: usize) -> Result<Option<IndexEntryMeta<'_>>> {
+        if block_idx >= self.num_blocks {
+            return Ok(None);
+        }
+        self.block_info(block_idx).map(|info| Some(IndexEntryMeta::from(info)))
+    }
+
+    /// Returns the file's block boundaries and file-level metadata as a `Manifest`, for
+    /// tooling that indexes many files without keeping a reader open for each.
+    /// A partitioned index is read in full.
+    pub fn export_manifest(&self) -> Result<Manifest> {
+        let blocks = self
+            .index_entries()?
+            .into_iter()
+            .map(|entry| ManifestBlock {
+                last_key: entry.last_key.to_vec(),
+                block_offset: entry.block_offset,
+                block_size: entry.block_size,
+            })
+            .collect();
+        Ok(Manifest {
+            version: self.version,
+            first_key: self.first_key.clone(),
+            num_entries: self.num_entries,
+            blocks,
+            properties: self.properties.clone(),
+        })
+    }
+
+    /// Returns whether the file holds no entries.
+    pub fn is_empty(&self) -> bool {
+        self.num_blocks == 0
+    }
+
+    /// Returns an iterator over every key-value pair in sorted key order.
+    /// Blocks are read from the file one at a time as the iterator advances.
+    pub fn iter(&self) -> SstIterator<'_> {
+        SstIterator::new(self)
+    }
+
+    /// Returns an iterator over every entry in sorted key order, including tombstones.
+    /// This is the view needed to merge the file with others.
+    pub fn iter_entries(&self) -> SstEntryIterator<'_> {
+        SstEntryIterator::new(self)
+    }
+
+    /// Returns an iterator over the data blocks in key order, for processing the file a
+    /// block at a time. Concatenating every block's entries gives the same entries as
+    /// `iter_entries`.
+    pub fn blocks(&self) -> SstBlockIterator<'_> {
+        SstBlockIterator::new(self)
+    }
+
+    /// Returns an iterator over every key-value pair in descending key order.
+    /// Each block is fully decoded before its entries are returned in reverse.
+    pub fn iter_rev(&self) -> SstRevIterator<'_> {
+        SstRevIterator::new(self)
+    }
+
+    /// Returns an iterator over the key-value pairs whose keys fall between `start` and `end`.
+    /// The scan starts at the first block that could contain `start` and stops as soon as
+    /// it passes `end`. If `start` is greater than `end` nothing is returned.
+    pub fn range(&self, start: Bound<&[u8]>, end: Bound<&[u8]>) -> SstIterator<'_> {
+        SstIterator::with_range(self, start, end)
+    }
+
+    /// Estimates how many bytes scanning the range between `start` and `end` would read,
+    /// by summing the sizes of the data blocks that could hold keys in it. This is an
+    /// upper bound, since the blocks at either end may hold keys outside the range, and
+    /// doesn't count blob values, which are stored outside the blocks.
+    pub fn range_size_estimate(&self, start: Bound<&[u8]>, end: Bound<&[u8]>) -> Result<u64> {
+        let first_block = self.seek_block(start)?;
+        // The last block that could hold a key within the end bound is the last of the span
+        // whose index key first reaches it, since every key in the span after is greater
+        let end_block = match end {
+            Bound::Included(end) | Bound::Excluded(end) => match self.seek_block(Bound::Included(end))? {
+                block_idx if block_idx < self.num_blocks => self.span_end(block_idx)?,
+                _ => self.num_blocks,
+            },
+            Bound::Unbounded => self.num_blocks,
+        };
+        let mut size = 0;
+        for block_idx in first_block..end_block {
+            size += self.block_info(block_idx)?.block_size;
+        }
+        Ok(size)
+    }
+
+    /// Returns the values of the keys between `start` and `end`, in key order, joined into
+    /// one buffer with `separator` between each pair of them if given. The buffer is sized
+    /// up front from `range_size_estimate`, so it rarely grows. Tombstones are skipped.
+    pub fn concat_values(&self, start: Bound<&[u8]>, end: Bound<&[u8]>, separator: Option<&[u8]>) -> Result<Vec<u8>> {
+        let capacity = self.range_size_estimate(start, end)?;
+        let mut out = Vec::with_capacity(usize::try_from(capacity).unwrap_or(0));
+        for (i, entry) in self.range(start, end).enumerate() {
+            let (_, value) = entry?;
+            if let Some(separator) = separator
+                && i > 0
+            {
+                out.extend_from_slice(separator);
+            }
+            out.extend_from_slice(&value);
+        }
+        Ok(out)
+    }
+
+    /// Returns an iterator over the key-value pairs whose keys start with `prefix`.
+    pub fn prefix(&self, prefix: &[u8]) -> SstIterator<'_> {
+        match Self::prefix_successor(prefix) {
+            Some(end) => SstIterator::with_range(self, Bound::Included(prefix), Bound::Excluded(&end)),
+            None => SstIterator::with_range(self, Bound::Included(prefix), Bound::Unbounded),
+        }
+    }
+
+    /// Writes every entry to `w` as a JSON array of `{"key": ..., "value": ...}` objects
+    /// in sorted key order, with keys and values base64-encoded. Tombstones are included
+    /// with a `null` value. Entries are streamed one at a time rather than collected first.
+    pub fn to_json_writer<W: Write>(&self, w: W) -> Result<()> {
+        let mut w = BufWriter::new(w);
+        w.write_all(b"[")?;
+        for (i, entry) in self.iter_entries().enumerate() {
+            let (key, entry) = entry?;
+            let separator = if i == 0 { "\n" } else { ",\n" };
+            let key = base64::encode(&key);
+            match entry {
+                Entry::Value(value) => write!(
+                    w,
+                    "{separator}  {{\"key\": \"{key}\", \"value\": \"{}\"}}",
+                    base64::encode(&value)
+                )?,
+                Entry::Tombstone => write!(w, "{separator}  {{\"key\": \"{key}\", \"value\": null}}")?,
+            }
+        }
+        if !self.is_empty() {
+            w.write_all(b"\n")?;
+        }
+        w.write_all(b"]\n")?;
+        w.flush()?;
+        Ok(())
+    }
+
+    // The smallest key greater than every key starting with `prefix`, or None
+    // when the prefix is empty or all 0xFF bytes and so has no successor
+    fn prefix_successor(prefix: &[u8]) -> Option<Vec<u8>> {
+        let last = prefix.iter().rposition(|&byte| byte != 0xFF)?;
+        let mut successor = prefix[..=last].to_vec();
+        successor[last] += 1;
+        Some(successor)
+    }
+
+    /// Checks the file against the xxHash64 of its bytes that the writer stored in the
+    /// footer, failing with `SstError::FileChecksumMismatch` if any byte has changed.
+    /// This confirms a copy of the file is intact more cheaply than `verify`, reading
+    /// the file once from start to end without decoding anything. Files written before
+    /// version 17 have no file checksum and fail with `SstError::NoFileChecksum`.
+    pub fn verify_file_checksum(&self) -> Result<()> {
+        if self.version < 17 {
+            return Err(SstError::NoFileChecksum);
+        }
+        // Opening the file checked that the footer fits, so the checksum lies inside it
+        let checksum_offset = self.backend.len() - FILE_CHECKSUM_END;
+
+        let mut hash = XxHash64::new();
+        let mut offset = 0;
+        while offset < checksum_offset {
+            let len = (checksum_offset - offset).min(1 << 20);
+            hash.update(&self.backend.read(offset, len)?);
+            offset += len;
+        }
+        let footer_end = self.backend.read(checksum_offset, FILE_CHECKSUM_END)?;
+        hash.update(&footer_end[8..]);
+
+        if hash.finish() != u64::from_le_bytes(footer_end[..8].try_into().unwrap()) {
+            return Err(SstError::FileChecksumMismatch);
+        }
+        Ok(())
+    }
+
+    /// Scrubs the whole file for corruption, returning the first problem found.
+    ///
+    /// Every data block is read from the file, bypassing the block cache, and checked:
+    /// its checksum (if it has one), its restart points, that its keys are sorted within
+    /// the block and across blocks and lie within the block's index key, that every key
+    /// passes the bloom filter and the block's hash index (if it has one) and that blob
+    /// values lie inside the file. The index must
+    /// describe ascending, non-overlapping blocks inside the file, and the first key and
+    /// entry count recorded in the footer must match, as must the properties block.
+    pub fn verify(&self) -> Result<()> {
+        self.verify_with_progress(|_, _| {})
+    }
+
+    /// Scrubs the file like `verify`, calling `progress` with `(bytes_processed, total_bytes)`
+    /// after each data block is checked and once more when the whole file has passed, so
+    /// interactive tools can report how far a long scrub has got. `bytes_processed` is the
+    /// offset in the file of the end of the last block checked, and `total_bytes` the
+    /// file's length. The reader isn't locked while `progress` runs, so it may use the reader.
+    pub fn verify_with_progress(&self, mut progress: impl FnMut(u64, u64)) -> Result<()> {
+        let file_len = self.backend.len();
+        // Blocks and blobs start after the header
+        let mut expected_offset = if self.version >= 14 { HEADER_SIZE } else { 0 };
+        let mut prev_key: Option<Vec<u8>> = None;
+        let mut prev_index_key: Option<Vec<u8>> = None;
+        let mut num_entries = 0u64;
+        let (mut total_key_bytes, mut total_value_bytes) = (0u64, 0u64);
+
+        for i in 0..self.num_blocks {
+            let info = self.block_info(i)?;
+            // Blocks a sparse index doesn't sample share the index key of the block before them
+            let starts_span = self.starts_span(i)?;
+            // Lookups binary search the index keys, so out of order keys send them to the wrong block
+            if let Some(prev_index_key) = &prev_index_key
+                && starts_span
+                && self.comparator.compare(&info.last_key, prev_index_key).is_le()
+            {
+                return Err(SstError::CorruptIndex(format!(
+                    "index key {} of block {} is not greater than index key {} of block {}",
+                    info.last_key.escape_ascii(),
+                    i,
+                    prev_index_key.escape_ascii(),
+                    i - 1
+                )));
+            }
+            // Blobs may sit between blocks, but blocks never overlap
+            if info.block_offset < expected_offset {
+                return Err(SstError::CorruptIndex(format!(
+                    "block {} starts at offset {} but the previous block ends at {}",
+                    i, info.block_offset, expected_offset
+                )));
+            }
+            expected_offset = info
+                .block_offset
+                .checked_add(info.block_size)
+                .filter(|&end| end <= file_len)
+                .ok_or_else(|| SstError::CorruptIndex(format!("block {} extends past the end of the file", i)))?;
+
+            let corrupt = |msg: &str| SstError::CorruptBlock(format!("block {} at offset {}: {}", i, info.block_offset, msg));
+            let add_context = |e: SstError| match e {
+                SstError::ChecksumMismatch => corrupt("checksum mismatch"),
+                SstError::CorruptBlock(msg) => corrupt(&msg),
+                e => e,
+            };
+
+            let block = self.read_block_uncached(info.block_offset, info.block_size).map_err(add_context)?;
+            let BlockParts {
+                entries,
+                restarts,
+                hash_index,
+            } = Self::split_block(&block, self.version).map_err(add_context)?;
+            let declared = u32::from_le_bytes(entries[..4].try_into().unwrap()) as u64;
+
+            let mut entry_offsets = Vec::new();
+            let mut block_prev_key = Vec::new();
+            let mut offset = 4;
+            while offset < entries.len() {
+                let (key, value, next) =
+                    Self::read_entry(entries, offset, &block_prev_key, self.version).map_err(add_context)?;
+                if let RawValue::Blob { offset: blob_offset, len } = value
+                    && blob_offset.checked_add(len).is_none_or(|end| end > file_len)
+                {
+                    return Err(corrupt(&format!("blob of the entry at offset {} extends past the end of the file", offset)));
+                }
+                if prev_key.is_none() && self.first_key.as_deref() != Some(key.as_slice()) {
+                    return Err(SstError::CorruptIndex("footer's first key does not match the file".to_string()));
+                }
+                if prev_key.as_ref().is_some_and(|prev| self.comparator.compare(&key, prev).is_le()) {
+                    return Err(corrupt(&format!("key at offset {} is not greater than the key before it", offset)));
+                }
+                if self.comparator.compare(&key, &info.last_key).is_gt() {
+                    return Err(corrupt(&format!("key at offset {} is greater than the block's index key", offset)));
+                }
+                if offset == 4 && info.first_key.as_ref().is_some_and(|first_key| *first_key != key) {
+                    return Err(SstError::CorruptIndex(format!("index records the wrong first key for block {}", i)));
+                }
+                if offset == 4
+                    && starts_span
+                    && let Some(prev_index_key) = &prev_index_key
+                    && self.comparator.compare(&key, prev_index_key).is_le()
+                {
+                    return Err(SstError::CorruptIndex(format!(
+                        "first key {} of block {} is not greater than index key {} of block {}",
+                        key.escape_ascii(),
+                        i,
+                        prev_index_key.escape_ascii(),
+                        i - 1
+                    )));
+                }
+                if let Some(bloom) = &self.bloom
+                    && !bloom::may_contain(bloom, &self.comparator.normalize(&key))
+                {
+                    return Err(corrupt(&format!("key at offset {} is missing from the bloom filter", offset)));
+                }
+                if let Some(buckets) = hash_index {
+                    let bucket = buckets[bloom::hash(&self.comparator.normalize(&key)) as usize % buckets.len()];
+                    let interval = restarts.partition_point(|&restart| restart <= offset).checked_sub(1);
+                    if bucket != HASH_BUCKET_COLLISION && Some(bucket as usize) != interval {
+                        return Err(corrupt(&format!("key at offset {} is missing from the hash index", offset)));
+                    }
+                }
+                total_key_bytes += key.len() as u64;
+                total_value_bytes += match value {
+                    RawValue::Inline(value) => value.len() as u64,
+                    RawValue::Blob { len, .. } => len,
+                    RawValue::Tombstone => 0,
+                };
+                entry_offsets.push(offset);
+                block_prev_key.clone_from(&key);
+                prev_key = Some(key);
+                offset = next;
+            }
+
+            if entry_offsets.is_empty() {
+                return Err(corrupt("block has no entries"));
+            }
+            if entry_offsets.len() as u64 != declared {
+                return Err(corrupt(&format!("block declares {} entries but holds {}", declared, entry_offsets.len())));
+            }
+            if restarts.iter().any(|restart| entry_offsets.binary_search(restart).is_err()) {
+                return Err(corrupt("restart point does not start an entry"));
+            }
+            num_entries += declared;
+            if self.version >= 10 && info.cumulative_entries != num_entries {
+                return Err(SstError::CorruptIndex(format!(
+                    "index records {} entries through block {} but the blocks hold {}",
+                    info.cumulative_entries, i, num_entries
+                )));
+            }
+            prev_index_key = Some(info.last_key.clone());
+            progress(expected_offset, file_len);
+        }
+
+        // Each partition's top-level entry must describe the partition's last block
+        for (i, partition) in self.index.iter().enumerate() {
+            let last = self.partition(i)?.last().ok_or_else(|| {
+                SstError::CorruptIndex(format!("index partition {} holds no blocks", i))
+            })?;
+            if last.last_key != partition.last_key
+                || (self.version >= 10 && last.cumulative_entries != partition.cumulative_entries)
+            {
+                return Err(SstError::CorruptIndex(format!(
+                    "top-level index entry {} does not match its partition",
+                    i
+                )));
+            }
+        }
+
+        if let Some(expected) = self.num_entries
+            && expected != num_entries
+        {
+            return Err(SstError::CorruptIndex(format!(
+                "footer declares {} entries but the blocks hold {}",
+                expected, num_entries
+            )));
+        }
+        if let Some(properties) = &self.properties {
+            let corrupt = |msg: &str| SstError::CorruptProperties(format!("{} does not match the file", msg));
+            if properties.num_entries != num_entries {
+                return Err(corrupt("entry count"));
+            }
+            if properties.min_key != self.first_key || properties.max_key != prev_key {
+                return Err(corrupt("key range"));
+            }
+            if properties.total_key_bytes != total_key_bytes || properties.total_value_bytes != total_value_bytes {
+                return Err(corrupt("total key or value size"));
+            }
+        }
+        progress(file_len, file_len);
+        Ok(())
+    }
+
+    // Returns the position of the first block that could contain a key within the start bound,
+    // or the number of blocks if none can
+    pub(crate) fn seek_block(&self, start: Bound<&[u8]>) -> Result<usize> {
+        let before_start = |last_key: &[u8]| match start {
+            Bound::Included(key) => self.comparator.compare(last_key, key).is_lt(),
+            Bound::Excluded(key) => self.comparator.compare(last_key, key).is_le(),
+            Bound::Unbounded => false,
+        };
+        // Both levels are sorted by last_key, so this is a binary search of each
+        let partition_idx = self.index.partition_point(|partition| before_start(&partition.last_key));
+        if partition_idx == self.index.len() {
+            return Ok(self.num_blocks);
+        }
+        let entries = self.partition(partition_idx)?;
+        Ok(self.index[partition_idx].first_block + entries.partition_point(|entry| before_start(&entry.last_key)))
+    }
+
+    /// Returns the number of data blocks in the file.
+    pub fn num_blocks(&self) -> usize {
+        self.num_blocks
+    }
+
+    // Returns the index entry of the data block at position `block_idx`, which must be
+    // less than the number of blocks
+    fn block_info(&self, block_idx: usize) -> Result<&IndexEntryInfo> {
+        let missing = || SstError::CorruptIndex(format!("the index holds no block {}", block_idx));
+        let partition_idx = self
+            .index
+            .partition_point(|partition| partition.first_block <= block_idx)
+            .checked_sub(1)
+            .ok_or_else(missing)?;
+        let entries = self.partition(partition_idx)?;
+        entries.get(block_idx - self.index[partition_idx].first_block).ok_or_else(missing)
+    }
+
+    // Returns the entries of the index partition at `partition_idx`, reading and caching
+    // the partition on first use
+    fn partition(&self, partition_idx: usize) -> Result<&[IndexEntryInfo]> {
+        let partition = &self.index[partition_idx];
+        if let Some(entries) = partition.entries.get() {
+            return Ok(entries);
+        }
+
+        let buf = self.backend.read(partition.offset, partition.size)?;
+        let entries = Self::parse_index(&buf, self.version)?;
+        let expected = self.index.get(partition_idx + 1).map_or(self.num_blocks, |next| next.first_block)
+            - partition.first_block;
+        if entries.len() != expected {
+            return Err(SstError::CorruptIndex(format!(
+                "index partition {} holds {} blocks but the top-level index records {}",
+                partition_idx,
+                entries.len(),
+                expected
+            )));
+        }
+        // Another thread may have loaded the partition meanwhile; either copy will do
+        Ok(partition.entries.get_or_init(|| entries))
+    }
+
+    // Returns false if `key` is certainly not in the file: it sorts before the first key
+    // or the bloom filter rules it out. Checking this first saves reading a block.
+    pub(crate) fn may_contain(&self, key: &[u8]) -> bool {
+        if self.first_key.as_deref().is_some_and(|first_key| self.comparator.compare(key, first_key).is_lt()) {
+            return false;
+        }
+        self.bloom.as_ref().is_none_or(|bloom| bloom::may_contain(bloom, &self.comparator.normalize(key)))
+    }
+
+    // Length of the file in bytes, or of its region for readers from `open_region`
+    pub(crate) fn file_len(&self) -> u64 {
+        self.backend.len()
+    }
+
+    // The order of the file's keys
+    pub(crate) fn comparator(&self) -> &dyn Comparator {
+        &*self.comparator
+    }
+
+    pub(crate) fn comparator_ref(&self) -> &Arc<dyn Comparator> {
+        &self.comparator
+    }
+
+    // Returns the index key of the data block at position `block_idx`: a key `>=` every
+    // key in the block and `<` every key in the next one
+    pub(crate) fn index_key(&self, block_idx: usize) -> Result<&[u8]> {
+        Ok(&self.block_info(block_idx)?.last_key)
+    }
+
+    // Reads the data block at position `block_idx` in the index
+    pub(crate) fn read_block_at(&self, block_idx: usize) -> Result<Arc<Vec<u8>>> {
+        let info = self.block_info(block_idx)?;
+        self.read_block(info.block_offset, info.block_size)
+    }
+
+    // Asks the OS to start reading the data block at position `block_idx` in the
+    // background. Only file-backed readers benefit; other backends ignore it, as do
+    // blocks past the end and partitions that can't be read.
+    pub(crate) fn prefetch_block(&self, block_idx: usize) {
+        if let Backend::File { file, start, .. } = &self.backend
+            && block_idx < self.num_blocks
+            && let Ok(info) = self.block_info(block_idx)
+        {
+            advise_willneed(file, start + info.block_offset, info.block_size);
+        }
+    }
+
+    // Reads and decodes every entry of the data block at position `block_idx`
+    pub(crate) fn read_block_entries(&self, block_idx: usize) -> Result<Vec<(Vec<u8>, Entry)>> {
+        let block = self.read_block_at(block_idx)?;
+        let entries = Self::split_block(&block, self.version)?.entries;
+
+        let mut decoded: Vec<(Vec<u8>, Entry)> = Vec::new();
+        let mut offset = 4;
+        while offset < entries.len() {
+            let prev_key = decoded.last().map(|(key, _)| key.as_slice()).unwrap_or_default();
+            let (key, value, next) = Self::read_entry(entries, offset, prev_key, self.version)?;
+            decoded.push((key, self.resolve(value)?));
+            offset = next;
+        }
+        Ok(decoded)
+    }
+
+    // Reads the data block at position `block_idx` as stored, along with its keys, which the
+    // file it's copied into needs for its bloom filter and properties. Returns None if any
+    // value is a blob, since blob pointers only make sense within this file.
+    pub(crate) fn read_raw_block_at(&self, block_idx: usize) -> Result<Option<RawBlock>> {
+        let info = self.block_info(block_idx)?;
+        let bytes = self.backend.read(info.block_offset, info.block_size)?;
+        let block = self.decode_block(&bytes)?;
+        let entries = Self::split_block(&block, self.version)?.entries;
+
+        let mut keys: Vec<Vec<u8>> = Vec::new();
+        let mut value_bytes = 0;
+        let mut offset = 4;
+        while offset < entries.len() {
+            let prev_key = keys.last().map(Vec::as_slice).unwrap_or_default();
+            let (key, value, next) = Self::read_entry(entries, offset, prev_key, self.version)?;
+            match value {
+                RawValue::Inline(value) => value_bytes += value.len() as u64,
+                RawValue::Blob { .. } => return Ok(None),
+                RawValue::Tombstone => {}
+            }
+            keys.push(key);
+            offset = next;
+        }
+        drop(block);
+        Ok(Some(RawBlock {
+            bytes,
+            keys,
+            value_bytes,
+        }))
+    }
+
+    // Reads a data block through the block cache, if one is enabled
+    fn read_block(&self, block_offset: u64, block_size: u64) -> Result<Arc<Vec<u8>>> {
+        if let Some((cache, file_id)) = &self.block_cache
+            && let Some(block) = cache.get(*file_id, block_offset)
+        {
+            return Ok(block);
+        }
+
+        // The lock isn't held while reading, so two threads missing on the same
+        // block may both read it; the second insert simply replaces the first
+        let block = Arc::new(self.read_block_uncached(block_offset, block_size)?);
+        if let Some((cache, file_id)) = &self.block_cache {
+            cache.insert(*file_id, block_offset, block.clone());
+        }
+        Ok(block)
+    }
+
+    // Reads a data block from the file, verifying its checksum and decompressing it
+    fn read_block_uncached(&self, block_offset: u64, block_size: u64) -> Result<Vec<u8>> {
+        let block_buf = self.backend.read(block_offset, block_size)?;
+        Ok(self.decode_block(&block_buf)?.into_owned())
+    }
+
+    // Reads a data block from the file as stored, verifying its checksum but leaving it compressed
+    pub(crate) fn read_raw_block(&self, block_offset: u64, block_size: u64) -> Result<Vec<u8>> {
+        let block_buf = self.backend.read(block_offset, block_size)?;
+        if self.version >= 2 {
+            checksum::verify_trailer(&block_buf)?;
+        }
+        Ok(block_buf)
+    }
+
+    // Verifies a raw block's checksum and decompresses it, borrowing from `block_buf`
+    // when the block isn't compressed
+    pub(crate) fn decode_block<'a>(&self, block_buf: &'a [u8]) -> Result<Cow<'a, [u8]>> {
+        // Blocks written since version 2 carry a trailer with an optional checksum
+        if self.version < 2 {
+            return Ok(Cow::Borrowed(block_buf));
+        }
+        let block = checksum::verify_trailer(block_buf)?;
+
+        // Blocks written since version 3 start with a compression flag
+        if self.version < 3 {
+            return Ok(Cow::Borrowed(block));
+        }
+        compression::decompress_block(block)
+    }
+
+    // Finds the restart interval that could hold the key, then scans it linearly.
+    // Blocks with a hash index (version 12+) look the interval up by the key's hash;
+    // otherwise, or when keys from several intervals share its bucket, the restart
+    // points are binary searched for the closest one at or before the key.
+    // Returns the matching entry's stored value.
+    pub(crate) fn search_in_block<'a>(&self, buf: &'a [u8], search_key: &[u8]) -> Result<Option<RawValue<'a>>> {
+        let version = self.version;
+        let block = Self::split_block(buf, version)?;
+
+        if let Some(buckets) = block.hash_index {
+            match buckets[bloom::hash(&self.comparator.normalize(search_key)) as usize % buckets.len()] {
+                // No key in the block hashes to this bucket
+                HASH_BUCKET_EMPTY => return Ok(None),
+                HASH_BUCKET_COLLISION => {}
+                restart_idx => return self.scan_restart_interval(&block, restart_idx as usize, search_key),
+            }
+        }
+
+        // Number of restart points whose key is <= the search key.
+        // Restart entries store their full key, so no previous key is needed.
+        let (mut low, mut high) = (0, block.restarts.len());
+        let mut restart_key = Vec::new();
+        while low < high {
+            let mid = low + (high - low) / 2;
+            restart_key.clear();
+            Self::read_entry_into(block.entries, block.restarts[mid], &mut restart_key, version)?;
+            if self.comparator.compare(&restart_key, search_key).is_le() {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        match low.checked_sub(1) {
+            Some(restart_idx) => self.scan_restart_interval(&block, restart_idx, search_key),
+            None => Ok(None),
+        }
+    }
+
+    // Scans the entries from the restart point at `restart_idx` up to the next one for the key
+    fn scan_restart_interval<'a>(
+        &self,
+        block: &BlockParts<'a>,
+        restart_idx: usize,
+        search_key: &[u8],
+    ) -> Result<Option<RawValue<'a>>> {
+        let mut offset = *block
+            .restarts
+            .get(restart_idx)
+            .ok_or_else(|| SstError::CorruptBlock("hash index points past the restart array".to_string()))?;
+        let end = block.restarts.get(restart_idx + 1).copied().unwrap_or(block.entries.len());
+        let mut key = Vec::new();
+        while offset < end {
+            let (entry, next) = Self::read_entry_into(block.entries, offset, &mut key, self.version)?;
+            if self.comparator.compare(&key, search_key).is_eq() {
+                return Ok(Some(entry));
+            }
+            offset = next;
+        }
+
+        Ok(None)
+    }
+
+    // Returns the last key of a decoded data block, decoding only its last restart interval
+    pub(crate) fn last_key_in_block(buf: &[u8], version: u32) -> Result<Vec<u8>> {
+        let block = Self::split_block(buf, version)?;
+        let mut offset = block.restarts.last().copied().unwrap_or(4);
+        let mut key = Vec::new();
+        while offset < block.entries.len() {
+            (_, offset) = Self::read_entry_into(block.entries, offset, &mut key, version)?;
+        }
+        Ok(key)
+    }
+
+    // Splits a decoded block into its entry region (including the num_entries header),
+    // the offsets of its restart points and its hash index, if it has one
+    pub(crate) fn split_block(buf: &[u8], version: u32) -> Result<BlockParts<'_>> {
+        let corrupt = |msg: &str| SstError::CorruptBlock(msg.to_string());
+
+        let tail_start = buf.len().checked_sub(4).ok_or_else(|| corrupt("block is too small"))?;
+        let tail = u32::from_le_bytes(buf[tail_start..].try_into().unwrap());
+        // Since version 12 the high bit of the restart count marks a hash index
+        let (num_restarts, has_hash_index) = if version >= 12 {
+            ((tail & !HASH_INDEX_FLAG) as usize, tail & HASH_INDEX_FLAG != 0)
+        } else {
+            (tail as usize, false)
+        };
+
+        let mut restarts_end = tail_start;
+        let mut hash_index = None;
+        if has_hash_index {
+            let buckets_end = restarts_end.checked_sub(2).ok_or_else(|| corrupt("hash index exceeds the block"))?;
+            let num_buckets = u16::from_le_bytes(buf[buckets_end..restarts_end].try_into().unwrap()) as usize;
+            let buckets_start = buckets_end
+                .checked_sub(num_buckets)
+                .filter(|_| num_buckets > 0)
+                .ok_or_else(|| corrupt("hash index exceeds the block"))?;
+            hash_index = Some(&buf[buckets_start..buckets_end]);
+            restarts_end = buckets_start;
+        }
+
+        let restarts_start = num_restarts
+            .checked_mul(4)
+            .and_then(|restarts_len| restarts_end.checked_sub(restarts_len))
+            .filter(|&start| start >= 4)
+            .ok_or_else(|| corrupt("restart array exceeds the block"))?;
+
+        let restarts: Vec<usize> = buf[restarts_start..restarts_end]
+            .chunks_exact(4)
+            .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap()) as usize)
+            .collect();
+        if restarts.iter().any(|&offset| offset < 4 || offset >= restarts_start) {
+            return Err(corrupt("restart point lies outside the block entries"));
+        }
+        // Reads walk the entries without counting them, so a block declaring none must
+        // hold none for every read to see it as empty
+        if buf[..4] == [0; 4] && restarts_start > 4 {
+            return Err(corrupt("block declares no entries but holds some"));
+        }
+
+        Ok(BlockParts {
+            entries: &buf[..restarts_start],
+            restarts,
+            hash_index,
+        })
+    }
+
+    // Turns a stored value into an entry, reading blob values from the file
+    pub(crate) fn resolve(&self, value: RawValue<'_>) -> Result<Entry> {
+        match value {
+            RawValue::Inline(value) => Ok(Entry::Value(value.to_vec())),
+            RawValue::Blob { offset, len } => Ok(Entry::Value(self.backend.read(offset, len)?)),
+            RawValue::Tombstone => Ok(Entry::Tombstone),
+        }
+    }
+
+    // The format version of the file, which decides how its data blocks are decoded
+    pub(crate) fn version(&self) -> u32 {
+        self.version
+    }
+
+    // Decodes the entry starting at `offset` like `read_entry_into`, returning its key as
+    // a new buffer rebuilt from `prev_key`, the key of the preceding entry
+    pub(crate) fn read_entry<'a>(
+        buf: &'a [u8],
+        offset: usize,
+        prev_key: &[u8],
+        version: u32,
+    ) -> Result<DecodedEntry<'a>> {
+        let mut key = prev_key.to_vec();
+        let (value, next) = Self::read_entry_into(buf, offset, &mut key, version)?;
+        Ok((key, value, next))
+    }
+
+    // Decodes the entry starting at `offset` in a file of the given format version, returning
+    // its stored value and the offset of the next entry. `key` must hold the key of the
+    // preceding entry, or any key sharing the prefix this entry's key shares with it, and is
+    // rebuilt into this entry's key in place: cut down to the shared prefix, then extended with
+    // the rest. Decoding a run of entries into one buffer this way copies each key's new bytes
+    // only, where rebuilding every key in a new buffer copies the shared prefix each time.
+    // `key` is left as it was if the entry can't be decoded.
+    // Entry Format: [key_len: u32][key][val_len: u32][val]
+    // Entry Format (version 6+): [shared_len: u32][unshared_len: u32][unshared key bytes][val_len: u32][val]
+    // Entry Format (version 7+): [shared_len: u32][unshared_len: u32][unshared key bytes][value_type: u8][val_len: u32][val]
+    // Blob values (version 9+) store [blob_offset: u64][blob_len: u64] as their value.
+    // Since version 11 shared_len, unshared_len and val_len are LEB128 varints.
+    pub(crate) fn read_entry_into<'a>(
+        buf: &'a [u8],
+        offset: usize,
+        key: &mut Vec<u8>,
+        version: u32,
+    ) -> Result<(RawValue<'a>, usize)> {
+        let truncated = || SstError::CorruptBlock(format!("entry at offset {} is truncated", offset));
+
+        let mut rest = buf.get(offset..).ok_or_else(truncated)?;
+        let read_len = |rest: &mut &[u8]| -> Result<usize> {
+            let len = if version >= 11 {
+                varint::read(rest).and_then(|len| usize::try_from(len).ok())
+            } else {
+                read_u32(rest).map(|len| len as usize)
+            };
+            len.ok_or_else(truncated)
+        };
+
+        let shared_len = if version >= 6 { read_len(&mut rest)? } else { 0 };
+        if shared_len > key.len() {
+            return Err(SstError::CorruptBlock(format!(
+                "entry at offset {} shares more bytes than the previous key has",
+                offset
+            )));
+        }
+        let key_len = read_len(&mut rest)?;
+        let unshared = read_bytes(&mut rest, key_len).ok_or_else(truncated)?;
+        let value_type = if version >= 7 {
+            read_bytes(&mut rest, 1).ok_or_else(truncated)?[0]
+        } else {
+            VALUE_TYPE_VALUE
+        };
+        let val_len = read_len(&mut rest)?;
+        let value = read_bytes(&mut rest, val_len).ok_or_else(truncated)?;
+
+        let value = match value_type {
+            VALUE_TYPE_VALUE => RawValue::Inline(value),
+            VALUE_TYPE_TOMBSTONE => RawValue::Tombstone,
+            VALUE_TYPE_BLOB if version >= 9 => {
+                let mut pointer = value;
+                match (read_u64(&mut pointer), read_u64(&mut pointer)) {
+                    (Some(offset), Some(len)) if pointer.is_empty() => RawValue::Blob { offset, len },
+                    _ => {
+                        return Err(SstError::CorruptBlock(format!(
+                            "entry at offset {} has a malformed blob pointer",
+                            offset
+                        )));
+                    }
+                }
+            }
+            other => {
+                return Err(SstError::CorruptBlock(format!(
+                    "entry at offset {} has unknown value type {}",
+                    offset, other
+                )));
+            }
+        };
+        key.truncate(shared_len);
+        key.extend_from_slice(unshared);
+        Ok((value, buf.len() - rest.len()))
+    }
+}
+
+// Splits `len` bytes off the front of `buf`, or returns None if not enough remain
+pub(crate) fn read_bytes<'a>(buf: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    let (bytes, rest) = buf.split_at_checked(len)?;
+    *buf = rest;
+    Some(bytes)
+}
+
+pub(crate) fn read_u32(buf: &mut &[u8]) -> Option<u32> {
+    read_bytes(buf, 4).map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+pub(crate) fn read_u64(buf: &mut &[u8]) -> Option<u64> {
+    read_bytes(buf, 8).map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+}
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::checksum::Checksum;
+    use crate::compression::Compression;
+    use crate::format::FORMAT_VERSION;
+    use crate::test_util::{FRUIT, TempPath, fruit, keys};
+    use crate::writer::SstWriterBuilder;
+
+    fn key(i: u32) -> Vec<u8> {
+        format!("key{:05}", i).into_bytes()
+    }
+
+    // Writes the keys of the even numbers below `2 * n`, each with its key as the value, to
+    // an in-memory SST, so the odd numbers' keys fall between them
+    fn even_keys(n: u32, options: SstWriterBuilder) -> SstReader {
+        let mut bytes = Vec::new();
+        let mut writer = options.build_with_writer(&mut bytes);
+        for i in 0..n {
+            writer.add(&key(i * 2), &key(i * 2)).unwrap();
+        }
+        writer.finish().unwrap();
+        SstReader::from_bytes(bytes).unwrap()
+    }
+
+    #[test]
+    fn binary_search_picks_the_block_a_linear_scan_would() {
+        for options in [
+            SstWriterBuilder::new().block_size(64),
+            SstWriterBuilder::new().block_size(64).index_partition_size(256),
+        ] {
+            let reader = even_keys(2000, options);
+            let entries = reader.index_entries().unwrap();
+            assert!(entries.len() > 100, "{} blocks", entries.len());
+            for i in 0..4002 {
+                let key = key(i);
+                let linear = entries.iter().position(|entry| entry.last_key >= key.as_slice());
+                let block_idx = reader.seek_block(Bound::Included(&key)).unwrap();
+                assert_eq!(block_idx, linear.unwrap_or(entries.len()), "key {}", i);
+                let expected = (i % 2 == 0 && i < 4000).then(|| key.clone());
+                assert_eq!(reader.get(&key).unwrap(), expected);
+            }
+            assert_eq!(reader.seek_block(Bound::Included(b"zzz")).unwrap(), entries.len());
+            assert_eq!(reader.get(b"zzz").unwrap(), None);
+            assert_eq!(reader.get(b"a").unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn concat_values_joins_the_values_in_the_range() {
+        let mut bytes = Vec::new();
+        let mut writer = SstWriterBuilder::new().block_size(64).build_with_writer(&mut bytes);
+        writer.delete(b"a").unwrap();
+        for i in 0..100 {
+            writer.add(&key(i), format!("{}", i).as_bytes()).unwrap();
+        }
+        writer.delete(b"z").unwrap();
+        writer.finish().unwrap();
+        let reader = SstReader::from_bytes(bytes).unwrap();
+
+        let all: Vec<_> = (0..100).map(|i| i.to_string()).collect();
+        let concat = |start, end, separator| reader.concat_values(start, end, separator).unwrap();
+        assert_eq!(concat(Bound::Unbounded, Bound::Unbounded, Some(&b", "[..])), all.join(", ").as_bytes());
+        assert_eq!(concat(Bound::Unbounded, Bound::Unbounded, None), all.concat().as_bytes());
+        let (start, end) = (key(10), key(13));
+        assert_eq!(concat(Bound::Excluded(&start), Bound::Included(&end), Some(b"|")), b"11|12|13");
+        assert_eq!(concat(Bound::Included(&end), Bound::Excluded(&end), Some(b"|")), b"");
+        // Only tombstones in the range
+        assert_eq!(concat(Bound::Included(b"x"), Bound::Unbounded, Some(b"|")), b"");
+    }
+
+    #[test]
+    fn index_entries_past_the_last_block_are_none() {
+        for options in [
+            SstWriterBuilder::new().block_size(64),
+            SstWriterBuilder::new().block_size(64).index_partition_size(256),
+        ] {
+            let reader = even_keys(500, options);
+            let entries = reader.index_entries().unwrap();
+            let num_blocks = reader.num_blocks();
+            assert_eq!(entries.len(), num_blocks);
+            for (block_idx, entry) in entries.iter().enumerate() {
+                assert_eq!(reader.index_entry(block_idx).unwrap().as_ref(), Some(entry));
+            }
+            assert_eq!(reader.index_entry(num_blocks).unwrap(), None);
+            assert_eq!(reader.index_entry(usize::MAX).unwrap(), None);
+            assert!(matches!(reader.block_info(num_blocks), Err(SstError::CorruptIndex(_))));
+        }
+
+        let reader = even_keys(0, SstWriterBuilder::new());
+        assert_eq!(reader.num_blocks(), 0);
+        assert_eq!(reader.index_entry(0).unwrap(), None);
+        assert!(matches!(reader.block_info(0), Err(SstError::CorruptIndex(_))));
+    }
+
+    #[test]
+    fn prefix_compressed_keys_are_rebuilt_as_inserted() {
+        // Runs of keys sharing long prefixes, so most entries store only a few bytes of their key
+        let mut keys = Vec::new();
+        for prefix in ["user/alice", "user/alicia", "user/bob", "user/bobby/profile", "zeta"] {
+            keys.extend((0..30).map(|i| format!("{}/{:03}", prefix, i).into_bytes()));
+        }
+        let mut bytes = Vec::new();
+        let mut writer = SstWriterBuilder::new().block_size(512).restart_interval(8).build_with_writer(&mut bytes);
+        for (i, key) in keys.iter().enumerate() {
+            match i % 7 {
+                3 => writer.delete(key).unwrap(),
+                _ => writer.add(key, format!("v{}", i).as_bytes()).unwrap(),
+            }
+        }
+        writer.finish().unwrap();
+        let reader = SstReader::from_bytes(bytes).unwrap();
+        assert!(reader.num_blocks() > 2, "{} blocks", reader.num_blocks());
+
+        // Decode every block through one key buffer, as the iterators do
+        let mut rebuilt = Vec::new();
+        let mut key = Vec::new();
+        for block_idx in 0..reader.num_blocks() {
+            let block = reader.read_block_at(block_idx).unwrap();
+            let entries = SstReader::split_block(&block, reader.version()).unwrap().entries;
+            let mut offset = 4;
+            while offset < entries.len() {
+                offset = SstReader::read_entry_into(entries, offset, &mut key, reader.version()).unwrap().1;
+                rebuilt.push(key.clone());
+            }
+        }
+        assert_eq!(rebuilt, keys);
+
+        let live: Vec<_> = keys.iter().enumerate().filter(|(i, _)| i % 7 != 3).map(|(_, key)| key.clone()).collect();
+        let scan = |start| reader.range(start, Bound::Unbounded).map(|entry| entry.unwrap().0).collect::<Vec<_>>();
+        assert_eq!(scan(Bound::Unbounded), live);
+        // Seeking into the middle of a run rebuilds the keys from the restart point before it
+        let start = b"user/bob/015";
+        let expected: Vec<_> = live.iter().filter(|key| key.as_slice() >= &start[..]).cloned().collect();
+        assert_eq!(scan(Bound::Included(start)), expected);
+    }
+
+    // Returns the index block of an in-memory SST, as the footer locates it
+    fn index_block(bytes: &[u8]) -> &[u8] {
+        let footer = Footer::read_from(&mut Cursor::new(bytes)).unwrap();
+        &bytes[footer.index_offset as usize..(footer.index_offset + footer.index_size) as usize]
+    }
+
+    #[test]
+    fn truncated_index_is_an_error() {
+        for options in [
+            SstWriterBuilder::new().block_size(64),
+            SstWriterBuilder::new().block_size(64).index_partition_size(128),
+        ] {
+            let mut bytes = Vec::new();
+            let mut writer = options.build_with_writer(&mut bytes);
+            for i in 0..100 {
+                writer.add(&key(i), b"value").unwrap();
+            }
+            writer.finish().unwrap();
+            let index = index_block(&bytes);
+            assert!(SstReader::parse_top_level_index(index, FORMAT_VERSION).is_ok());
+            for len in 0..index.len() {
+                let result = SstReader::parse_top_level_index(&index[..len], FORMAT_VERSION);
+                assert!(matches!(result, Err(SstError::CorruptIndex(_))), "index cut to {} bytes", len);
+            }
+        }
+
+        // Counts larger than the buffer could hold are rejected before allocating for them
+        let flat = [INDEX_TYPE_FLAT, 0xFF, 0xFF, 0xFF, 0xFF];
+        let result = SstReader::parse_top_level_index(&flat, FORMAT_VERSION);
+        assert!(matches!(result, Err(SstError::CorruptIndex(_))));
+        let partitioned = [INDEX_TYPE_PARTITIONED, 0xFF, 0xFF, 0xFF, 0xFF];
+        let result = SstReader::parse_top_level_index(&partitioned, FORMAT_VERSION);
+        assert!(matches!(result, Err(SstError::CorruptIndex(_))));
+    }
+
+    #[test]
+    fn corrupt_blocks_are_errors_not_panics() {
+        // A xorshift generator, so failures reproduce
+        let mut state = 0x2545_F491_4F6C_DD1Du64;
+        let mut random = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as usize
+        };
+        let probes: Vec<Vec<u8>> = [0, 40, 41, 44, 50, 399, 1000].into_iter().map(key).collect();
+
+        for options in [SstWriterBuilder::new(), SstWriterBuilder::new().hash_index(true)] {
+            let reader = even_keys(200, options.block_size(256).restart_interval(4));
+            let block = reader.read_block_at(1).unwrap();
+            assert!(reader.search_in_block(&block[..3], &probes[0]).is_err());
+            for len in 0..block.len() {
+                for probe in &probes {
+                    let _ = reader.search_in_block(&block[..len], probe);
+                }
+            }
+            for _ in 0..5000 {
+                let mut corrupt = block.to_vec();
+                corrupt.truncate(block.len() - random() % 8);
+                let i = random() % corrupt.len();
+                corrupt[i] ^= (random() as u8) | 1;
+                for probe in &probes {
+                    let _ = reader.search_in_block(&corrupt, probe);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn files_too_small_for_a_footer_are_an_error() {
+        let path = TempPath::new("tiny.sst");
+        for len in [0, 10] {
+            fs::write(&path, vec![0xAB; len]).unwrap();
+            for result in [SstReader::open(&path), SstReader::open_lazy(&path), SstReader::open_in_memory(&path)] {
+                assert!(matches!(result, Err(SstError::UnexpectedEof)), "{} bytes", len);
+            }
+        }
+    }
+
+    fn write_even_keys(path: &Path, n: u32) {
+        let mut writer = SstWriterBuilder::new().block_size(256).build(path).unwrap();
+        for i in 0..n {
+            writer.add(&key(i * 2), &key(i * 2)).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn a_shared_reader_serves_concurrent_gets() {
+        let path = TempPath::new("shared.sst");
+        write_even_keys(&path, 5000);
+        let reader = Arc::new(SstReader::open(&path).unwrap());
+        let threads: Vec<_> = (0..8)
+            .map(|t| {
+                let reader = Arc::clone(&reader);
+                thread::spawn(move || {
+                    // Each thread walks the keys from a different start, so they read different blocks
+                    for i in (0..10_000).map(|i| (i + t * 1250) % 10_000) {
+                        let expected = (i % 2 == 0).then(|| key(i));
+                        assert_eq!(reader.get(&key(i)).unwrap(), expected, "key {}", i);
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn mmap_reads_what_a_file_reader_reads() {
+        let path = TempPath::new("mmap.sst");
+        write_even_keys(&path, 1000);
+        let file = SstReader::open(&path).unwrap();
+        let mmap = SstReader::open_mmap(&path).unwrap();
+        for i in 0..2001 {
+            assert_eq!(mmap.get(&key(i)).unwrap(), file.get(&key(i)).unwrap(), "key {}", i);
+        }
+        assert_eq!(mmap.iter().count(), 1000);
+    }
+
+    #[test]
+    fn mmap_of_a_replaced_file_serves_the_old_contents() {
+        let path = TempPath::new("mmap-replaced.sst");
+        let replacement = TempPath::new("mmap-replacement.sst");
+        write_even_keys(&path, 1000);
+        let reader = SstReader::open_mmap(&path).unwrap();
+        write_even_keys(&replacement, 10);
+        fs::rename(&replacement, &path).unwrap();
+        assert!(reader.is_stale().unwrap());
+        assert_eq!(reader.get(&key(1998)).unwrap(), Some(key(1998)));
+        assert_eq!(reader.iter().count(), 1000);
+    }
+
+    // Truncating a mapped file kills the process reading it, so the read happens in a
+    // child process running just this test, which the parent expects to die of SIGBUS
+    #[cfg(unix)]
+    #[test]
+    fn mmap_of_a_truncated_file_raises_sigbus() {
+        use std::os::unix::process::ExitStatusExt;
+        use std::process::{Command, Stdio};
+
+        const CHILD_PATH: &str = "SST_TEST_TRUNCATED_MMAP";
+        if let Some(path) = std::env::var_os(CHILD_PATH) {
+            let reader = SstReader::open_mmap(Path::new(&path)).unwrap();
+            File::options().write(true).open(&path).unwrap().set_len(0).unwrap();
+            let _ = reader.get(&key(1000));
+            unreachable!("read from a truncated mapping");
+        }
+        let path = TempPath::new("mmap-truncated.sst");
+        write_even_keys(&path, 1000);
+        let status = Command::new(std::env::current_exe().unwrap())
+            .args(["--exact", "reader::tests::mmap_of_a_truncated_file_raises_sigbus"])
+            .env(CHILD_PATH, &*path)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .unwrap();
+        assert_eq!(status.signal(), Some(libc::SIGBUS), "{}", status);
+    }
+
+    #[test]
+    fn keys_the_bloom_filter_rejects_read_no_block() {
+        for bits_per_key in [0, 4, 10, 20] {
+            let mut bytes = Vec::new();
+            let mut writer = SstWriterBuilder::new()
+                .block_size(256)
+                .bloom_bits_per_key(bits_per_key)
+                .build_with_writer(&mut bytes);
+            for i in 0..1000 {
+                writer.add(&key(i * 2), b"value").unwrap();
+            }
+            let stats = writer.finish().unwrap();
+            // About bits_per_key bits for each key, rounded up to whole bytes
+            assert!(stats.bloom_bytes >= 1000 * bits_per_key as u64 / 8, "{} bits per key", bits_per_key);
+            assert!(stats.bloom_bytes <= 1000 * bits_per_key as u64 / 8 + 8, "{} bits per key", bits_per_key);
+            let reader = SstReader::from_bytes(bytes).unwrap().with_block_cache(0);
+
+            // The odd keys all fall within the file's key range, so only the filter can rule them out
+            let mut rejected = 0;
+            for i in (1..2000).step_by(2) {
+                let before = reader.cache_stats().unwrap();
+                assert_eq!(reader.get(&key(i)).unwrap(), None);
+                if !reader.may_contain(&key(i)) {
+                    assert_eq!(reader.cache_stats().unwrap(), before, "key {} read a block", i);
+                    rejected += 1;
+                }
+            }
+            match bits_per_key {
+                0 => assert_eq!(rejected, 0),
+                4 => assert!(rejected > 700, "{} of 1000 rejected", rejected),
+                _ => assert!(rejected > 950, "{} of 1000 rejected", rejected),
+            }
+            for i in (0..2000).step_by(2) {
+                assert_eq!(reader.get(&key(i)).unwrap().as_deref(), Some(&b"value"[..]));
+            }
+        }
+    }
+
+    #[test]
+    fn multi_get_reads_each_block_once_whatever_the_key_order() {
+        let reader = even_keys(1000, SstWriterBuilder::new().block_size(256));
+        assert!(reader.num_blocks() > 20, "{} blocks", reader.num_blocks());
+        // Every key, present or not, in a scrambled order, along with keys outside the file
+        let mut probes: Vec<Vec<u8>> = (0..2000).map(|i| key(i * 7919 % 2000)).collect();
+        probes.extend([b"a".to_vec(), b"zzz".to_vec(), key(0), key(1998)]);
+        let probe_refs: Vec<&[u8]> = probes.iter().map(Vec::as_slice).collect();
+
+        let values = reader.multi_get(&probe_refs).unwrap();
+        let expected: Vec<_> = probes.iter().map(|key| reader.get(key).unwrap()).collect();
+        assert_eq!(values, expected);
+        assert_eq!(values.iter().flatten().count(), 1002);
+        assert_eq!(values[2000..], [None, None, Some(key(0)), Some(key(1998))]);
+
+        // The cache holds nothing, so every block read is a miss
+        let reader = even_keys(1000, SstWriterBuilder::new().block_size(256)).with_block_cache(0);
+        reader.multi_get(&probe_refs).unwrap();
+        assert_eq!(reader.cache_stats().unwrap().misses, reader.num_blocks() as u64);
+    }
+
+    #[test]
+    fn contains_key_agrees_with_get() {
+        let mut bytes = Vec::new();
+        let mut writer = SstWriterBuilder::new().block_size(128).blob_threshold(16).build_with_writer(&mut bytes);
+        for i in 0..300 {
+            match i % 3 {
+                0 => writer.add(&key(i * 2), b"short").unwrap(),
+                1 => writer.add(&key(i * 2), &[b'x'; 40]).unwrap(),
+                _ => writer.delete(&key(i * 2)).unwrap(),
+            }
+        }
+        writer.finish().unwrap();
+        let reader = SstReader::from_bytes(bytes).unwrap();
+
+        let mut present = 0;
+        for probe in (0..601).map(key).chain([b"a".to_vec(), b"zzz".to_vec()]) {
+            let contains = reader.contains_key(&probe).unwrap();
+            assert_eq!(contains, reader.get(&probe).unwrap().is_some(), "key {}", probe.escape_ascii());
+            present += contains as usize;
+        }
+        assert_eq!(present, 200);
+    }
+
+    #[test]
+    fn floor_and_ceil_find_the_nearest_keys() {
+        let reader = fruit();
+        let key_of = |entry: Option<(Vec<u8>, Vec<u8>)>| entry.map(|(key, _)| String::from_utf8(key).unwrap());
+        let floor = |key: &str| key_of(reader.floor(key.as_bytes()).unwrap());
+        let ceil = |key: &str| key_of(reader.ceil(key.as_bytes()).unwrap());
+        // Between existing keys
+        assert_eq!((floor("blueberry").as_deref(), ceil("blueberry").as_deref()), (Some("banana"), Some("cherry")));
+        assert_eq!((floor("coconut").as_deref(), ceil("coconut").as_deref()), (Some("cherry"), Some("date")));
+        // On existing keys
+        assert_eq!((floor("date").as_deref(), ceil("date").as_deref()), (Some("date"), Some("date")));
+        assert_eq!(reader.floor(b"apple").unwrap(), Some((b"apple".to_vec(), FRUIT[0].1.as_bytes().to_vec())));
+        // Before the first key and after the last
+        assert_eq!((floor("aardvark"), ceil("aardvark").as_deref()), (None, Some("apple")));
+        assert_eq!((floor("fig").as_deref(), ceil("fig")), (Some("elderberry"), None));
+
+        // Across blocks, skipping deleted keys
+        let mut bytes = Vec::new();
+        let mut writer = SstWriterBuilder::new().block_size(64).build_with_writer(&mut bytes);
+        for i in 0..200 {
+            match i % 20 < 10 {
+                true => writer.add(&key(i * 2), b"value").unwrap(),
+                false => writer.delete(&key(i * 2)).unwrap(),
+            }
+        }
+        writer.finish().unwrap();
+        let reader = SstReader::from_bytes(bytes).unwrap();
+        assert!(reader.num_blocks() > 20, "{} blocks", reader.num_blocks());
+        let live = |i: &u32| i.is_multiple_of(2) && i / 2 % 20 < 10 && *i < 400;
+        for i in 0..401 {
+            let expected_floor = (0..=i).rev().find(live).map(key);
+            let expected_ceil = (i..400).find(live).map(key);
+            assert_eq!(reader.floor(&key(i)).unwrap().map(|(key, _)| key), expected_floor, "key {}", i);
+            assert_eq!(reader.ceil(&key(i)).unwrap().map(|(key, _)| key), expected_ceil, "key {}", i);
+        }
+    }
+
+    #[test]
+    fn nth_agrees_with_indexing_the_entries() {
+        for options in [
+            SstWriterBuilder::new().block_size(64),
+            SstWriterBuilder::new().block_size(64).index_partition_size(256),
+        ] {
+            let mut bytes = Vec::new();
+            let mut writer = options.build_with_writer(&mut bytes);
+            for i in 0..500 {
+                match i % 7 {
+                    3 => writer.delete(&key(i)).unwrap(),
+                    _ => writer.add(&key(i), format!("{}", i).as_bytes()).unwrap(),
+                }
+            }
+            writer.finish().unwrap();
+            let reader = SstReader::from_bytes(bytes).unwrap();
+            assert!(reader.num_blocks() > 20, "{} blocks", reader.num_blocks());
+
+            let entries: Vec<_> = reader.iter_entries().map(Result::unwrap).collect();
+            assert_eq!(entries.len(), 500);
+            for (n, entry) in entries.iter().enumerate() {
+                assert_eq!(reader.nth(n as u64).unwrap().as_ref(), Some(entry), "entry {}", n);
+            }
+            assert_eq!(reader.nth(500).unwrap(), None);
+            assert_eq!(reader.nth(u64::MAX).unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn lookups_through_the_hash_index_find_every_key() {
+        for restart_interval in [1, 4, 16] {
+            let options = SstWriterBuilder::new().block_size(512).restart_interval(restart_interval);
+            let plain = even_keys(2000, options.clone());
+            let hashed = even_keys(2000, options.hash_index(true));
+            for block_idx in 0..hashed.num_blocks() {
+                let block = hashed.read_block_at(block_idx).unwrap();
+                assert!(SstReader::split_block(&block, hashed.version()).unwrap().hash_index.is_some());
+            }
+
+            for i in 0..4001 {
+                let expected = (i % 2 == 0 && i < 4000).then(|| key(i));
+                assert_eq!(hashed.get(&key(i)).unwrap(), expected, "key {}", i);
+                assert_eq!(plain.get(&key(i)).unwrap(), expected, "key {}", i);
+            }
+            // Keys sharing a prefix with stored keys
+            for probe in [&b"key"[..], b"key0", b"key000000", b"key01999x", b"zzz"] {
+                assert_eq!(hashed.get(probe).unwrap(), None);
+            }
+        }
+    }
+
+    // Overwrites handle `i` of the footer of an in-memory SST: 0 is the index offset, 1 the
+    // index size
+    fn set_footer_handle(bytes: &mut [u8], i: usize, value: u64) {
+        let start = bytes.len() - FILE_CHECKSUM_END as usize - (8 - i) * 8;
+        bytes[start..start + 8].copy_from_slice(&value.to_le_bytes());
+    }
+
+    #[test]
+    fn an_index_offset_outside_the_file_is_a_corrupt_index() {
+        let mut bytes = Vec::new();
+        let mut writer = SstWriterBuilder::new().block_size(64).build_with_writer(&mut bytes);
+        for i in 0..100 {
+            writer.add(&key(i), b"value").unwrap();
+        }
+        writer.finish().unwrap();
+        let footer = Footer::read_from(&mut Cursor::new(&bytes)).unwrap();
+        let data_len = bytes.len() as u64 - footer.len;
+
+        let expected = |offset| {
+            format!(
+                "footer places the index block at offset {} with size {}, past the {} bytes before the footer",
+                offset, footer.index_size, data_len
+            )
+        };
+
+        let path = TempPath::new("corrupt-index-offset.sst");
+        // Just past the last byte the index could end at, far past the file, and so far
+        // that the index's end overflows
+        for offset in [data_len - footer.index_size + 1, 1 << 40, u64::MAX - 1] {
+            let mut corrupt = bytes.clone();
+            set_footer_handle(&mut corrupt, 0, offset);
+            fs::write(&path, &corrupt).unwrap();
+            for result in [SstReader::open(&path), SstReader::open_lazy(&path), SstReader::from_bytes(corrupt)] {
+                match result {
+                    Err(SstError::CorruptIndex(msg)) => assert_eq!(msg, expected(offset)),
+                    Err(e) => panic!("offset {}: {}", offset, e),
+                    Ok(_) => panic!("offset {} was accepted", offset),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn get_ref_returns_the_bytes_get_does() {
+        let path = TempPath::new("get-ref.sst");
+        for compression in [Compression::None, Compression::Snappy] {
+            let mut writer = SstWriterBuilder::new()
+                .block_size(256)
+                .blob_threshold(64)
+                .compression(compression)
+                .build(&path)
+                .unwrap();
+            for i in 0..500 {
+                match i % 5 {
+                    0 => writer.delete(&key(i * 2)).unwrap(),
+                    1 => writer.add(&key(i * 2), &[i as u8; 100]).unwrap(),
+                    _ => writer.add(&key(i * 2), &key(i * 2)).unwrap(),
+                }
+            }
+            writer.finish().unwrap();
+
+            let file = SstReader::open(&path).unwrap();
+            let mmap = SstReader::open_mmap(&path).unwrap();
+            let memory = SstReader::open_in_memory(&path).unwrap();
+            for (reader, borrows) in [(&file, false), (&mmap, true), (&memory, true)] {
+                // Values in uncompressed blocks of a mapped or in-memory file are borrowed from it
+                let borrows = borrows && compression == Compression::None;
+                for i in 0..1001 {
+                    let value = reader.get_ref(&key(i)).unwrap();
+                    assert_eq!(matches!(value, Some(Cow::Borrowed(_))), borrows && value.is_some(), "key {}", i);
+                    assert_eq!(value.map(Cow::into_owned), file.get(&key(i)).unwrap(), "key {}", i);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn value_readers_stream_large_values() {
+        let path = TempPath::new("value-reader.sst");
+        let large: Vec<u8> = (0..3_000_000u32).map(|i| (i % 251) as u8).collect();
+        let mut writer = SstWriterBuilder::new().blob_threshold(1024).build(&path).unwrap();
+        writer.add(b"a", b"small").unwrap();
+        writer.add(b"b", &large).unwrap();
+        writer.delete(b"c").unwrap();
+        writer.finish().unwrap();
+
+        for reader in [SstReader::open(&path).unwrap(), SstReader::open_mmap(&path).unwrap()] {
+            // Read in small pieces, as a caller streaming the value elsewhere would
+            let mut value_reader = reader.get_value_reader(b"b").unwrap().unwrap();
+            let mut value = Vec::new();
+            let mut chunk = [0; 4096];
+            loop {
+                let n = value_reader.read(&mut chunk).unwrap();
+                if n == 0 {
+                    break;
+                }
+                value.extend_from_slice(&chunk[..n]);
+            }
+            assert!(value == large, "streamed {} bytes", value.len());
+
+            let mut small = Vec::new();
+            reader.get_value_reader(b"a").unwrap().unwrap().read_to_end(&mut small).unwrap();
+            assert_eq!(small, b"small");
+            assert!(reader.get_value_reader(b"c").unwrap().is_none());
+            assert!(reader.get_value_reader(b"d").unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn parallel_multi_gets_return_values_in_the_order_asked() {
+        let path = TempPath::new("multi-get-parallel.sst");
+        write_even_keys(&path, 5000);
+        let reader = SstReader::open(&path).unwrap();
+        assert!(reader.num_blocks() > 100, "{} blocks", reader.num_blocks());
+
+        // Every key, present or not, scrambled, with some asked for twice
+        let mut probes: Vec<Vec<u8>> = (0..10_000).map(|i| key(i * 7919 % 10_000)).collect();
+        probes.extend((0..100).map(|i| key(i * 2)));
+        probes.push(b"zzz".to_vec());
+        let probe_refs: Vec<&[u8]> = probes.iter().map(Vec::as_slice).collect();
+        let expected: Vec<_> = probes.iter().map(|key| reader.get(key).unwrap()).collect();
+        assert_eq!(expected.iter().flatten().count(), 5100);
+
+        for threads in [0, 1, 3, 8, 1000] {
+            assert_eq!(reader.multi_get_parallel(&probe_refs, threads).unwrap(), expected, "{} threads", threads);
+        }
+        assert!(reader.multi_get_parallel(&[], 4).unwrap().is_empty());
+    }
+
+    #[test]
+    fn keys_outside_the_key_range_read_no_block() {
+        for options in [
+            SstWriterBuilder::new().block_size(256).bloom_bits_per_key(0),
+            SstWriterBuilder::new().block_size(256).bloom_bits_per_key(0).index_sample_interval(4),
+        ] {
+            // Keys from key00100 to key00298
+            let mut bytes = Vec::new();
+            let mut writer = options.build_with_writer(&mut bytes);
+            for i in 50..150 {
+                writer.add(&key(i * 2), b"value").unwrap();
+            }
+            writer.finish().unwrap();
+            let reader = SstReader::from_bytes(bytes).unwrap().with_block_cache(0);
+
+            let outside = [&b"a"[..], b"key", b"key00099", &key(0), &key(299), b"key002980", b"zzz", b"\xff"];
+            for probe in outside {
+                assert_eq!(reader.get(probe).unwrap(), None);
+                assert_eq!(reader.get_entry(probe).unwrap(), None);
+                assert!(!reader.contains_key(probe).unwrap());
+            }
+            assert_eq!(reader.multi_get(&outside).unwrap(), vec![None; outside.len()]);
+            assert_eq!(reader.cache_stats(), Some(CacheStats::default()));
+
+            // Keys on the ends of the range are read, along with the rest of a sparse index's span
+            assert!(reader.contains_key(&key(100)).unwrap());
+            assert!(reader.contains_key(&key(298)).unwrap());
+            assert!(reader.cache_stats().unwrap().misses >= 2);
+        }
+    }
+
+    #[test]
+    fn application_magics_tell_files_apart() {
+        let path = TempPath::new("application-magic.sst");
+        let mut writer = SstWriterBuilder::new().application_magic(*b"ORDERS01").build(&path).unwrap();
+        writer.add(b"key", b"value").unwrap();
+        writer.finish().unwrap();
+
+        let reader = SstReader::open(&path).unwrap();
+        assert_eq!(reader.application_magic(), Some(*b"ORDERS01"));
+        let reader = reader.expect_application_magic(*b"ORDERS01").unwrap();
+        assert_eq!(reader.get(b"key").unwrap(), Some(b"value".to_vec()));
+        reader.verify_file_checksum().unwrap();
+        let result = SstReader::open(&path).unwrap().expect_application_magic(*b"ORDERS02");
+        assert!(matches!(result, Err(SstError::ApplicationMagicMismatch)));
+
+        // A file written without one has none to match
+        let reader = fruit();
+        assert_eq!(reader.application_magic(), None);
+        assert!(matches!(reader.expect_application_magic(*b"ORDERS01"), Err(SstError::ApplicationMagicMismatch)));
+        assert!(fruit().expect_application_magic([0; 8]).is_ok());
+    }
+
+    #[test]
+    fn lazily_opened_readers_answer_as_eager_ones_do() {
+        let path = TempPath::new("lazy.sst");
+        for options in [
+            SstWriterBuilder::new().block_size(128),
+            SstWriterBuilder::new().block_size(128).index_partition_size(256),
+            SstWriterBuilder::new().block_size(128).index_sample_interval(3),
+        ] {
+            let mut writer = options.build(&path).unwrap();
+            for i in 0..1000 {
+                match i % 9 {
+                    4 => writer.delete(&key(i * 2)).unwrap(),
+                    _ => writer.add(&key(i * 2), &key(i * 2)).unwrap(),
+                }
+            }
+            writer.finish().unwrap();
+            let eager = SstReader::open(&path).unwrap();
+            let lazy = SstReader::open_lazy(&path).unwrap();
+
+            assert_eq!((lazy.first_key(), lazy.last_key()), (eager.first_key(), eager.last_key()));
+            assert_eq!((lazy.len(), lazy.num_blocks()), (eager.len(), eager.num_blocks()));
+            assert_eq!(lazy.properties(), eager.properties());
+            for i in 0..2001 {
+                assert_eq!(lazy.get_entry(&key(i)).unwrap(), eager.get_entry(&key(i)).unwrap(), "key {}", i);
+                assert_eq!(lazy.floor(&key(i)).unwrap(), eager.floor(&key(i)).unwrap(), "key {}", i);
+            }
+            let (start, end) = (key(333), key(1555));
+            let scan = |reader: &SstReader| {
+                let range = reader.range(Bound::Included(&start), Bound::Excluded(&end));
+                range.map(Result::unwrap).collect::<Vec<_>>()
+            };
+            assert_eq!(scan(&lazy), scan(&eager));
+            assert_eq!(lazy.nth(500).unwrap(), eager.nth(500).unwrap());
+            assert_eq!(lazy.index_entries().unwrap(), eager.index_entries().unwrap());
+            assert!(lazy.iter().map(Result::unwrap).eq(eager.iter().map(Result::unwrap)));
+            lazy.verify().unwrap();
+        }
+    }
+
+    #[test]
+    fn in_memory_readers_answer_as_file_readers_do_without_the_file() {
+        let path = TempPath::new("in-memory.sst");
+        write_even_keys(&path, 1000);
+        let file = SstReader::open(&path).unwrap();
+        let memory = SstReader::open_in_memory(&path).unwrap();
+        let gets: Vec<_> = (0..2001).map(|i| file.get(&key(i)).unwrap()).collect();
+        let entries: Vec<_> = file.iter_entries().map(Result::unwrap).collect();
+        let index = file.export_manifest().unwrap();
+
+        // Emptying the file leaves the reader nothing to read from it
+        fs::write(&path, b"").unwrap();
+        assert!(file.get(&key(0)).is_err());
+        assert!(memory.is_stale().unwrap());
+        assert_eq!((0..2001).map(|i| memory.get(&key(i)).unwrap()).collect::<Vec<_>>(), gets);
+        assert_eq!(memory.iter_entries().map(Result::unwrap).collect::<Vec<_>>(), entries);
+        assert_eq!(memory.export_manifest().unwrap(), index);
+        memory.verify().unwrap();
+        memory.verify_file_checksum().unwrap();
+    }
+
+    // Builds an uncompressed SST by hand from decoded data blocks, each given with the first
+    // key and index key of its index entry, for files the writer would never produce
+    fn hand_built(blocks: &[(&[u8], &[u8], &[u8])]) -> Vec<u8> {
+        let mut bytes = MAGIC.to_le_bytes().to_vec();
+        let mut index = vec![INDEX_TYPE_FLAT];
+        index.extend_from_slice(&(blocks.len() as u32).to_le_bytes());
+        let mut num_entries = 0;
+        for (first_key, last_key, block) in blocks {
+            let block_offset = bytes.len() as u64;
+            let mut stored = compression::compress_block(block, Compression::None).unwrap();
+            checksum::append_trailer(&mut stored, Checksum::None);
+            bytes.extend_from_slice(&stored);
+            num_entries += u32::from_le_bytes(block[..4].try_into().unwrap()) as u64;
+            index.extend_from_slice(&(last_key.len() as u32).to_le_bytes());
+            index.extend_from_slice(last_key);
+            index.extend_from_slice(&block_offset.to_le_bytes());
+            index.extend_from_slice(&(stored.len() as u64).to_le_bytes());
+            index.extend_from_slice(&num_entries.to_le_bytes());
+            index.extend_from_slice(&(first_key.len() as u32).to_le_bytes());
+            index.extend_from_slice(first_key);
+        }
+        let index_offset = bytes.len() as u64;
+        bytes.extend_from_slice(&index);
+        let properties_offset = bytes.len() as u64;
+        let properties = Properties { num_entries, ..Properties::default() }.to_bytes();
+        bytes.extend_from_slice(&properties);
+        let footer = Footer {
+            index_offset,
+            index_size: index.len() as u64,
+            first_key: blocks.first().map(|(first_key, _, _)| first_key.to_vec()),
+            num_entries: Some(num_entries),
+            properties_offset,
+            properties_size: properties.len() as u64,
+            ..Footer::default()
+        };
+        bytes.extend_from_slice(&footer.to_bytes());
+        bytes
+    }
+
+    // Returns the decoded data blocks of an in-memory SST holding `keys`, `per_block` to a block
+    fn decoded_blocks(keys: &[&str], per_block: usize) -> Vec<Vec<u8>> {
+        let mut bytes = Vec::new();
+        let mut writer = SstWriterBuilder::new().max_entries_per_block(per_block).build_with_writer(&mut bytes);
+        for key in keys {
+            writer.add(key.as_bytes(), key.to_uppercase().as_bytes()).unwrap();
+        }
+        writer.finish().unwrap();
+        let reader = SstReader::from_bytes(bytes).unwrap();
+        (0..reader.num_blocks()).map(|block_idx| reader.read_block_at(block_idx).unwrap().to_vec()).collect()
+    }
+
+    #[test]
+    fn blocks_without_entries_read_as_empty() {
+        // No entries and no restart points
+        let empty = [0; 8];
+        let blocks = decoded_blocks(&["a", "b", "c", "d"], 2);
+        let reader = SstReader::from_bytes(hand_built(&[
+            (b"a", b"b", &blocks[0]),
+            (b"bb", b"bb", &empty),
+            (b"c", b"d", &blocks[1]),
+        ]))
+        .unwrap();
+
+        for probe in [&b"a"[..], b"bb", b"c", b""] {
+            assert!(reader.search_in_block(&empty, probe).unwrap().is_none());
+        }
+        assert_eq!(SstReader::last_key_in_block(&empty, reader.version()).unwrap(), b"");
+        assert_eq!(reader.get(b"bb").unwrap(), None);
+        assert_eq!(reader.get(b"c").unwrap(), Some(b"C".to_vec()));
+
+        assert_eq!(keys(reader.iter()), ["a", "b", "c", "d"]);
+        assert_eq!(reader.iter_entries().count(), 4);
+        assert_eq!(keys(reader.iter_rev()), ["d", "c", "b", "a"]);
+        let block_lens: Vec<_> = reader.blocks().map(|block| block.unwrap().entries().unwrap().len()).collect();
+        assert_eq!(block_lens, [2, 0, 2]);
+        let range = |start: &[u8], end: &[u8]| keys(reader.range(Bound::Included(start), Bound::Included(end)));
+        assert_eq!(range(b"b", b"z"), ["b", "c", "d"]);
+        assert_eq!(range(b"bb", b"z"), ["c", "d"]);
+        assert!(range(b"ba", b"bb").is_empty());
+        assert_eq!(reader.floor(b"bb").unwrap(), Some((b"b".to_vec(), b"B".to_vec())));
+        assert_eq!(reader.ceil(b"bb").unwrap(), Some((b"c".to_vec(), b"C".to_vec())));
+        assert_eq!(reader.nth(2).unwrap(), Some((b"c".to_vec(), Entry::Value(b"C".to_vec()))));
+        // The writer never leaves a block empty, so a scrub reports one
+        assert!(matches!(reader.verify(), Err(SstError::CorruptBlock(msg)) if msg.ends_with("block has no entries")));
+    }
+
+    #[test]
+    fn a_footer_claiming_an_absurd_index_size_is_a_corrupt_index() {
+        let mut bytes = Vec::new();
+        let mut writer = SstWriterBuilder::new().block_size(64).build_with_writer(&mut bytes);
+        for i in 0..100 {
+            writer.add(&key(i), b"value").unwrap();
+        }
+        writer.finish().unwrap();
+        let footer = Footer::read_from(&mut Cursor::new(&bytes)).unwrap();
+
+        let path = TempPath::new("absurd-index-size.sst");
+        // Sizes far past the file, and one so large that the index's end overflows;
+        // none of them may be allocated
+        for size in [1 << 40, u64::MAX / 2, u64::MAX - footer.index_offset + 1] {
+            let mut corrupt = bytes.clone();
+            set_footer_handle(&mut corrupt, 1, size);
+            fs::write(&path, &corrupt).unwrap();
+            for result in [SstReader::open(&path), SstReader::open_lazy(&path), SstReader::from_bytes(corrupt)] {
+                match result {
+                    Err(SstError::CorruptIndex(_)) => {}
+                    Err(e) => panic!("size {}: {}", size, e),
+                    Ok(_) => panic!("size {} was accepted", size),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn repeated_get_into_reuses_the_buffer() {
+        let path = TempPath::new("get-into-reuse.sst");
+        let mut writer = SstWriterBuilder::new().block_size(256).blob_threshold(64).build(&path).unwrap();
+        let value = |i: u32| key(i).repeat(i as usize % 20 + 1);
+        for i in 0..200 {
+            writer.add(&key(i), &value(i)).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let reader = SstReader::open(&path).unwrap();
+        let mut buf = Vec::with_capacity(1024);
+        let (ptr, capacity) = (buf.as_ptr(), buf.capacity());
+        // Inline values, values in blobs and missing keys, in an order that jumps
+        // between blocks
+        for i in (0..200).rev().chain((0..200).step_by(7)) {
+            assert!(reader.get_into(&key(i), &mut buf).unwrap());
+            assert_eq!(buf, value(i));
+            assert!(!reader.get_into(&[&key(i)[..], b"~"].concat(), &mut buf).unwrap());
+            assert!(buf.is_empty());
+            assert_eq!((buf.as_ptr(), buf.capacity()), (ptr, capacity));
+        }
+    }
+
+    #[test]
+    fn out_of_order_indexes_fail_verification() {
+        let blocks = decoded_blocks(&["a", "b", "c", "d"], 2);
+
+        // The second block's index key sorts before the first's
+        let reversed = hand_built(&[(b"a", b"d", &blocks[0]), (b"c", b"b", &blocks[1])]);
+        let expected = "index key b of block 1 is not greater than index key d of block 0";
+        assert!(matches!(
+            SstReader::from_bytes(reversed).unwrap().verify(),
+            Err(SstError::CorruptIndex(msg)) if msg == expected
+        ));
+
+        // The second block starts inside the first
+        let mut bytes = hand_built(&[(b"a", b"b", &blocks[0]), (b"c", b"d", &blocks[1])]);
+        let footer = Footer::read_from(&mut Cursor::new(&bytes)).unwrap();
+        let reader = SstReader::from_bytes(bytes.clone()).unwrap();
+        let (first_offset, first_size) = reader.block_info(0).map(|info| (info.block_offset, info.block_size)).unwrap();
+        // Past the index type and block count, the first entry and the second's index key
+        let entry_len = 4 + 1 + 8 + 8 + 8 + 4 + 1;
+        let at = footer.index_offset as usize + 5 + entry_len + 4 + 1;
+        bytes[at..at + 8].copy_from_slice(&(first_offset + 1).to_le_bytes());
+        let overlapping = SstReader::from_bytes(bytes).unwrap();
+        assert_eq!(overlapping.block_info(1).unwrap().block_offset, first_offset + 1);
+        let expected = format!(
+            "block 1 starts at offset {} but the previous block ends at {}",
+            first_offset + 1,
+            first_offset + first_size
+        );
+        assert!(matches!(overlapping.verify(), Err(SstError::CorruptIndex(msg)) if msg == expected));
+    }
+
+    #[test]
+    fn range_size_estimates_cover_the_blocks_a_scan_reads() {
+        let reader = even_keys(200, SstWriterBuilder::new().block_size(256));
+        assert!(reader.num_blocks() > 4);
+        let block_sizes: HashMap<u64, u64> = reader
+            .blocks()
+            .map(|block| block.unwrap().meta())
+            .map(|meta| (meta.block_offset + meta.block_size, meta.block_size))
+            .collect();
+        // The progress callback reports the end of each block the scan reads, and the end
+        // of the file if the scan gets there
+        let bytes_read = |start: Bound<&[u8]>, end: Bound<&[u8]>| {
+            let mut read = 0;
+            let scan = reader.range(start, end).on_progress(|end, _| read += block_sizes.get(&end).unwrap_or(&0));
+            scan.for_each(drop);
+            read
+        };
+
+        let block = reader.blocks().nth(2).unwrap().unwrap();
+        let entries = block.entries().unwrap();
+        let (first, last) = (&entries[0].0[..], &entries[entries.len() - 1].0[..]);
+        let (absent, past_end) = ([&key(7)[..], b"~"].concat(), [&key(400)[..], b"~"].concat());
+        for (start, end, expected) in [
+            // Between two keys, and a start past the end
+            (Bound::Included(&absent[..]), Bound::Included(&absent[..]), None),
+            (Bound::Included(&key(8)[..]), Bound::Excluded(&key(8)[..]), None),
+            (Bound::Included(&key(50)[..]), Bound::Included(&key(10)[..]), Some(0)),
+            (Bound::Included(first), Bound::Included(last), Some(block.meta().block_size)),
+            (Bound::Unbounded, Bound::Unbounded, Some(block_sizes.values().sum())),
+            (Bound::Included(&past_end[..]), Bound::Unbounded, Some(0)),
+        ] {
+            let estimate = reader.range_size_estimate(start, end).unwrap();
+            assert_eq!(estimate, bytes_read(start, end), "{:?}..{:?}", start, end);
+            if let Some(expected) = expected {
+                assert_eq!(estimate, expected, "{:?}..{:?}", start, end);
+            }
+        }
+    }
+
+    #[test]
+    fn keys_between_blocks_read_no_block() {
+        // Without a bloom filter, only the first keys in the index can rule the gaps out
+        let reader = even_keys(300, SstWriterBuilder::new().block_size(128).bloom_bits_per_key(0)).with_block_cache(0);
+        assert!(reader.num_blocks() > 4);
+        let blocks: Vec<_> = reader.blocks().map(|block| block.unwrap()).collect();
+        let mut gap_keys = Vec::new();
+        for pair in blocks.windows(2) {
+            let index_key = pair[0].last_key();
+            let next_first = pair[1].meta().first_key.unwrap();
+            // Just past the index key, and just before the next block's first key
+            let mut before_next = next_first.to_vec();
+            *before_next.last_mut().unwrap() -= 1;
+            before_next.push(b'~');
+            for gap_key in [[index_key, b"\0"].concat(), before_next] {
+                assert!(gap_key.as_slice() > index_key && gap_key.as_slice() < next_first);
+                gap_keys.push(gap_key);
+            }
+        }
+
+        let before = reader.cache_stats().unwrap();
+        for gap_key in &gap_keys {
+            assert_eq!(reader.get(gap_key).unwrap(), None);
+            assert_eq!(reader.get_entry(gap_key).unwrap(), None);
+            assert!(!reader.contains_key(gap_key).unwrap());
+            assert!(!reader.get_into(gap_key, &mut Vec::new()).unwrap());
+            assert_eq!(reader.cache_stats().unwrap(), before, "{} read a block", gap_key.escape_ascii());
+        }
+        let gap_refs: Vec<&[u8]> = gap_keys.iter().map(Vec::as_slice).collect();
+        assert_eq!(reader.multi_get(&gap_refs).unwrap(), vec![None; gap_keys.len()]);
+        assert_eq!(reader.cache_stats().unwrap(), before);
+
+        // A key within a block still reads it
+        assert_eq!(reader.get(&key(3)).unwrap(), None);
+        assert_eq!(reader.cache_stats().unwrap().misses, before.misses + 1);
+    }
+
+    #[test]
+    fn get_at_returns_the_newest_version_at_the_snapshot() {
+        let path = TempPath::new("mvcc.sst");
+        let mut writer = SstWriterBuilder::new().block_size(64).comparator(InternalKeyComparator).build(&path).unwrap();
+        // Newest version first within each user key; "a" is a prefix of "ab"
+        writer.add(&comparator::internal_key(b"a", 30), b"a30").unwrap();
+        writer.delete(&comparator::internal_key(b"a", 20)).unwrap();
+        writer.add(&comparator::internal_key(b"a", 10), b"a10").unwrap();
+        writer.add(&comparator::internal_key(b"ab", 25), b"ab25").unwrap();
+        writer.add(&comparator::internal_key(b"ab", 5), b"ab5").unwrap();
+        // Enough versions of one key to fill several blocks
+        for sequence in (1..=60).rev() {
+            writer.add(&comparator::internal_key(b"b", sequence), format!("b{}", sequence).as_bytes()).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let reader = SstReader::open_with_comparator(&path, InternalKeyComparator).unwrap();
+        reader.verify().unwrap();
+        let blocks_of_b = reader
+            .blocks()
+            .filter(|block| {
+                let entries = block.as_ref().unwrap().entries().unwrap();
+                entries.iter().any(|(key, _)| comparator::parse_internal_key(key).unwrap().0 == b"b")
+            })
+            .count();
+        assert!(blocks_of_b > 2, "{} blocks", blocks_of_b);
+
+        let get_at = |user_key: &[u8], snapshot| reader.get_at(user_key, snapshot).unwrap();
+        let some = |value: &str| Some(value.as_bytes().to_vec());
+        // Below, equal to and between the versions, and past the newest. Below the oldest
+        // version of "a", the next entry is a version of "ab", which mustn't be returned.
+        assert_eq!(get_at(b"a", 0), None);
+        assert_eq!(get_at(b"a", 9), None);
+        assert_eq!(get_at(b"a", 10), some("a10"));
+        assert_eq!(get_at(b"a", 15), some("a10"));
+        assert_eq!(get_at(b"a", 20), None);
+        assert_eq!(get_at(b"a", 29), None);
+        assert_eq!(get_at(b"a", 30), some("a30"));
+        assert_eq!(get_at(b"a", u64::MAX), some("a30"));
+        assert_eq!(get_at(b"ab", 4), None);
+        assert_eq!(get_at(b"ab", 5), some("ab5"));
+        assert_eq!(get_at(b"ab", 24), some("ab5"));
+        assert_eq!(get_at(b"ab", u64::MAX), some("ab25"));
+        for snapshot in 0..70 {
+            let expected = (snapshot > 0).then(|| format!("b{}", snapshot.min(60)).into_bytes());
+            assert_eq!(get_at(b"b", snapshot), expected, "snapshot {}", snapshot);
+        }
+        assert_eq!(get_at(b"b", u64::MAX), some("b60"));
+        for absent in [&b""[..], b"aa", b"abc", b"c"] {
+            assert_eq!(get_at(absent, u64::MAX), None);
+        }
+
+        let bytewise = even_keys(10, SstWriterBuilder::new());
+        assert!(matches!(bytewise.get_at(b"a", u64::MAX), Err(SstError::ComparatorMismatch { .. })));
+    }
+
+    #[test]
+    fn regions_of_a_container_file_open_as_separate_ssts() {
+        let write = |entries: &[(Vec<u8>, Vec<u8>)]| {
+            let mut bytes = Vec::new();
+            let mut writer = SstWriterBuilder::new().block_size(128).blob_threshold(64).build_with_writer(&mut bytes);
+            for (key, value) in entries {
+                writer.add(key, value).unwrap();
+            }
+            writer.finish().unwrap();
+            bytes
+        };
+        let first: Vec<_> = (0..100).map(|i| (key(i), key(i).repeat(i as usize % 10 + 1))).collect();
+        let second = FRUIT.iter().map(|(key, value)| (key.as_bytes().to_vec(), value.as_bytes().to_vec()));
+        let second: Vec<_> = second.collect();
+        let (first_bytes, second_bytes) = (write(&first), write(&second));
+
+        // Junk before, between and after the two SSTs
+        let mut container = vec![0xaa; 100];
+        let first_offset = container.len() as u64;
+        container.extend_from_slice(&first_bytes);
+        container.extend_from_slice(&[0x55; 57]);
+        let second_offset = container.len() as u64;
+        container.extend_from_slice(&second_bytes);
+        container.extend_from_slice(&[0xaa; 30]);
+        let path = TempPath::new("container.sst");
+        fs::write(&path, &container).unwrap();
+
+        let regions = [(first_offset, &first_bytes, &first), (second_offset, &second_bytes, &second)];
+        for (offset, bytes, entries) in regions {
+            let reader = SstReader::open_region(&path, offset, bytes.len() as u64).unwrap();
+            reader.verify().unwrap();
+            reader.verify_file_checksum().unwrap();
+            let all: Vec<_> = reader.iter().map(Result::unwrap).collect();
+            assert_eq!(&all, entries);
+            for (key, value) in entries {
+                assert_eq!(reader.get(key).unwrap().as_ref(), Some(value));
+            }
+            assert_eq!(reader.get(b"missing").unwrap(), None);
+        }
+
+        let len = container.len() as u64;
+        for (offset, length) in [(len + 1, 10), (second_offset, len - second_offset + 1), (1, u64::MAX)] {
+            assert!(SstReader::open_region(&path, offset, length).is_err(), "{}+{}", offset, length);
+        }
+        // A region that takes in the junk after the SST has no footer at its end
+        assert!(SstReader::open_region(&path, second_offset, len - second_offset).is_err());
+    }
+
+    #[test]
+    fn sparse_indexes_find_keys_in_unsampled_blocks() {
+        for interval in [3, 5] {
+            // Without a bloom filter, absent keys are searched for in the blocks
+            let options = SstWriterBuilder::new().block_size(64).bloom_bits_per_key(0).index_sample_interval(interval);
+            let reader = even_keys(300, options);
+            let blocks: Vec<_> = reader.blocks().map(|block| block.unwrap()).collect();
+            let unsampled: Vec<_> = (0..blocks.len()).filter(|&i| blocks[i].meta().first_key.is_none()).collect();
+            assert!(unsampled.len() >= 2 * (interval - 1), "{} unsampled blocks", unsampled.len());
+            let all: Vec<_> = (0..300).map(|i| (key(i * 2), key(i * 2))).collect();
+
+            for &block_idx in &unsampled {
+                let keys: Vec<_> = blocks[block_idx].entries().unwrap().into_iter().map(|(key, _)| key).collect();
+                for key in [&keys[0], &keys[keys.len() / 2], &keys[keys.len() - 1]] {
+                    assert_eq!(reader.get(key).unwrap().as_ref(), Some(key), "interval {}", interval);
+                }
+                // Keys around the block: both gaps, and between its own keys
+                let before = [&keys[0][..keys[0].len() - 1], b"/"].concat();
+                let after = [&keys[keys.len() - 1][..], b"~"].concat();
+                let within = [&keys[0][..], b"~"].concat();
+                for absent in [before, after, within] {
+                    assert_eq!(reader.get(&absent).unwrap(), None, "{}", absent.escape_ascii());
+                    assert!(!reader.contains_key(&absent).unwrap());
+                }
+
+                // A seek into the span, at the block's first key or just before it
+                let position = all.iter().position(|(key, _)| *key == keys[0]).unwrap();
+                let mut iter = reader.iter();
+                iter.seek(&keys[0]).unwrap();
+                assert_eq!(iter.map(Result::unwrap).collect::<Vec<_>>(), all[position..]);
+                let mut iter = reader.iter();
+                iter.seek(&key(position as u32 * 2 - 1)).unwrap();
+                assert_eq!(iter.map(Result::unwrap).collect::<Vec<_>>(), all[position..]);
+            }
+            assert_eq!(reader.get(&key(600)).unwrap(), None);
+            assert_eq!(reader.get(b"zzz").unwrap(), None);
+            assert_eq!(reader.iter().map(Result::unwrap).collect::<Vec<_>>(), all);
+            reader.verify().unwrap();
+        }
+    }
+
+    #[test]
+    fn sidecar_blooms_answer_absent_keys_without_the_file() {
+        let path = TempPath::new("sidecar.sst");
+        let sidecar = bloom::sidecar_path(&path);
+        let write = |path: &Path, n| {
+            let mut writer = SstWriterBuilder::new().block_size(128).bloom_sidecar(true).build(path).unwrap();
+            for i in 0..n {
+                writer.add(&key(i * 2), b"value").unwrap();
+            }
+            writer.finish().unwrap().total_bytes
+        };
+        let len = write(&path, 500);
+        let absent: Vec<_> = (0..500).map(|i| key(i * 2 + 1)).collect();
+
+        // A sidecar written for another file is refused
+        let other = TempPath::new("sidecar-other.sst");
+        write(&other, 400);
+        let other_sidecar = bloom::sidecar_path(&other);
+        let result = SstReader::open_with_bloom(&path, &other_sidecar);
+        assert!(matches!(result, Err(SstError::CorruptBloomSidecar(_))), "{:?}", result.err());
+        fs::remove_file(&other_sidecar).unwrap();
+
+        // Without a sidecar, the file's own filter rules out the same keys
+        let own = SstReader::open(&path).unwrap();
+        let fallback = SstReader::open_with_bloom(&path, &other_sidecar).unwrap();
+        for key in &absent {
+            assert_eq!(fallback.may_contain(key), own.may_contain(key));
+        }
+        assert!(absent.iter().filter(|key| !fallback.may_contain(key)).count() > 450);
+
+        let reader = SstReader::open_with_bloom(&path, &sidecar).unwrap().with_block_cache(0);
+        fs::remove_file(&sidecar).unwrap();
+        let rejected: Vec<_> = absent.iter().filter(|key| !reader.may_contain(key)).collect();
+        assert!(rejected.len() > 450, "{} keys rejected", rejected.len());
+        // Zero the data blocks, then cut the file short: keys the sidecar rules out read neither
+        let mut file = fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.seek(SeekFrom::Start(HEADER_SIZE)).unwrap();
+        file.write_all(&vec![0; len as usize / 2]).unwrap();
+        for set_len in [len, HEADER_SIZE] {
+            file.set_len(set_len).unwrap();
+            for key in &rejected {
+                assert_eq!(reader.get(key).unwrap(), None);
+                assert_eq!(reader.get_entry(key).unwrap(), None);
+                assert!(!reader.contains_key(key).unwrap());
+            }
+            assert_eq!(reader.cache_stats(), Some(CacheStats::default()));
+        }
+        assert!(reader.get(&key(0)).is_err());
+    }
+
+    #[test]
+    fn verify_reports_progress_for_every_block() {
+        let reader = even_keys(300, SstWriterBuilder::new().block_size(128));
+        let total = reader.file_len();
+        let mut calls = Vec::new();
+        reader.verify_with_progress(|processed, total| calls.push((processed, total))).unwrap();
+
+        assert!(calls.len() > reader.num_blocks(), "{} calls", calls.len());
+        for block in reader.blocks() {
+            let meta = block.unwrap().meta();
+            assert!(calls.contains(&(meta.block_offset + meta.block_size, total)));
+        }
+        assert!(calls.windows(2).all(|pair| pair[0].0 <= pair[1].0));
+        assert_eq!(calls.last(), Some(&(total, total)));
+    }
+}
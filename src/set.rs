@@ -0,0 +1,164 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::comparator::{BytewiseComparator, Comparator};
+use crate::error::Result;
+use crate::iter::SstEntryIterator;
+use crate::merge::MergingIterator;
+use crate::reader::{Entry, SstReader};
+
+/// A group of SST files queried as one sorted map, such as the files of an LSM tree.
+///
+/// Files are ordered oldest to newest: when a key appears in more than one file, the
+/// entry from the file added last wins, and a tombstone hides older values for its key.
+/// Every file must be written with the same comparator, and the set merges their keys
+/// in its order.
+pub struct SstSet {
+    // Oldest first
+    readers: Vec<SstReader>,
+}
+
+impl SstSet {
+    /// Creates a set from readers ordered oldest to newest.
+    pub fn new(readers: Vec<SstReader>) -> Self {
+        SstSet { readers }
+    }
+
+    /// Opens the SST files at `paths`, ordered oldest to newest.
+    pub fn open(paths: &[&Path]) -> Result<Self> {
+        let readers = paths
+            .iter()
+            .map(|path| SstReader::open(path))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self::new(readers))
+    }
+
+    /// Adds a file that is newer than every file already in the set.
+    pub fn push(&mut self, reader: SstReader) {
+        self.readers.push(reader);
+    }
+
+    /// Returns the readers in the set, oldest first.
+    pub fn readers(&self) -> &[SstReader] {
+        &self.readers
+    }
+
+    /// Looks up a key, returning the value from the newest file that holds it, or None
+    /// if no file does or the newest entry for it is a tombstone. Files are searched from
+    /// newest to oldest, stopping at the first one that holds the key.
+    pub fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        for reader in self.readers.iter().rev() {
+            match reader.get_entry(key)? {
+                Some(Entry::Value(value)) => return Ok(Some(value)),
+                Some(Entry::Tombstone) => return Ok(None),
+                None => {}
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns an iterator over the key-value pairs of every file merged in sorted key
+    /// order. Each key is returned once, with its newest value; deleted keys are skipped.
+    pub fn iter(&self) -> SstSetIterator<'_> {
+        let comparator = self.readers.first().map_or_else(
+            || Arc::new(BytewiseComparator) as Arc<dyn Comparator>,
+            |reader| Arc::clone(reader.comparator_ref()),
+        );
+        SstSetIterator {
+            inner: MergingIterator::new(self.readers.iter().map(SstReader::iter_entries).collect(), comparator),
+        }
+    }
+}
+
+/// Iterates over the merged key-value pairs of an `SstSet` in sorted key order.
+pub struct SstSetIterator<'a> {
+    inner: MergingIterator<SstEntryIterator<'a>, Entry>,
+}
+
+impl Iterator for SstSetIterator<'_> {
+    type Item = Result<(Vec<u8>, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.inner.next()? {
+                Ok((key, Entry::Value(value))) => return Some(Ok((key, value))),
+                Ok((_, Entry::Tombstone)) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::comparator::CaseInsensitiveAsciiComparator;
+    use crate::test_util::{TempPath, strings};
+    use crate::writer::{SstWriter, SstWriterBuilder};
+
+    // Writes `entries` to an SST at `path`, treating a None value as a deletion
+    fn write(path: &Path, entries: &[(&str, Option<&str>)]) {
+        let mut writer = SstWriter::new(path).unwrap();
+        for (key, value) in entries {
+            match value {
+                Some(value) => writer.add(key.as_bytes(), value.as_bytes()).unwrap(),
+                None => writer.delete(key.as_bytes()).unwrap(),
+            }
+        }
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn newer_files_win() {
+        let [old, mid, new] = ["old", "mid", "new"].map(|name| TempPath::new(&format!("set-{}.sst", name)));
+        write(&old, &[("apple", Some("old")), ("banana", Some("old")), ("cherry", Some("old"))]);
+        write(&mid, &[("banana", Some("mid")), ("cherry", None), ("date", Some("mid"))]);
+        write(&new, &[("apple", Some("new")), ("cherry", Some("new")), ("date", None), ("fig", Some("new"))]);
+        let set = SstSet::open(&[&old, &mid, &new]).unwrap();
+
+        let get = |key: &str| set.get(key.as_bytes()).unwrap().map(|value| String::from_utf8(value).unwrap());
+        assert_eq!(get("apple").as_deref(), Some("new"));
+        assert_eq!(get("banana").as_deref(), Some("mid"));
+        // Deleted in the middle file, then written again
+        assert_eq!(get("cherry").as_deref(), Some("new"));
+        // Deleted in the newest file
+        assert_eq!(get("date"), None);
+        assert_eq!(get("fig").as_deref(), Some("new"));
+        assert_eq!(get("grape"), None);
+
+        let expected = [("apple", "new"), ("banana", "mid"), ("cherry", "new"), ("fig", "new")];
+        let expected: Vec<_> = expected.iter().map(|&(k, v)| (k.to_string(), v.to_string())).collect();
+        assert_eq!(strings(set.iter()), expected);
+    }
+
+    #[test]
+    fn a_tombstone_hides_older_values_in_scans() {
+        let (old, new) = (TempPath::new("set-tomb-old.sst"), TempPath::new("set-tomb-new.sst"));
+        write(&old, &[("apple", Some("old")), ("banana", Some("old"))]);
+        write(&new, &[("apple", None)]);
+        let mut set = SstSet::open(&[&old]).unwrap();
+        assert_eq!(set.iter().count(), 2);
+        set.push(SstReader::open(&new).unwrap());
+        assert_eq!(strings(set.iter()), [("banana".to_string(), "old".to_string())]);
+        assert_eq!(set.get(b"apple").unwrap(), None);
+    }
+
+    #[test]
+    fn files_are_merged_by_their_comparator() {
+        let (old, new) = (TempPath::new("set-case-old.sst"), TempPath::new("set-case-new.sst"));
+        for (path, entries) in [(&old, [("a", "old"), ("B", "old")]), (&new, [("A", "new"), ("c", "new")])] {
+            let mut writer = SstWriterBuilder::new().comparator(CaseInsensitiveAsciiComparator).build(path).unwrap();
+            for (key, value) in entries {
+                writer.add(key.as_bytes(), value.as_bytes()).unwrap();
+            }
+            writer.finish().unwrap();
+        }
+        let readers = [&old, &new].map(|path| SstReader::open_with_comparator(path, CaseInsensitiveAsciiComparator));
+        let set = SstSet::new(readers.into_iter().collect::<Result<_>>().unwrap());
+
+        // "A" replaces "a" from the older file, and "B" sorts between them and "c"
+        let expected = [("A", "new"), ("B", "old"), ("c", "new")].map(|(k, v)| (k.to_string(), v.to_string()));
+        assert_eq!(strings(set.iter()), expected);
+        assert_eq!(set.get(b"a").unwrap(), Some(b"new".to_vec()));
+    }
+}
@@ -0,0 +1,311 @@
+use std::fs;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::comparator::Comparator;
+use crate::error::Result;
+use crate::format;
+use crate::merge::MergingIterator;
+use crate::reader::SstReader;
+use crate::writer::{SstWriterBuilder, WriteStats};
+
+// Distinguishes the spill files of writers in the same process
+static NEXT_RUN_ID: AtomicU64 = AtomicU64::new(0);
+
+// Most runs merged at once, which bounds the number of files held open
+const MAX_MERGE_WIDTH: usize = 64;
+
+// A sorted stream of key-value pairs fed into the final merge
+type Run<'a> = Box<dyn Iterator<Item = Result<(Vec<u8>, Vec<u8>)>> + 'a>;
+
+/// Builds an SST from key-value pairs added in any order.
+///
+/// Pairs are buffered in memory and sorted by the options' comparator when the writer
+/// finishes. If a key is added more than once, the pair added last wins: with a
+/// comparator under which different keys compare equal, its key is the one stored. Once the buffered keys and values
+/// exceed the memory limit, the buffer is sorted and spilled to a temporary SST
+/// (a run), and `finish` merges every run into the output (an external merge sort).
+/// Runs are deleted when the writer is finished or dropped.
+pub struct SortingSstWriter {
+    path: PathBuf,
+    options: SstWriterBuilder,
+    memory_limit: usize,
+    temp_dir: PathBuf,
+    buffer: Vec<(Vec<u8>, Vec<u8>)>,
+    // Total size of the buffered keys and values
+    buffered_bytes: usize,
+    // Spilled runs, oldest first
+    runs: Vec<PathBuf>,
+}
+
+impl SortingSstWriter {
+    /// Creates a writer for the given path with the default `SstWriter` options.
+    /// Nothing is written to `path` until `finish` is called.
+    pub fn new(path: &Path) -> Self {
+        Self::with_options(path, SstWriterBuilder::new())
+    }
+
+    /// Creates a writer for the given path that builds the output with `options`.
+    pub fn with_options(path: &Path, options: SstWriterBuilder) -> Self {
+        SortingSstWriter {
+            path: path.to_path_buf(),
+            options,
+            memory_limit: 64 * 1024 * 1024,
+            temp_dir: std::env::temp_dir(),
+            buffer: Vec::new(),
+            buffered_bytes: 0,
+            runs: Vec::new(),
+        }
+    }
+
+    /// Sets how many bytes of keys and values are buffered before they are spilled
+    /// to a temporary run (64 MiB by default).
+    pub fn memory_limit(mut self, bytes: usize) -> Self {
+        self.memory_limit = bytes;
+        self
+    }
+
+    /// Sets the directory temporary runs are written to (the system temporary
+    /// directory by default).
+    pub fn temp_dir(mut self, dir: &Path) -> Self {
+        self.temp_dir = dir.to_path_buf();
+        self
+    }
+
+    /// Adds a key-value pair. Keys may be added in any order; if `key` was added
+    /// before, this value replaces the earlier one. Keys must not be empty
+    /// (`SstError::EmptyKey`) or longer than `MAX_KEY_LEN` (`SstError::KeyTooLarge`).
+    pub fn add(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        // Rejected up front rather than when the buffer is next written out
+        format::check_key_len(key.len())?;
+        self.buffer.push((key.to_vec(), value.to_vec()));
+        self.buffered_bytes += key.len() + value.len();
+        if self.buffered_bytes > self.memory_limit {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    // Sorts the buffer and writes it to a new run
+    fn spill(&mut self) -> Result<()> {
+        let mut buffer = mem::take(&mut self.buffer);
+        self.buffered_bytes = 0;
+        sort_and_dedup(&mut buffer, self.comparator());
+
+        let path = self.run_path();
+        // Record the run before writing it so a partially written file is still cleaned up
+        self.runs.push(path.clone());
+        write_run(&path, self.comparator(), buffer.into_iter().map(Ok))
+    }
+
+    fn comparator(&self) -> &Arc<dyn Comparator> {
+        self.options.comparator_ref()
+    }
+
+    fn run_path(&self) -> PathBuf {
+        self.temp_dir.join(format!(
+            "sst-sort-{}-{}.run",
+            process::id(),
+            NEXT_RUN_ID.fetch_add(1, Ordering::Relaxed)
+        ))
+    }
+
+    // Merges the oldest runs into one until few enough remain to merge in a single pass
+    fn reduce_runs(&mut self) -> Result<()> {
+        // One slot is left for the in-memory buffer
+        while self.runs.len() >= MAX_MERGE_WIDTH {
+            // The merged run takes the place of the runs it replaces, so it stays older
+            // than every run after them
+            let path = self.run_path();
+            self.runs.insert(MAX_MERGE_WIDTH, path.clone());
+            {
+                let readers = self.runs[..MAX_MERGE_WIDTH]
+                    .iter()
+                    .map(|path| self.open_run(path))
+                    .collect::<Result<Vec<_>>>()?;
+                let inputs = readers.iter().map(SstReader::iter).collect();
+                write_run(&path, self.comparator(), MergingIterator::new(inputs, Arc::clone(self.comparator())))?;
+            }
+            for run in self.runs.drain(..MAX_MERGE_WIDTH) {
+                let _ = fs::remove_file(run);
+            }
+        }
+        Ok(())
+    }
+
+    /// Sorts everything added and writes the SST, merging in any spilled runs.
+    pub fn finish(mut self) -> Result<WriteStats> {
+        let mut buffer = mem::take(&mut self.buffer);
+        sort_and_dedup(&mut buffer, self.comparator());
+        self.reduce_runs()?;
+
+        let readers = self
+            .runs
+            .iter()
+            .map(|path| self.open_run(path))
+            .collect::<Result<Vec<_>>>()?;
+        // The buffer holds the newest pairs, so it goes last to win over the runs
+        let mut inputs: Vec<Run> = readers
+            .iter()
+            .map(|reader| Box::new(reader.iter()) as Run)
+            .collect();
+        inputs.push(Box::new(buffer.into_iter().map(Ok)));
+
+        let mut writer = self.options.clone().build(&self.path)?;
+        for entry in MergingIterator::new(inputs, Arc::clone(self.comparator())) {
+            let (key, value) = entry?;
+            writer.add(&key, &value)?;
+        }
+        writer.finish()
+    }
+
+    // Runs are written in the output's order, so they are read with its comparator
+    fn open_run(&self, path: &Path) -> Result<SstReader> {
+        SstReader::open_with_comparator(path, Arc::clone(self.comparator()))
+    }
+}
+
+impl Drop for SortingSstWriter {
+    fn drop(&mut self) {
+        for run in &self.runs {
+            let _ = fs::remove_file(run);
+        }
+    }
+}
+
+// Writes a run sorted by `comparator`. Runs are read back once and then deleted,
+// so they skip the bloom filter and the sync to disk.
+fn write_run<I>(path: &Path, comparator: &Arc<dyn Comparator>, pairs: I) -> Result<()>
+where
+    I: Iterator<Item = Result<(Vec<u8>, Vec<u8>)>>,
+{
+    let mut writer = SstWriterBuilder::new()
+        .comparator(Arc::clone(comparator))
+        .bloom_bits_per_key(0)
+        .sync_on_finish(false)
+        .build(path)?;
+    for pair in pairs {
+        let (key, value) = pair?;
+        writer.add(&key, &value)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+// Sorts pairs by key in the order of `comparator`, keeping only the last pair added
+// for each key
+fn sort_and_dedup(pairs: &mut Vec<(Vec<u8>, Vec<u8>)>, comparator: &Arc<dyn Comparator>) {
+    // A stable sort keeps pairs with equal keys in the order they were added
+    pairs.sort_by(|a, b| comparator.compare(&a.0, &b.0));
+    pairs.dedup_by(|later, kept| {
+        if comparator.compare(&later.0, &kept.0).is_ne() {
+            return false;
+        }
+        mem::swap(later, kept);
+        true
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::comparator::CaseInsensitiveAsciiComparator;
+    use crate::test_util::{TempPath, strings};
+
+    fn key(i: u32) -> Vec<u8> {
+        format!("key{:05}", i).into_bytes()
+    }
+
+    // Adds keys 0..n in a scrambled order, each with a value recording the round, then
+    // rewrites every third key in a second round so the later value must win
+    fn add_scrambled(writer: &mut SortingSstWriter, n: u32) {
+        for round in 0..2 {
+            for i in (0..n).map(|i| i * 7919 % n).filter(|i| round == 0 || i % 3 == 0) {
+                writer.add(&key(i), format!("{}-{}", i, round).as_bytes()).unwrap();
+            }
+        }
+    }
+
+    // Checks the output holds keys 0..n in order, with the values `add_scrambled` left
+    fn check_output(path: &Path, n: u32) {
+        let reader = SstReader::open(path).unwrap();
+        let entries: Vec<_> = reader.iter().map(Result::unwrap).collect();
+        let expected: Vec<_> = (0..n)
+            .map(|i| (key(i), format!("{}-{}", i, if i % 3 == 0 { 1 } else { 0 }).into_bytes()))
+            .collect();
+        assert_eq!(entries, expected);
+    }
+
+    // Counts the runs left in `dir`
+    fn runs_in(dir: &Path) -> usize {
+        let paths = fs::read_dir(dir).unwrap().map(|entry| entry.unwrap().path());
+        paths.filter(|path| path.extension().is_some_and(|extension| extension == "run")).count()
+    }
+
+    #[test]
+    fn small_inputs_are_sorted_in_memory() {
+        let output = TempPath::new("sorting-memory.sst");
+        let temp_dir = TempPath::new("sorting-memory-runs");
+        fs::create_dir_all(&temp_dir).unwrap();
+        let mut writer = SortingSstWriter::new(&output).temp_dir(&temp_dir);
+        add_scrambled(&mut writer, 1000);
+        assert!(writer.runs.is_empty());
+        writer.finish().unwrap();
+        assert_eq!(runs_in(&temp_dir), 0);
+        check_output(&output, 1000);
+        fs::remove_dir(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn large_inputs_spill_to_runs_and_merge() {
+        let output = TempPath::new("sorting-spill.sst");
+        let temp_dir = TempPath::new("sorting-spill-runs");
+        fs::create_dir_all(&temp_dir).unwrap();
+        // About 15 bytes a pair, so a run every 70 pairs or so: more than fit in one merge
+        let mut writer = SortingSstWriter::new(&output).temp_dir(&temp_dir).memory_limit(1024);
+        add_scrambled(&mut writer, 5000);
+        assert!(writer.runs.len() > MAX_MERGE_WIDTH, "{} runs", writer.runs.len());
+        assert_eq!(runs_in(&temp_dir), writer.runs.len());
+        writer.finish().unwrap();
+        assert_eq!(runs_in(&temp_dir), 0);
+        check_output(&output, 5000);
+        fs::remove_dir(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn runs_are_deleted_when_the_writer_is_dropped() {
+        let temp_dir = TempPath::new("sorting-drop-runs");
+        fs::create_dir_all(&temp_dir).unwrap();
+        let output = TempPath::new("sorting-drop.sst");
+        let mut writer = SortingSstWriter::new(&output).temp_dir(&temp_dir).memory_limit(1024);
+        add_scrambled(&mut writer, 500);
+        assert!(runs_in(&temp_dir) > 0);
+        drop(writer);
+        assert_eq!(runs_in(&temp_dir), 0);
+        fs::remove_dir(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn pairs_are_sorted_and_merged_by_the_comparator() {
+        // Bytewise, "B" sorts before "a", and "A" and "a" are different keys
+        let pairs = [("B", "1"), ("a", "2"), ("c", "3"), ("A", "4"), ("b", "5")];
+        let expected = [("A", "4"), ("b", "5"), ("c", "3")].map(|(k, v)| (k.to_string(), v.to_string()));
+        for memory_limit in [usize::MAX, 1] {
+            let output = TempPath::new(&format!("sorting-case-{}.sst", memory_limit));
+            let options = SstWriterBuilder::new().comparator(CaseInsensitiveAsciiComparator);
+            let mut writer = SortingSstWriter::with_options(&output, options).memory_limit(memory_limit);
+            for (key, value) in pairs {
+                writer.add(key.as_bytes(), value.as_bytes()).unwrap();
+            }
+            // Spilling every pair merges the runs, rather than sorting in memory
+            assert_eq!(writer.runs.len(), if memory_limit == 1 { pairs.len() } else { 0 });
+            writer.finish().unwrap();
+
+            let reader = SstReader::open_with_comparator(&output, CaseInsensitiveAsciiComparator).unwrap();
+            assert_eq!(strings(reader.iter()), expected, "memory limit {}", memory_limit);
+        }
+    }
+}
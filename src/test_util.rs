@@ -0,0 +1,85 @@
+// Helpers shared by the unit tests
+
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use crate::reader::SstReader;
+use crate::writer::SstWriter;
+
+// The fruit dataset, in key order
+pub(crate) const FRUIT: [(&str, &str); 5] = [
+    ("apple", "A fruit that grows on trees."),
+    ("banana", "An elongated, edible fruit."),
+    ("cherry", "A small, round stone fruit."),
+    ("date", "A sweet, dark brown oval fruit."),
+    ("elderberry", "A dark purple berry."),
+];
+
+// Opens an in-memory SST holding the fruit dataset
+pub(crate) fn fruit() -> SstReader {
+    let mut bytes = Vec::new();
+    let mut writer = SstWriter::with_writer(&mut bytes);
+    for (key, value) in FRUIT {
+        writer.add(key.as_bytes(), value.as_bytes()).unwrap();
+    }
+    writer.finish().unwrap();
+    SstReader::from_bytes(bytes).unwrap()
+}
+
+// Writes `entries`, which must be in key order, to an SST file at `path`
+pub(crate) fn write_sst(path: &Path, entries: &[(&str, &str)]) {
+    let mut writer = SstWriter::new(path).unwrap();
+    for (key, value) in entries {
+        writer.add(key.as_bytes(), value.as_bytes()).unwrap();
+    }
+    writer.finish().unwrap();
+}
+
+// Collects the entries an iterator returns as strings, failing on any error
+pub(crate) fn strings(
+    entries: impl Iterator<Item = crate::error::Result<(Vec<u8>, Vec<u8>)>>,
+) -> Vec<(String, String)> {
+    entries
+        .map(|entry| {
+            let (key, value) = entry.unwrap();
+            (String::from_utf8(key).unwrap(), String::from_utf8(value).unwrap())
+        })
+        .collect()
+}
+
+// Collects the keys an iterator returns as strings, failing on any error
+pub(crate) fn keys(entries: impl Iterator<Item = crate::error::Result<(Vec<u8>, Vec<u8>)>>) -> Vec<String> {
+    entries.map(|entry| String::from_utf8(entry.unwrap().0).unwrap()).collect()
+}
+
+// A path in the system temporary directory, unique to the test process and `name`.
+// The file there, if any, is deleted when the path is dropped.
+pub(crate) struct TempPath(PathBuf);
+
+impl TempPath {
+    pub(crate) fn new(name: &str) -> Self {
+        TempPath(std::env::temp_dir().join(format!("sst-test-{}-{}", process::id(), name)))
+    }
+}
+
+impl Deref for TempPath {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempPath {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
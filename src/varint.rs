@@ -0,0 +1,106 @@
+// LEB128 variable-length integers: 7 bits per byte, least significant group first,
+// with the high bit set on every byte except the last
+
+// Number of bytes `value` takes when encoded
+pub(crate) fn encoded_len(value: u64) -> usize {
+    (64 - (value | 1).leading_zeros() as usize).div_ceil(7)
+}
+
+// Appends the encoding of `value` to `buf`
+pub(crate) fn put(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+// Decodes a value from the front of `buf`, or returns None if it is truncated or overflows a u64
+pub(crate) fn read(buf: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+    for (i, &byte) in buf.iter().enumerate().take(10) {
+        let bits = (byte & 0x7F) as u64;
+        // The tenth byte can only hold the top bit of a u64
+        if i == 9 && bits > 1 {
+            return None;
+        }
+        value |= bits << (7 * i);
+        if byte & 0x80 == 0 {
+            *buf = &buf[i + 1..];
+            return Some(value);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reader::SstReader;
+    use crate::writer::SstWriterBuilder;
+
+    #[test]
+    fn round_trips_across_byte_boundaries() {
+        let cases = [
+            (0, &[0x00][..]),
+            (127, &[0x7F]),
+            (128, &[0x80, 0x01]),
+            (16383, &[0xFF, 0x7F]),
+            (16384, &[0x80, 0x80, 0x01]),
+            (u64::MAX, &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x01]),
+        ];
+        for (value, encoding) in cases {
+            let mut buf = Vec::new();
+            put(&mut buf, value);
+            assert_eq!(buf, encoding, "{}", value);
+            assert_eq!(encoded_len(value), encoding.len(), "{}", value);
+
+            // Decoding leaves whatever follows the value
+            buf.push(0xAA);
+            let mut rest = &buf[..];
+            assert_eq!(read(&mut rest), Some(value));
+            assert_eq!(rest, [0xAA]);
+            // and fails, leaving the buffer alone, if the value is cut short
+            let mut truncated = &encoding[..encoding.len() - 1];
+            assert_eq!(read(&mut truncated), None, "{}", value);
+            assert_eq!(truncated.len(), encoding.len() - 1);
+        }
+    }
+
+    #[test]
+    fn values_overflowing_a_u64_are_rejected() {
+        let mut too_big = vec![0xFF; 9];
+        too_big.push(0x02);
+        assert_eq!(read(&mut &too_big[..]), None);
+        // Eleven bytes can't be a u64 however small their bits
+        let mut too_long = vec![0x80; 10];
+        too_long.push(0x00);
+        assert_eq!(read(&mut &too_long[..]), None);
+    }
+
+    #[test]
+    fn entries_with_lengths_at_the_boundaries_round_trip() {
+        let mut bytes = Vec::new();
+        let mut writer = SstWriterBuilder::new().block_size(1 << 16).build_with_writer(&mut bytes);
+        let mut expected = Vec::new();
+        for (i, value_len) in [0, 1, 127, 128, 16383, 16384, 40000].into_iter().enumerate() {
+            for (j, key_len) in [1, 127, 128, 16383, 16384].into_iter().enumerate() {
+                // Keys share their first byte with the previous key, or more, so the shared
+                // and unshared lengths cross the boundaries too
+                let mut key = format!("{}{}", i, j).into_bytes();
+                key.resize(key_len + 2, b'k');
+                let value = vec![(i * 7 + j) as u8; value_len];
+                writer.add(&key, &value).unwrap();
+                expected.push((key, value));
+            }
+        }
+        writer.finish().unwrap();
+
+        let reader = SstReader::from_bytes(bytes).unwrap();
+        reader.verify().unwrap();
+        assert_eq!(reader.iter().map(Result::unwrap).collect::<Vec<_>>(), expected);
+        for (key, value) in &expected {
+            assert_eq!(reader.get(key).unwrap().as_ref(), Some(value));
+        }
+    }
+}
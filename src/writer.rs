@@ -1,54 +1,167 @@
-use std::fs::{File, OpenOptions};
-use std::io::{self, BufWriter, Write};
-use std::path::Path;
+use std::cmp::Ordering;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::bloom;
+use crate::checksum::{self, Checksum, XxHash64};
+use crate::comparator::{BytewiseComparator, Comparator};
+use crate::compression::{self, Compression};
+use crate::error::{Result, SstError};
+use crate::format::{
+    self, FILE_CHECKSUM_END, FORMAT_VERSION, Footer, HASH_BUCKET_COLLISION, HASH_BUCKET_EMPTY, HASH_INDEX_FLAG,
+    HEADER_SIZE, INDEX_TYPE_FLAT, INDEX_TYPE_PARTITIONED, MAGIC, VALUE_TYPE_BLOB, VALUE_TYPE_TOMBSTONE,
+    VALUE_TYPE_VALUE,
+};
+use crate::properties::Properties;
+use crate::reader::{RawBlock, SstReader, read_bytes, read_u32, read_u64};
+use crate::varint;
 
 // An in-memory representation of a data block
 struct DataBlock {
-    entries: Vec<(Vec<u8>, Vec<u8>)>,
-    size: usize,
+    // Each entry's key, value type and stored value
+    entries: Vec<(Vec<u8>, u8, Vec<u8>)>,
+    // Bytes taken by the encoded entries
+    entries_size: usize,
+    restarts: Vec<u32>,
+    // Number of entries between consecutive restart points
+    restart_interval: usize,
 }
 
 impl DataBlock {
-    fn new() -> Self {
+    fn new(restart_interval: usize) -> Self {
         DataBlock {
             entries: Vec::new(),
-            size: 0,
+            entries_size: 0,
+            restarts: Vec::new(),
+            restart_interval,
+        }
+    }
+
+    // Add an entry with the given value type and stored value to the block
+    fn add(&mut self, key: &[u8], value_type: u8, value: &[u8]) {
+        // Every restart_interval entries, remember where the entry starts
+        // (after the 4-byte num_entries header) so readers can binary search
+        if self.entries.len().is_multiple_of(self.restart_interval) {
+            self.restarts.push((4 + self.entries_size) as u32);
         }
+        let shared = self.shared_prefix_len(self.entries.len(), key);
+        let unshared = key.len() - shared;
+        // Varint lengths for shared, unshared and value, plus the value type byte
+        self.entries_size += varint::encoded_len(shared as u64)
+            + varint::encoded_len(unshared as u64)
+            + varint::encoded_len(value.len() as u64)
+            + 1
+            + unshared
+            + value.len();
+        self.entries.push((key.to_vec(), value_type, value.to_vec()));
     }
 
-    // Add a key-value pair to the block
-    fn add(&mut self, key: &[u8], value: &[u8]) {
-        // 4 bytes for key_len, 4 for value_len
-        self.size += 8 + key.len() + value.len();
-        self.entries.push((key.to_vec(), value.to_vec()));
+    // Length of the prefix the entry at position `i` shares with the previous key.
+    // Entries at restart points always store their full key.
+    fn shared_prefix_len(&self, i: usize, key: &[u8]) -> usize {
+        if i.is_multiple_of(self.restart_interval) {
+            return 0;
+        }
+        let prev_key = &self.entries[i - 1].0;
+        prev_key.iter().zip(key).take_while(|(a, b)| a == b).count()
+    }
+
+    // Whether another entry can start in the block: restart points are 32-bit offsets,
+    // so a block has to be cut before its entries reach 4 GiB
+    fn has_room(&self) -> bool {
+        4 + self.entries_size <= u32::MAX as usize
     }
     
+    // Length of the bytes `to_bytes` returns, without serialising the block
+    fn serialized_len(&self, hash_index: bool) -> usize {
+        let mut len = 4 + self.entries_size + 4 * self.restarts.len() + 4;
+        if self.has_hash_index(hash_index) {
+            len += self.hash_buckets_len() + 2;
+        }
+        len
+    }
+
+    // Bucket values below the two markers name a restart interval, so blocks with
+    // more intervals than that are written without a hash index
+    fn has_hash_index(&self, hash_index: bool) -> bool {
+        hash_index && self.restarts.len() < HASH_BUCKET_COLLISION as usize
+    }
+
     // Get the last key in the block
     fn last_key(&self) -> Option<&[u8]> {
-        self.entries.last().map(|(k, _)| k.as_slice())
+        self.entries.last().map(|(k, _, _)| k.as_slice())
     }
 
-    // Serialise the block to bytes
-    // Format: [num_entries: u32][key1_len: u32][key1][val1_len: u32][val1]...
-    fn to_bytes(&self) -> Vec<u8> {
+    // Serialise the block to bytes, with a hash index if `hash_index` is set
+    // Format: [num_entries: u32][shared1_len: varint][unshared1_len: varint][unshared key1 bytes]
+    //         [value_type1: u8][val1_len: varint][val1]...[restart1: u32]...
+    //         [bucket1: u8]...[num_buckets: u16] (if the block has a hash index)
+    //         [num_restarts | HASH_INDEX_FLAG: u32]
+    fn to_bytes(&self, hash_index: bool, comparator: &dyn Comparator) -> Vec<u8> {
         let mut bytes = Vec::new();
         bytes.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
-        for (key, value) in &self.entries {
-            bytes.extend_from_slice(&(key.len() as u32).to_le_bytes());
-            bytes.extend_from_slice(key);
-            bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        for (i, (key, value_type, value)) in self.entries.iter().enumerate() {
+            let shared = self.shared_prefix_len(i, key);
+            varint::put(&mut bytes, shared as u64);
+            varint::put(&mut bytes, (key.len() - shared) as u64);
+            bytes.extend_from_slice(&key[shared..]);
+            bytes.push(*value_type);
+            varint::put(&mut bytes, value.len() as u64);
             bytes.extend_from_slice(value);
         }
+        for restart in &self.restarts {
+            bytes.extend_from_slice(&restart.to_le_bytes());
+        }
+        let mut num_restarts = self.restarts.len() as u32;
+        if self.has_hash_index(hash_index) {
+            let buckets = self.hash_buckets(comparator);
+            bytes.extend_from_slice(&buckets);
+            bytes.extend_from_slice(&(buckets.len() as u16).to_le_bytes());
+            num_restarts |= HASH_INDEX_FLAG;
+        }
+        bytes.extend_from_slice(&num_restarts.to_le_bytes());
         bytes
     }
+
+    // Number of hash index buckets: about 4 for every 3 keys, keeping collisions rare
+    fn hash_buckets_len(&self) -> usize {
+        (self.entries.len() * 4 / 3).clamp(1, u16::MAX as usize)
+    }
+
+    // Maps each key's hash bucket to the restart interval holding the key, hashing keys
+    // as `comparator` normalises them so lookups of any equal key find the same bucket
+    fn hash_buckets(&self, comparator: &dyn Comparator) -> Vec<u8> {
+        let num_buckets = self.hash_buckets_len();
+        let mut buckets = vec![HASH_BUCKET_EMPTY; num_buckets];
+        for (i, (key, _, _)) in self.entries.iter().enumerate() {
+            let restart_idx = (i / self.restart_interval) as u8;
+            let bucket = &mut buckets[bloom::hash(&comparator.normalize(key)) as usize % num_buckets];
+            if *bucket == HASH_BUCKET_EMPTY {
+                *bucket = restart_idx;
+            } else if *bucket != restart_idx {
+                *bucket = HASH_BUCKET_COLLISION;
+            }
+        }
+        buckets
+    }
 }
 
 // Represents an entry in the index block
-// Format: [last_key_len: u32][last_key][block_offset: u64][block_size: u64]
+// Format: [last_key_len: u32][last_key][block_offset: u64][block_size: u64][cumulative_entries: u64]
+//         [first_key_len: u32][first_key]
 struct IndexEntry {
+    // A separator >= every key in the block and < every key in the next block.
+    // The final block keeps its exact last key so readers can report it.
     last_key: Vec<u8>,
     block_offset: u64,
     block_size: u64,
+    // Number of entries in this block and all blocks before it
+    cumulative_entries: u64,
+    // The block's exact first key, so lookups for keys between blocks needn't read one
+    first_key: Vec<u8>,
 }
 
 impl IndexEntry {
@@ -58,54 +171,817 @@ impl IndexEntry {
         bytes.extend_from_slice(&self.last_key);
         bytes.extend_from_slice(&self.block_offset.to_le_bytes());
         bytes.extend_from_slice(&self.block_size.to_le_bytes());
+        bytes.extend_from_slice(&self.cumulative_entries.to_le_bytes());
+        bytes.extend_from_slice(&(self.first_key.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.first_key);
         bytes
     }
 }
 
-
-/// Builds an SST file.
-pub struct SstWriter {
-    writer: BufWriter<File>,
-    current_block: DataBlock,
-    index: Vec<IndexEntry>,
+// The progress of a writer, saved by `SstWriter::checkpoint` so the build can be resumed
+// Written right after a block is flushed, so it has no partly built block.
+// Format: [format_version: u32][offset: u64][file_hash: u64][num_entries: u64][total_key_bytes: u64]
+//         [total_value_bytes: u64][first_key][last_key][pending_key][pending_value]
+//         [num_index_entries: u32]([index entry])...[num_key_hashes: u64]([key_hash: u32])...
+// The keys and the pending value are each stored as [present: u8]([len: u32][bytes]).
+struct Checkpoint {
     offset: u64,
-    block_size_threshold: usize,
+    // The file checksum's hash of the first `offset` bytes of the file
+    file_hash: u64,
+    num_entries: u64,
+    total_key_bytes: u64,
+    total_value_bytes: u64,
+    first_key: Option<Vec<u8>>,
+    last_key: Option<Vec<u8>>,
+    pending: Option<(Vec<u8>, Option<Vec<u8>>)>,
+    index: Vec<IndexEntry>,
+    key_hashes: Vec<u32>,
 }
 
-impl SstWriter {
-    /// Creates a new writer for the given path.
-    pub fn new(path: &Path) -> io::Result<Self> {
+impl Checkpoint {
+    fn from_bytes(mut buf: &[u8]) -> Result<Checkpoint> {
+        let truncated = || SstError::CorruptCheckpoint("state is truncated".to_string());
+        let read_optional = |buf: &mut &[u8]| -> Result<Option<Vec<u8>>> {
+            match read_bytes(buf, 1).ok_or_else(truncated)?[0] {
+                0 => Ok(None),
+                _ => {
+                    let len = read_u32(buf).ok_or_else(truncated)? as usize;
+                    Ok(Some(read_bytes(buf, len).ok_or_else(truncated)?.to_vec()))
+                }
+            }
+        };
+
+        let version = read_u32(&mut buf).ok_or_else(truncated)?;
+        if version != FORMAT_VERSION {
+            return Err(SstError::CorruptCheckpoint(format!(
+                "state was saved by format version {}, not {}",
+                version, FORMAT_VERSION
+            )));
+        }
+        let offset = read_u64(&mut buf).ok_or_else(truncated)?;
+        let file_hash = read_u64(&mut buf).ok_or_else(truncated)?;
+        let num_entries = read_u64(&mut buf).ok_or_else(truncated)?;
+        let total_key_bytes = read_u64(&mut buf).ok_or_else(truncated)?;
+        let total_value_bytes = read_u64(&mut buf).ok_or_else(truncated)?;
+        let first_key = read_optional(&mut buf)?;
+        let last_key = read_optional(&mut buf)?;
+        let pending_key = read_optional(&mut buf)?;
+        let pending_value = read_optional(&mut buf)?;
+
+        let num_index_entries = read_u32(&mut buf).ok_or_else(truncated)?;
+        let mut index = Vec::new();
+        for _ in 0..num_index_entries {
+            let key_len = read_u32(&mut buf).ok_or_else(truncated)? as usize;
+            let last_key = read_bytes(&mut buf, key_len).ok_or_else(truncated)?.to_vec();
+            let block_offset = read_u64(&mut buf).ok_or_else(truncated)?;
+            let block_size = read_u64(&mut buf).ok_or_else(truncated)?;
+            let cumulative_entries = read_u64(&mut buf).ok_or_else(truncated)?;
+            let key_len = read_u32(&mut buf).ok_or_else(truncated)? as usize;
+            index.push(IndexEntry {
+                last_key,
+                block_offset,
+                block_size,
+                cumulative_entries,
+                first_key: read_bytes(&mut buf, key_len).ok_or_else(truncated)?.to_vec(),
+            });
+        }
+
+        let num_key_hashes = read_u64(&mut buf).ok_or_else(truncated)?;
+        let mut key_hashes = Vec::new();
+        for _ in 0..num_key_hashes {
+            key_hashes.push(read_u32(&mut buf).ok_or_else(truncated)?);
+        }
+
+        Ok(Checkpoint {
+            offset,
+            file_hash,
+            num_entries,
+            total_key_bytes,
+            total_value_bytes,
+            first_key,
+            last_key,
+            pending: pending_key.map(|key| (key, pending_value)),
+            index,
+            key_hashes,
+        })
+    }
+}
+
+/// Configures and creates an `SstWriter`.
+#[derive(Debug, Clone)]
+pub struct SstWriterBuilder {
+    block_size: usize,
+    max_entries_per_block: Option<usize>,
+    restart_interval: usize,
+    checksum: Checksum,
+    compression: Compression,
+    bloom_bits_per_key: usize,
+    blob_threshold: Option<usize>,
+    hash_index: bool,
+    index_partition_size: Option<usize>,
+    index_sample_interval: usize,
+    dedup_keys: bool,
+    sync_on_finish: bool,
+    bloom_sidecar: bool,
+    write_buffer_capacity: Option<usize>,
+    application_magic: [u8; 8],
+    user_metadata: Vec<(String, Vec<u8>)>,
+    comparator: Arc<dyn Comparator>,
+}
+
+impl Default for SstWriterBuilder {
+    fn default() -> Self {
+        SstWriterBuilder {
+            block_size: 4096, // 4KB block size target
+            max_entries_per_block: None,
+            restart_interval: 16,
+            checksum: Checksum::None,
+            compression: Compression::None,
+            bloom_bits_per_key: 10,
+            blob_threshold: None,
+            hash_index: false,
+            index_partition_size: None,
+            index_sample_interval: 1,
+            dedup_keys: false,
+            sync_on_finish: true,
+            bloom_sidecar: false,
+            write_buffer_capacity: None,
+            application_magic: [0; 8],
+            user_metadata: Vec::new(),
+            comparator: Arc::new(BytewiseComparator),
+        }
+    }
+}
+
+impl SstWriterBuilder {
+    /// Creates a builder with the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the target size in bytes of each data block (4096 by default).
+    /// A block is flushed once it reaches this size as stored before compression,
+    /// counting its header, restart points, hash index and trailer as well as its entries.
+    pub fn block_size(mut self, block_size: usize) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    /// Caps the number of entries in each data block (no cap by default; values below 1
+    /// are treated as 1). A block is then flushed once it reaches either `block_size`
+    /// bytes or this many entries, whichever comes first, which gives blocks of fixed-size
+    /// records a predictable number of entries.
+    pub fn max_entries_per_block(mut self, max_entries: usize) -> Self {
+        self.max_entries_per_block = Some(max_entries.max(1));
+        self
+    }
+
+    /// Sets the number of entries between restart points in each data block (16 by
+    /// default, as in LevelDB; values below 1 are treated as 1). Keys at restart points
+    /// are stored in full rather than prefix compressed, and lookups binary search the
+    /// restart points before scanning forward. A smaller interval makes lookups scan
+    /// fewer entries, while a larger one stores fewer full keys and so smaller blocks.
+    /// Each block records where its restart points are, so readers need no setting.
+    pub fn restart_interval(mut self, interval: usize) -> Self {
+        self.restart_interval = interval.max(1);
+        self
+    }
+
+    /// Enables or disables a CRC32 checksum on every data block (off by default).
+    /// Readers verify the checksum before searching a block.
+    pub fn checksums(mut self, enabled: bool) -> Self {
+        self.checksum = if enabled { Checksum::Crc32 } else { Checksum::None };
+        self
+    }
+
+    /// Sets the checksum stored with every data block (`Checksum::None` by default).
+    /// The algorithm is recorded per block, so readers verify either kind.
+    pub fn checksum(mut self, checksum: Checksum) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// Sets the compression applied to each data block (`Compression::None` by default).
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    /// Sets the number of bloom filter bits per key (10 by default, about a 1% false
+    /// positive rate). Passing 0 disables the bloom filter.
+    pub fn bloom_bits_per_key(mut self, bits_per_key: usize) -> Self {
+        self.bloom_bits_per_key = bits_per_key;
+        self
+    }
+
+    /// Stores values of at least `threshold` bytes as blobs outside the data blocks,
+    /// leaving only a pointer in the block (off by default). Blocks stay small, so
+    /// lookups and scans don't read large values they skip over; `get` and the
+    /// iterators follow the pointer transparently.
+    pub fn blob_threshold(mut self, threshold: usize) -> Self {
+        self.blob_threshold = Some(threshold);
+        self
+    }
+
+    /// Enables or disables a hash index in every data block (off by default). The index
+    /// maps each key's hash to the part of the block holding the key, so point lookups
+    /// skip the binary search over restart points and rule out most missing keys without
+    /// decoding an entry, at the cost of about one byte per key. Scans are unaffected.
+    pub fn hash_index(mut self, enabled: bool) -> Self {
+        self.hash_index = enabled;
+        self
+    }
+
+    /// Splits the index into partitions of about `size` bytes each, plus a small top-level
+    /// index pointing to them (off by default, writing a single index block). Readers load
+    /// only the top level when opening the file and read partitions as lookups reach them,
+    /// so opening a very large file stays cheap.
+    pub fn index_partition_size(mut self, size: usize) -> Self {
+        self.index_partition_size = Some(size);
+        self
+    }
+
+    /// Makes the index sparse, recording keys only for every `interval`th data block (1,
+    /// every block, by default). Each sampled block starts a span of `interval` blocks and
+    /// records the span's first key and index key; the other blocks in the span record
+    /// only where they are. Lookups binary search the spans, then read the span's blocks
+    /// in turn until reaching the one that could hold the key. With small blocks or long
+    /// keys this makes the index far smaller, at the cost of reading up to `interval`
+    /// blocks per lookup.
+    pub fn index_sample_interval(mut self, interval: usize) -> Self {
+        self.index_sample_interval = interval.max(1);
+        self
+    }
+
+    /// Enables or disables last-wins handling of duplicate keys (off by default). When
+    /// enabled, adding a key equal to the previous one replaces its value (or tombstone)
+    /// instead of failing with `SstError::DuplicateKey`. The latest entry is held back
+    /// until a greater key or `finish` shows it is final, so each entry is copied once.
+    pub fn dedup_keys(mut self, enabled: bool) -> Self {
+        self.dedup_keys = enabled;
+        self
+    }
+
+    /// Sets whether `finish` syncs the file to disk before returning (on by default).
+    /// When enabled, the file's contents are durable once `finish` returns, though the
+    /// directory entry of a newly created file is only durable once its parent
+    /// directory is synced too. Only applies to writers created for a path; writers
+    /// over other `Write` targets are flushed but never synced.
+    pub fn sync_on_finish(mut self, enabled: bool) -> Self {
+        self.sync_on_finish = enabled;
+        self
+    }
+
+    /// Sets whether `finish` also writes a copy of the bloom filter to a small sidecar file
+    /// at `bloom::sidecar_path` of the SST's path (off by default). Readers opened with
+    /// `SstReader::open_with_bloom` load the filter from there, so the sidecar can be kept
+    /// on fast local storage while the SST itself lives somewhere slower. The sidecar is
+    /// written once the SST is complete, and is synced along with it. Only applies to
+    /// writers created for a path.
+    pub fn bloom_sidecar(mut self, enabled: bool) -> Self {
+        self.bloom_sidecar = enabled;
+        self
+    }
+
+    /// Sets the capacity in bytes of the buffer between the writer and the file
+    /// (`BufWriter`'s default, currently 8 KiB, if unset). A larger buffer means fewer
+    /// write syscalls when building large files. Only applies to `build`.
+    pub fn write_buffer_capacity(mut self, capacity: usize) -> Self {
+        self.write_buffer_capacity = Some(capacity);
+        self
+    }
+
+    /// Sets an application magic to record in the footer (none by default), so an
+    /// application can tell its own SSTs apart from those written by other tools. It is
+    /// stored alongside the crate's magic number rather than replacing it; readers check
+    /// it with `SstReader::expect_application_magic`. All zeros is the same as none.
+    pub fn application_magic(mut self, magic: [u8; 8]) -> Self {
+        self.application_magic = magic;
+        self
+    }
+
+    /// Sets application-defined metadata to record in the properties block (none by
+    /// default), such as the schema version of the values. Readers get it back from
+    /// `SstReader::user_metadata`. Setting a name twice keeps the last value.
+    pub fn user_metadata(mut self, mut metadata: Vec<(String, Vec<u8>)>) -> Self {
+        metadata.reverse();
+        metadata.sort_by(|(a, _), (b, _)| a.cmp(b));
+        metadata.dedup_by(|(a, _), (b, _)| a == b);
+        self.user_metadata = metadata;
+        self
+    }
+
+    /// Sets the order keys must be added in (`BytewiseComparator` by default). Any other
+    /// comparator's name is recorded in the file, and it must be read with a reader
+    /// opened by `SstReader::open_with_comparator` with the same comparator.
+    pub fn comparator(mut self, comparator: impl Comparator + 'static) -> Self {
+        self.comparator = Arc::new(comparator);
+        self
+    }
+
+    pub(crate) fn comparator_ref(&self) -> &Arc<dyn Comparator> {
+        &self.comparator
+    }
+
+    /// Creates a writer for the given path with these options.
+    pub fn build(self, path: &Path) -> Result<SstWriter> {
         let file = OpenOptions::new()
             .write(true)
             .create(true)
             .truncate(true)
             .open(path)?;
-        Ok(SstWriter {
-            writer: BufWriter::new(file),
-            current_block: DataBlock::new(),
+        Ok(self.build_with_file(file, path))
+    }
+
+    /// Reopens the partly written SST at `path` to continue from the checkpoint saved
+    /// to `state` by `SstWriter::checkpoint`, typically in an earlier run of the process.
+    /// Anything written to the file after that checkpoint is discarded. Keys added to
+    /// the resumed writer must still sort after every key added before the checkpoint.
+    ///
+    /// The builder should have the options the original writer was built with. The
+    /// state file is left in place; delete it once the resumed writer is finished.
+    /// Fails with `SstError::CorruptCheckpoint`, leaving the file untouched, if the file
+    /// no longer holds what had been written when the checkpoint was saved.
+    pub fn resume(self, path: &Path, state: &Path) -> Result<SstWriter> {
+        let state = fs::read(state)?;
+        let checkpoint = Checkpoint::from_bytes(&state)?;
+
+        let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+        if file.metadata()?.len() < checkpoint.offset {
+            return Err(SstError::CorruptCheckpoint(format!(
+                "checkpoint is at offset {} but the file holds {} bytes",
+                checkpoint.offset,
+                file.metadata()?.len()
+            )));
+        }
+
+        // The file checksum covers everything written before the checkpoint, so hash it again
+        let mut file_hash = XxHash64::new();
+        let mut buf = vec![0; 64 * 1024];
+        let mut remaining = checkpoint.offset;
+        file.seek(SeekFrom::Start(0))?;
+        while remaining > 0 {
+            let len = remaining.min(buf.len() as u64) as usize;
+            file.read_exact(&mut buf[..len])?;
+            file_hash.update(&buf[..len]);
+            remaining -= len as u64;
+        }
+        if file_hash.finish() != checkpoint.file_hash {
+            return Err(SstError::CorruptCheckpoint(
+                "the file doesn't hold what was written before the checkpoint".to_string(),
+            ));
+        }
+        file.set_len(checkpoint.offset)?;
+        file.seek(SeekFrom::End(0))?;
+
+        let mut writer = self.build_with_file(file, path);
+        writer.writer.hash = file_hash;
+        writer.offset = checkpoint.offset;
+        writer.num_entries = checkpoint.num_entries;
+        writer.total_key_bytes = checkpoint.total_key_bytes;
+        writer.total_value_bytes = checkpoint.total_value_bytes;
+        writer.first_key = checkpoint.first_key;
+        writer.last_key = checkpoint.last_key;
+        writer.pending = checkpoint.pending;
+        writer.index = checkpoint.index;
+        writer.key_hashes = checkpoint.key_hashes;
+        Ok(writer)
+    }
+
+    // Creates a writer over the open file at `path`, buffering writes, syncing on finish
+    // and writing a bloom sidecar as configured
+    fn build_with_file(self, file: File, path: &Path) -> SstWriter {
+        let sync_on_finish = self.sync_on_finish;
+        let bloom_sidecar = self.bloom_sidecar.then(|| bloom::sidecar_path(path));
+        let file = match self.write_buffer_capacity {
+            Some(capacity) => BufWriter::with_capacity(capacity, file),
+            None => BufWriter::new(file),
+        };
+        let mut writer = self.build_with_writer(file);
+        if sync_on_finish {
+            writer.sync = Some(|writer| writer.get_ref().sync_all());
+        }
+        writer.bloom_sidecar = bloom_sidecar;
+        writer
+    }
+
+    /// Creates a writer that writes the SST to `writer` with these options.
+    pub fn build_with_writer<W: Write>(self, writer: W) -> SstWriter<W> {
+        SstWriter {
+            writer: HashingWriter {
+                inner: writer,
+                hash: XxHash64::new(),
+            },
+            current_block: DataBlock::new(self.restart_interval),
             index: Vec::new(),
             offset: 0,
-            block_size_threshold: 4096, // 4KB block size target
-        })
+            block_size_threshold: self.block_size,
+            max_entries_per_block: self.max_entries_per_block,
+            first_key: None,
+            last_key: None,
+            checksum: self.checksum,
+            compression: self.compression,
+            bloom_bits_per_key: self.bloom_bits_per_key,
+            blob_threshold: self.blob_threshold,
+            hash_index: self.hash_index,
+            index_partition_size: self.index_partition_size,
+            index_sample_interval: self.index_sample_interval,
+            dedup_keys: self.dedup_keys,
+            application_magic: self.application_magic,
+            user_metadata: self.user_metadata,
+            comparator: self.comparator,
+            pending: None,
+            key_hashes: Vec::new(),
+            num_entries: 0,
+            total_key_bytes: 0,
+            total_value_bytes: 0,
+            sync: None,
+            bloom_sidecar: None,
+        }
+    }
+}
+
+// Passes writes through to `inner`, hashing every byte written for the file checksum
+struct HashingWriter<W> {
+    inner: W,
+    hash: XxHash64,
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hash.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Sizes and counts describing a finished SST, returned by `SstWriter::finish`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct WriteStats {
+    /// Number of entries written, tombstones included.
+    pub entries: u64,
+    /// Number of data blocks written.
+    pub data_blocks: u64,
+    /// Number of entries in the index, one per data block.
+    pub index_entries: u64,
+    /// Size of the whole file in bytes.
+    pub total_bytes: u64,
+    /// Bytes taken by data blocks, after compression and including their trailers.
+    pub data_bytes: u64,
+    /// Bytes taken by values stored as blobs outside the data blocks.
+    pub blob_bytes: u64,
+    /// Bytes taken by the bloom filter.
+    pub bloom_bytes: u64,
+    /// Bytes taken by the index block, including every partition of a partitioned index.
+    pub index_bytes: u64,
+    /// Bytes taken by the properties block.
+    pub properties_bytes: u64,
+}
+
+/// Builds an SST file, writing it to a file on disk by default or to any `Write` target.
+pub struct SstWriter<W: Write = BufWriter<File>> {
+    writer: HashingWriter<W>,
+    current_block: DataBlock,
+    index: Vec<IndexEntry>,
+    offset: u64,
+    block_size_threshold: usize,
+    max_entries_per_block: Option<usize>,
+    first_key: Option<Vec<u8>>,
+    last_key: Option<Vec<u8>>,
+    checksum: Checksum,
+    compression: Compression,
+    bloom_bits_per_key: usize,
+    blob_threshold: Option<usize>,
+    hash_index: bool,
+    index_partition_size: Option<usize>,
+    index_sample_interval: usize,
+    dedup_keys: bool,
+    application_magic: [u8; 8],
+    user_metadata: Vec<(String, Vec<u8>)>,
+    comparator: Arc<dyn Comparator>,
+    // With dedup_keys, the latest entry, not yet written in case the same key follows
+    pending: Option<(Vec<u8>, Option<Vec<u8>>)>,
+    key_hashes: Vec<u32>,
+    num_entries: u64,
+    total_key_bytes: u64,
+    total_value_bytes: u64,
+    // Syncs the underlying file to disk once everything has been flushed
+    sync: Option<fn(&W) -> io::Result<()>>,
+    // Where to write a copy of the bloom filter once the file is finished, if anywhere
+    bloom_sidecar: Option<PathBuf>,
+}
+
+/// An operation in a batch of writes passed to `SstWriter::from_write_batch`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    /// Sets the key to the value.
+    Put,
+    /// Deletes the key, leaving a tombstone.
+    Delete,
+}
+
+impl SstWriter {
+    /// Creates a new writer for the given path with the default options.
+    pub fn new(path: &Path) -> Result<Self> {
+        SstWriterBuilder::new().build(path)
+    }
+
+    /// Returns a builder for configuring a writer.
+    pub fn builder() -> SstWriterBuilder {
+        SstWriterBuilder::new()
     }
 
-    /// Adds a key-value pair. Keys MUST be added in sorted order.
-    pub fn add(&mut self, key: &[u8], value: &[u8]) -> io::Result<()> {
-        self.current_block.add(key, value);
-        if self.current_block.size >= self.block_size_threshold {
+    /// Builds an SST at `path` from an iterator of key-value pairs and returns the
+    /// number of entries written. The iterator MUST already be sorted by key;
+    /// out-of-order items fail with `SstError::KeysOutOfOrder`, and repeated keys
+    /// with `SstError::DuplicateKey`.
+    pub fn write_sorted<I>(path: &Path, iter: I) -> Result<u64>
+    where
+        I: IntoIterator<Item = (Vec<u8>, Vec<u8>)>,
+    {
+        let mut writer = SstWriter::new(path)?;
+        let mut entries = 0;
+        for (key, value) in iter {
+            writer.add(&key, &value)?;
+            entries += 1;
+        }
+        writer.finish()?;
+        Ok(entries)
+    }
+
+    /// Builds an SST at `output` from a text file of `key<delimiter>value` lines,
+    /// such as a CSV or TSV export. The lines MUST already be sorted by key.
+    ///
+    /// Only the first delimiter on a line splits it, so values may contain the
+    /// delimiter; keys can't. Empty lines are skipped and `\r\n` line endings are
+    /// accepted. A line without a delimiter, with an empty key, or whose key isn't
+    /// greater than the previous line's, fails with `SstError::InvalidInputLine`.
+    pub fn from_csv(input: &Path, output: &Path, delimiter: u8) -> Result<WriteStats> {
+        let reader = BufReader::new(File::open(input)?);
+        let mut writer = SstWriter::new(output)?;
+        for (i, line) in reader.split(b'\n').enumerate() {
+            let line = line?;
+            let line = line.strip_suffix(b"\r").unwrap_or(&line);
+            if line.is_empty() {
+                continue;
+            }
+            let invalid = |reason: &str| SstError::InvalidInputLine {
+                line: i as u64 + 1,
+                reason: reason.to_string(),
+            };
+            let split = line
+                .iter()
+                .position(|&byte| byte == delimiter)
+                .ok_or_else(|| invalid("missing delimiter"))?;
+            match writer.add(&line[..split], &line[split + 1..]) {
+                Err(SstError::KeysOutOfOrder) => {
+                    return Err(invalid("key is less than the previous line's key"));
+                }
+                Err(SstError::DuplicateKey) => return Err(invalid("key is the same as the previous line's key")),
+                Err(SstError::EmptyKey) => return Err(invalid("key is empty")),
+                result => result?,
+            }
+        }
+        writer.finish()
+    }
+
+    /// Builds an SST at `path` from a batch of writes in the style of a RocksDB
+    /// `WriteBatch`, in any order. Operations are sorted by key, and where a key appears
+    /// more than once the last operation on it in the batch wins. Deletes are written as
+    /// tombstones, so they hide the key in older files merged with this one; their values
+    /// are ignored.
+    pub fn from_write_batch<K, V>(path: &Path, batch: &[(Op, K, V)]) -> Result<WriteStats>
+    where
+        K: AsRef<[u8]>,
+        V: AsRef<[u8]>,
+    {
+        let mut ops: Vec<&(Op, K, V)> = batch.iter().collect();
+        // The sort is stable, so operations on the same key stay in batch order and
+        // dedup_keys keeps the last of them
+        ops.sort_by(|(_, a, _), (_, b, _)| a.as_ref().cmp(b.as_ref()));
+        let mut writer = SstWriterBuilder::new().dedup_keys(true).build(path)?;
+        for (op, key, value) in ops {
+            match op {
+                Op::Put => writer.add(key.as_ref(), value.as_ref())?,
+                Op::Delete => writer.delete(key.as_ref())?,
+            }
+        }
+        writer.finish()
+    }
+}
+
+impl<W: Write> SstWriter<W> {
+    /// Creates a writer with the default options that writes the SST to `writer`.
+    /// Blocks are written strictly sequentially, so the target never needs to seek;
+    /// pass `&mut Vec<u8>` to build an SST in memory.
+    pub fn with_writer(writer: W) -> Self {
+        SstWriterBuilder::new().build_with_writer(writer)
+    }
+
+    /// Adds a key-value pair. Keys MUST be added in strictly ascending order:
+    /// a smaller key fails with `SstError::KeysOutOfOrder` and a repeated one with
+    /// `SstError::DuplicateKey`, unless the writer was built with `dedup_keys`.
+    /// Keys must not be empty (`SstError::EmptyKey`) or longer than `MAX_KEY_LEN`
+    /// (`SstError::KeyTooLarge`); values may be empty and of any length.
+    pub fn add(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        self.add_entry(key, Some(value))
+    }
+
+    /// Records a tombstone for `key`, marking it as deleted. The tombstone hides any
+    /// value for the same key in older files when they are merged with this one.
+    /// Keys MUST be valid and added in strictly ascending order, just like with `add`.
+    pub fn delete(&mut self, key: &[u8]) -> Result<()> {
+        self.add_entry(key, None)
+    }
+
+    /// Returns the number of bytes of the file written so far: the header, every data
+    /// block flushed and every blob. It grows each time a block fills up, so it suits
+    /// progress reporting and throttling of large loads. The block being filled, the
+    /// index and the other metadata only count once `finish` writes them. Bytes may
+    /// still sit in the write buffer rather than on disk.
+    pub fn bytes_written(&self) -> u64 {
+        self.offset
+    }
+
+    // Adds a key with a value, or a tombstone if `value` is None
+    fn add_entry(&mut self, key: &[u8], value: Option<&[u8]>) -> Result<()> {
+        format::check_key_len(key.len())?;
+        if !self.dedup_keys {
+            return self.write_entry(key, value);
+        }
+
+        if let Some((pending_key, pending_value)) = &mut self.pending {
+            match self.comparator.compare(key, pending_key) {
+                Ordering::Less => return Err(SstError::KeysOutOfOrder),
+                Ordering::Equal => {
+                    *pending_value = value.map(<[u8]>::to_vec);
+                    return Ok(());
+                }
+                Ordering::Greater => {}
+            }
+        }
+        match self.pending.replace((key.to_vec(), value.map(<[u8]>::to_vec))) {
+            Some((pending_key, pending_value)) => self.write_entry(&pending_key, pending_value.as_deref()),
+            None => Ok(()),
+        }
+    }
+
+    // Writes a key with a value, or a tombstone if `value` is None, into the current block
+    fn write_entry(&mut self, key: &[u8], value: Option<&[u8]>) -> Result<()> {
+        self.write_header()?;
+        // The reader's index lookup relies on keys being strictly increasing
+        if let Some(last_key) = &self.last_key {
+            match self.comparator.compare(key, last_key) {
+                Ordering::Less => return Err(SstError::KeysOutOfOrder),
+                Ordering::Equal => return Err(SstError::DuplicateKey),
+                Ordering::Greater => {}
+            }
+        }
+        if self.first_key.is_none() {
+            self.first_key = Some(key.to_vec());
+        }
+        self.last_key = Some(key.to_vec());
+        self.num_entries += 1;
+        self.total_key_bytes += key.len() as u64;
+        self.total_value_bytes += value.map_or(0, |value| value.len() as u64);
+        if self.bloom_bits_per_key > 0 {
+            self.key_hashes.push(bloom::hash(&self.comparator.normalize(key)));
+        }
+
+        // A block whose entries fill the 4 GiB its restart points can address is written
+        // out early, however large the block size is
+        if !self.current_block.has_room() {
+            self.flush_block()?;
+        }
+
+        // Now that the first key after the previous block is known,
+        // shorten that block's index key to a separator
+        if self.current_block.entries.is_empty()
+            && let Some(entry) = self.index.last_mut()
+        {
+            entry.last_key = self.comparator.separator(&entry.last_key, key);
+        }
+
+        match value {
+            // Tombstones are stored with an empty value
+            None => self.current_block.add(key, VALUE_TYPE_TOMBSTONE, &[]),
+            Some(value) if self.blob_threshold.is_some_and(|threshold| value.len() >= threshold) => {
+                let pointer = self.write_blob(value)?;
+                self.current_block.add(key, VALUE_TYPE_BLOB, &pointer);
+            }
+            Some(value) => self.current_block.add(key, VALUE_TYPE_VALUE, value),
+        }
+        if self.current_block_size() >= self.block_size_threshold
+            || self
+                .max_entries_per_block
+                .is_some_and(|max_entries| self.current_block.entries.len() >= max_entries)
+        {
             self.flush_block()?;
         }
         Ok(())
     }
 
+    // Returns whether the data blocks of `reader` can be copied into this file unchanged:
+    // they must be in the current block format, unchanged since version 12, and use this
+    // writer's compression and checksum, which the properties block records for the whole file.
+    // Their keys must also be in this writer's order.
+    pub(crate) fn can_copy_blocks_from(&self, reader: &SstReader) -> bool {
+        reader.version() >= 12
+            && reader.comparator().name() == self.comparator.name()
+            && reader.properties().is_some_and(|properties| {
+                properties.compression == self.compression && properties.checksum == self.checksum
+            })
+    }
+
+    // Appends a data block read from another SST without decompressing or re-encoding it.
+    // Its keys must all be greater than every key added so far.
+    pub(crate) fn copy_block(&mut self, block: &RawBlock) -> Result<()> {
+        let (Some(first_key), Some(last_key)) = (block.keys.first(), block.keys.last()) else {
+            return Ok(());
+        };
+        if let Some((key, value)) = self.pending.take() {
+            self.write_entry(&key, value.as_deref())?;
+        }
+        self.write_header()?;
+        if let Some(prev_key) = &self.last_key {
+            match self.comparator.compare(first_key, prev_key) {
+                Ordering::Less => return Err(SstError::KeysOutOfOrder),
+                Ordering::Equal => return Err(SstError::DuplicateKey),
+                Ordering::Greater => {}
+            }
+        }
+        self.flush_block()?;
+
+        // The previous block's index key is still its last key, so shorten it as usual
+        if let Some(entry) = self.index.last_mut() {
+            entry.last_key = self.comparator.separator(&entry.last_key, first_key);
+        }
+        if self.first_key.is_none() {
+            self.first_key = Some(first_key.clone());
+        }
+        self.last_key = Some(last_key.clone());
+        self.num_entries += block.keys.len() as u64;
+        self.total_key_bytes += block.keys.iter().map(|key| key.len() as u64).sum::<u64>();
+        self.total_value_bytes += block.value_bytes;
+        if self.bloom_bits_per_key > 0 {
+            self.key_hashes.extend(block.keys.iter().map(|key| bloom::hash(&self.comparator.normalize(key))));
+        }
+
+        self.writer.write_all(&block.bytes)?;
+        self.index.push(IndexEntry {
+            last_key: last_key.clone(),
+            block_offset: self.offset,
+            block_size: block.bytes.len() as u64,
+            cumulative_entries: self.num_entries,
+            first_key: first_key.clone(),
+        });
+        self.offset += block.bytes.len() as u64;
+        Ok(())
+    }
+
+    // The size the current block would take in the file without compression: its
+    // serialised entries, restart points and hash index, plus the compression flag and
+    // checksum trailer. This is what the block size threshold is compared against.
+    fn current_block_size(&self) -> usize {
+        1 + self.current_block.serialized_len(self.hash_index) + checksum::trailer_len(self.checksum)
+    }
+
+    // Writes the magic number to the start of the file, unless anything has been written already
+    fn write_header(&mut self) -> Result<()> {
+        if self.offset == 0 {
+            self.writer.write_all(&MAGIC.to_le_bytes())?;
+            self.offset = HEADER_SIZE;
+        }
+        Ok(())
+    }
+
+    // Writes a large value to the file ahead of the block that will point to it,
+    // returning the pointer: [blob_offset: u64][blob_len: u64]
+    fn write_blob(&mut self, value: &[u8]) -> Result<[u8; 16]> {
+        self.writer.write_all(value)?;
+        let mut pointer = [0; 16];
+        pointer[..8].copy_from_slice(&self.offset.to_le_bytes());
+        pointer[8..].copy_from_slice(&(value.len() as u64).to_le_bytes());
+        self.offset += value.len() as u64;
+        Ok(pointer)
+    }
+
     // Writes the current data block to the file
-    fn flush_block(&mut self) -> io::Result<()> {
+    fn flush_block(&mut self) -> Result<()> {
         if self.current_block.entries.is_empty() {
             return Ok(());
         }
         
+        let first_key = self.current_block.entries[0].0.clone();
         let last_key = self.current_block.last_key().unwrap().to_vec();
-        let block_bytes = self.current_block.to_bytes();
+        // The checksum covers the compressed bytes so corruption is caught before decompressing
+        let block = self.current_block.to_bytes(self.hash_index, &*self.comparator);
+        let mut block_bytes = compression::compress_block(&block, self.compression)?;
+        checksum::append_trailer(&mut block_bytes, self.checksum);
         let block_size = block_bytes.len() as u64;
 
         self.writer.write_all(&block_bytes)?;
@@ -114,35 +990,757 @@ impl SstWriter {
             last_key,
             block_offset: self.offset,
             block_size,
+            cumulative_entries: self.num_entries,
+            first_key,
         });
 
         self.offset += block_size;
-        self.current_block = DataBlock::new();
+        self.current_block = DataBlock::new(self.current_block.restart_interval);
         Ok(())
     }
 
-    /// Finalizes the SST file by writing the index and footer.
-    pub fn finish(mut self) -> io::Result<()> {
-        // Flush any remaining data in the current block
+    /// Saves the writer's progress to a state file at `state`, so that a later run can
+    /// continue the build with `SstWriterBuilder::resume` instead of starting over.
+    ///
+    /// The current block is written out first, and the file is synced before the state
+    /// is saved unless `sync_on_finish(false)` was set, so the state never refers to data
+    /// that isn't durable. The state file is replaced atomically. Adding entries may go
+    /// on after a checkpoint; resuming discards whatever was written after the latest one.
+    pub fn checkpoint(&mut self, state: &Path) -> Result<()> {
+        self.write_header()?;
         self.flush_block()?;
-        
-        // Write the index block
-        let index_block_offset = self.offset;
-        let mut index_bytes = Vec::new();
-        index_bytes.extend_from_slice(&(self.index.len() as u32).to_le_bytes());
+        self.writer.flush()?;
+        if let Some(sync) = self.sync {
+            sync(&self.writer.inner)?;
+        }
+
+        // Write a temporary file and rename it over the old state, so a crash leaves
+        // either the previous checkpoint or this one
+        let mut temp_path = state.as_os_str().to_owned();
+        temp_path.push(".tmp");
+        let mut temp = File::create(&temp_path)?;
+        temp.write_all(&self.checkpoint_bytes())?;
+        temp.sync_all()?;
+        fs::rename(&temp_path, state)?;
+        Ok(())
+    }
+
+    // Serialises the writer's progress in the checkpoint state format
+    fn checkpoint_bytes(&self) -> Vec<u8> {
+        let write_optional = |bytes: &mut Vec<u8>, field: Option<&[u8]>| match field {
+            Some(field) => {
+                bytes.push(1);
+                bytes.extend_from_slice(&(field.len() as u32).to_le_bytes());
+                bytes.extend_from_slice(field);
+            }
+            None => bytes.push(0),
+        };
+
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&self.offset.to_le_bytes());
+        bytes.extend_from_slice(&self.writer.hash.finish().to_le_bytes());
+        bytes.extend_from_slice(&self.num_entries.to_le_bytes());
+        bytes.extend_from_slice(&self.total_key_bytes.to_le_bytes());
+        bytes.extend_from_slice(&self.total_value_bytes.to_le_bytes());
+        write_optional(&mut bytes, self.first_key.as_deref());
+        write_optional(&mut bytes, self.last_key.as_deref());
+        write_optional(&mut bytes, self.pending.as_ref().map(|(key, _)| key.as_slice()));
+        write_optional(&mut bytes, self.pending.as_ref().and_then(|(_, value)| value.as_deref()));
+        bytes.extend_from_slice(&(self.index.len() as u32).to_le_bytes());
         for entry in &self.index {
-            index_bytes.extend_from_slice(&entry.to_bytes());
+            bytes.extend_from_slice(&entry.to_bytes());
+        }
+        bytes.extend_from_slice(&(self.key_hashes.len() as u64).to_le_bytes());
+        for hash in &self.key_hashes {
+            bytes.extend_from_slice(&hash.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Finalizes the SST file by writing the index and footer, and returns statistics
+    /// about the file's layout. Finishing a writer with no entries produces a valid, empty SST.
+    /// Writers created for a path sync the file to disk before returning unless
+    /// `sync_on_finish(false)` was set.
+    pub fn finish(mut self) -> Result<WriteStats> {
+        // An empty file still gets its header
+        self.write_header()?;
+        if let Some((key, value)) = self.pending.take() {
+            self.write_entry(&key, value.as_deref())?;
+        }
+        // Flush any remaining data in the current block (a no-op if it is empty)
+        self.flush_block()?;
+
+        // Write the bloom filter, if enabled and there are keys to filter
+        let bloom_offset = self.offset;
+        let mut filter = Vec::new();
+        if self.bloom_bits_per_key > 0 && !self.key_hashes.is_empty() {
+            filter = bloom::build(&self.key_hashes, self.bloom_bits_per_key);
+            self.writer.write_all(&filter)?;
+            self.offset += filter.len() as u64;
         }
+        let bloom_size = filter.len() as u64;
+
+        // Write the index partitions, if any, and the index block the footer points to
+        let index_start = self.offset;
+        let index_bytes = match self.index_partition_size {
+            Some(partition_size) => self.write_index_partitions(partition_size)?,
+            None => {
+                let mut bytes = vec![INDEX_TYPE_FLAT];
+                bytes.extend_from_slice(&(self.index.len() as u32).to_le_bytes());
+                for i in 0..self.index.len() {
+                    bytes.extend_from_slice(&self.index_entry_bytes(i));
+                }
+                bytes
+            }
+        };
+        let index_block_offset = self.offset;
         self.writer.write_all(&index_bytes)?;
         let index_block_size = index_bytes.len() as u64;
+        self.offset += index_block_size;
+
+        // Write the properties block
+        let properties_offset = self.offset;
+        let properties = Properties {
+            num_entries: self.num_entries,
+            min_key: self.first_key.clone(),
+            max_key: self.last_key,
+            total_key_bytes: self.total_key_bytes,
+            total_value_bytes: self.total_value_bytes,
+            compression: self.compression,
+            checksum: self.checksum,
+            // Only an order other than the default is recorded
+            comparator: Some(self.comparator.name().to_string())
+                .filter(|name| name != BytewiseComparator.name()),
+            user_metadata: self.user_metadata,
+        };
+        let properties_bytes = properties.to_bytes();
+        self.writer.write_all(&properties_bytes)?;
+        let properties_size = properties_bytes.len() as u64;
 
         // Write the footer
-        // Footer Format: [index_block_offset: u64][index_block_size: u64][magic_number: u64]
-        self.writer.write_all(&index_block_offset.to_le_bytes())?;
-        self.writer.write_all(&index_block_size.to_le_bytes())?;
-        self.writer.write_all(&0xDEADBEEFCAFEBABEu64.to_le_bytes())?; // Magic number
+        let footer = Footer {
+            index_offset: index_block_offset,
+            index_size: index_block_size,
+            bloom_offset,
+            bloom_size,
+            first_key: self.first_key,
+            num_entries: Some(self.num_entries),
+            properties_offset,
+            properties_size,
+            application_magic: self.application_magic,
+            ..Footer::default()
+        };
+        let mut footer_bytes = footer.to_bytes();
+        // The file checksum covers the rest of the footer, including the tail after it
+        let checksum_start = footer_bytes.len() - FILE_CHECKSUM_END as usize;
+        self.writer.hash.update(&footer_bytes[..checksum_start]);
+        self.writer.hash.update(&footer_bytes[checksum_start + 8..]);
+        let file_checksum = self.writer.hash.finish();
+        footer_bytes[checksum_start..checksum_start + 8].copy_from_slice(&file_checksum.to_le_bytes());
+        self.writer.inner.write_all(&footer_bytes)?;
 
         self.writer.flush()?;
-        Ok(())
+        if let Some(sync) = self.sync {
+            sync(&self.writer.inner)?;
+        }
+
+        // The sidecar is written even without a filter, so one left by an earlier file
+        // at the same path can't outlive it
+        if let Some(path) = &self.bloom_sidecar {
+            let mut sidecar = File::create(path)?;
+            sidecar.write_all(&bloom::sidecar_to_bytes(&filter, file_checksum))?;
+            if self.sync.is_some() {
+                sidecar.sync_all()?;
+            }
+        }
+
+        let data_bytes = self.index.iter().map(|entry| entry.block_size).sum();
+        Ok(WriteStats {
+            entries: self.num_entries,
+            data_blocks: self.index.len() as u64,
+            index_entries: self.index.len() as u64,
+            total_bytes: properties_offset + properties_size + footer_bytes.len() as u64,
+            data_bytes,
+            // Everything between the header and the bloom filter is either a data block or a blob
+            blob_bytes: bloom_offset - HEADER_SIZE - data_bytes,
+            bloom_bytes: bloom_size,
+            index_bytes: properties_offset - index_start,
+            properties_bytes: properties_size,
+        })
+    }
+
+    // Serialises the index entry of the block at position `i`. In a sparse index only the
+    // first block of each span records keys: the span's first key, and the index key of its
+    // last block. The other blocks in the span record empty keys.
+    fn index_entry_bytes(&self, i: usize) -> Vec<u8> {
+        let interval = self.index_sample_interval;
+        let entry = &self.index[i];
+        if interval == 1 {
+            return entry.to_bytes();
+        }
+        let span_last = (i + interval - 1).min(self.index.len() - 1);
+        let sampled = i.is_multiple_of(interval);
+        IndexEntry {
+            last_key: if sampled { self.index[span_last].last_key.clone() } else { Vec::new() },
+            block_offset: entry.block_offset,
+            block_size: entry.block_size,
+            cumulative_entries: entry.cumulative_entries,
+            first_key: if sampled { entry.first_key.clone() } else { Vec::new() },
+        }
+        .to_bytes()
+    }
+
+    // Writes the index as partitions of about `partition_size` bytes each, and returns the
+    // top-level index pointing to them. Each partition is a flat index of its blocks.
+    // Top-Level Index Format: [INDEX_TYPE_PARTITIONED: u8][num_partitions: u32]
+    //                         ([last_key_len: u32][last_key][partition_offset: u64][partition_size: u64]
+    //                          [cumulative_entries: u64][cumulative_blocks: u64])...
+    fn write_index_partitions(&mut self, partition_size: usize) -> Result<Vec<u8>> {
+        let mut top_level = Vec::new();
+        let mut num_partitions = 0u32;
+        let mut partition = Vec::new();
+        let mut partition_entries = 0u32;
+        for i in 0..self.index.len() {
+            partition.extend_from_slice(&self.index_entry_bytes(i));
+            partition_entries += 1;
+
+            // Close the partition once it is full, and after the last block. Spans of a
+            // sparse index are never split, as each partition is parsed on its own.
+            let span_ends = (i + 1).is_multiple_of(self.index_sample_interval);
+            if (partition.len() < partition_size || !span_ends) && i + 1 < self.index.len() {
+                continue;
+            }
+            let entry = &self.index[i];
+            let partition_offset = self.offset;
+            self.writer.write_all(&partition_entries.to_le_bytes())?;
+            self.writer.write_all(&partition)?;
+            let size = 4 + partition.len() as u64;
+            self.offset += size;
+
+            top_level.extend_from_slice(&(entry.last_key.len() as u32).to_le_bytes());
+            top_level.extend_from_slice(&entry.last_key);
+            top_level.extend_from_slice(&partition_offset.to_le_bytes());
+            top_level.extend_from_slice(&size.to_le_bytes());
+            top_level.extend_from_slice(&entry.cumulative_entries.to_le_bytes());
+            top_level.extend_from_slice(&(i as u64 + 1).to_le_bytes());
+            num_partitions += 1;
+            partition.clear();
+            partition_entries = 0;
+        }
+
+        let mut bytes = vec![INDEX_TYPE_PARTITIONED];
+        bytes.extend_from_slice(&num_partitions.to_le_bytes());
+        bytes.extend_from_slice(&top_level);
+        Ok(bytes)
+    }
+}
+
+/// Wraps an `SstWriter` so that it is finished when dropped, in case `finish` is never
+/// called, leaving a readable file rather than one with no footer. It derefs to the
+/// writer, so entries are added through it as usual.
+///
+/// `Drop` can't return an error, so an error finishing the file on drop is passed to
+/// the handler set with `on_drop_error`, or printed to stderr if there is none. Call
+/// `finish` to handle the error yourself, or `into_inner` to take the writer back
+/// without finishing it.
+pub struct ScopedSstWriter<W: Write = BufWriter<File>> {
+    // Taken out when the writer is finished or unwrapped
+    writer: Option<SstWriter<W>>,
+    on_drop_error: Option<Box<dyn FnOnce(SstError) + Send>>,
+}
+
+impl<W: Write> ScopedSstWriter<W> {
+    /// Wraps `writer` so it is finished when dropped.
+    pub fn new(writer: SstWriter<W>) -> Self {
+        ScopedSstWriter {
+            writer: Some(writer),
+            on_drop_error: None,
+        }
+    }
+
+    /// Sets what to do with an error finishing the file on drop, instead of printing it.
+    pub fn on_drop_error(mut self, handler: impl FnOnce(SstError) + Send + 'static) -> Self {
+        self.on_drop_error = Some(Box::new(handler));
+        self
+    }
+
+    /// Finishes the file like `SstWriter::finish`, returning its error rather than
+    /// passing it to the drop handler.
+    pub fn finish(mut self) -> Result<WriteStats> {
+        self.writer.take().expect("writer is only taken when consumed").finish()
+    }
+
+    /// Returns the wrapped writer without finishing it, so dropping it no longer
+    /// finishes the file.
+    pub fn into_inner(mut self) -> SstWriter<W> {
+        self.writer.take().expect("writer is only taken when consumed")
+    }
+}
+
+impl<W: Write> Deref for ScopedSstWriter<W> {
+    type Target = SstWriter<W>;
+
+    fn deref(&self) -> &SstWriter<W> {
+        self.writer.as_ref().expect("writer is only taken when consumed")
+    }
+}
+
+impl<W: Write> DerefMut for ScopedSstWriter<W> {
+    fn deref_mut(&mut self) -> &mut SstWriter<W> {
+        self.writer.as_mut().expect("writer is only taken when consumed")
+    }
+}
+
+impl<W: Write> Drop for ScopedSstWriter<W> {
+    fn drop(&mut self) {
+        let Some(writer) = self.writer.take() else {
+            return;
+        };
+        if let Err(e) = writer.finish() {
+            match self.on_drop_error.take() {
+                Some(handler) => handler(e),
+                None => eprintln!("failed to finish SST on drop: {}", e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Bound;
+
+    use super::*;
+    use crate::reader::Entry;
+    use crate::test_util::{FRUIT, TempPath, strings};
+
+    #[test]
+    fn empty_file_lifecycle() {
+        let path = TempPath::new("empty.sst");
+        let stats = SstWriter::new(&path).unwrap().finish().unwrap();
+        assert_eq!((stats.entries, stats.data_blocks, stats.index_entries), (0, 0, 0));
+        assert_eq!((stats.data_bytes, stats.bloom_bytes), (0, 0));
+        assert_eq!(stats.total_bytes, fs::metadata(&path).unwrap().len());
+
+        for reader in [SstReader::open(&path).unwrap(), SstReader::open_lazy(&path).unwrap()] {
+            assert!(reader.is_empty());
+            assert_eq!(reader.len(), Some(0));
+            assert_eq!((reader.first_key(), reader.last_key()), (None, None));
+            assert_eq!(reader.get(b"key").unwrap(), None);
+            assert!(reader.index_entries().unwrap().is_empty());
+            assert_eq!(reader.iter().count(), 0);
+            assert_eq!(reader.range(Bound::Included(&b"a"[..]), Bound::Unbounded).count(), 0);
+            reader.verify().unwrap();
+            reader.verify_file_checksum().unwrap();
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn blocks_are_cut_before_restart_offsets_overflow() {
+        let mut bytes = Vec::new();
+        let mut writer = SstWriterBuilder::new()
+            .block_size(usize::MAX)
+            .restart_interval(1)
+            .build_with_writer(&mut bytes);
+        writer.add(b"a", b"1").unwrap();
+        // Pretend the block's entries have grown to just under 4 GiB, so the next entry
+        // starts at the last offset a restart point can hold
+        writer.current_block.entries_size = u32::MAX as usize - 4;
+        writer.add(b"b", b"2").unwrap();
+        assert!(writer.index.is_empty());
+        assert_eq!(writer.current_block.restarts, [4, u32::MAX]);
+
+        // The next entry would start past it, so it goes into a new block
+        writer.add(b"c", b"3").unwrap();
+        assert_eq!(writer.index.len(), 1);
+        assert_eq!(writer.current_block.restarts, [4]);
+        assert_eq!(writer.current_block.entries.len(), 1);
+    }
+
+    #[test]
+    fn small_block_sizes_split_the_entries_into_several_blocks() {
+        let mut bytes = Vec::new();
+        let mut writer = SstWriterBuilder::new().block_size(64).build_with_writer(&mut bytes);
+        for (key, value) in FRUIT {
+            writer.add(key.as_bytes(), value.as_bytes()).unwrap();
+        }
+        let stats = writer.finish().unwrap();
+        let reader = SstReader::from_bytes(bytes).unwrap();
+        assert!(stats.data_blocks > 1, "{} blocks", stats.data_blocks);
+        assert_eq!(reader.num_blocks() as u64, stats.data_blocks);
+
+        let expected: Vec<_> = FRUIT.iter().map(|&(key, value)| (key.to_string(), value.to_string())).collect();
+        assert_eq!(strings(reader.iter()), expected);
+        for (key, value) in FRUIT {
+            assert_eq!(reader.get(key.as_bytes()).unwrap().as_deref(), Some(value.as_bytes()));
+        }
+    }
+
+    #[test]
+    fn values_over_the_blob_threshold_are_stored_outside_the_blocks() {
+        let path = TempPath::new("blobs.sst");
+        let entries: Vec<(Vec<u8>, Vec<u8>)> = (0..300u32)
+            .map(|i| {
+                let len = match i % 4 {
+                    0 => 10,
+                    1 => 99,
+                    2 => 100,
+                    _ => 5000 + i as usize,
+                };
+                (format!("key{:05}", i).into_bytes(), vec![i as u8; len])
+            })
+            .collect();
+        let mut writer = SstWriterBuilder::new().block_size(1024).blob_threshold(100).build(&path).unwrap();
+        for (key, value) in &entries {
+            writer.add(key, value).unwrap();
+        }
+        let stats = writer.finish().unwrap();
+        let large: u64 = entries.iter().map(|(_, value)| value.len() as u64).filter(|&len| len >= 100).sum();
+        assert_eq!(stats.blob_bytes, large);
+        // Blocks only hold pointers to the large values
+        assert!(stats.data_bytes < large / 10, "{} bytes of blocks", stats.data_bytes);
+
+        for reader in [SstReader::open(&path).unwrap(), SstReader::open_mmap(&path).unwrap()] {
+            reader.verify().unwrap();
+            assert_eq!(reader.iter().map(Result::unwrap).collect::<Vec<_>>(), entries);
+            for (key, value) in &entries {
+                assert_eq!(reader.get(key).unwrap().as_ref(), Some(value));
+            }
+        }
+    }
+
+    #[test]
+    fn empty_values_round_trip_and_empty_keys_are_rejected() {
+        let mut bytes = Vec::new();
+        let mut writer = SstWriter::with_writer(&mut bytes);
+        assert!(matches!(writer.add(b"", b"value"), Err(SstError::EmptyKey)));
+        assert!(matches!(writer.delete(b""), Err(SstError::EmptyKey)));
+        writer.add(b"a", b"").unwrap();
+        writer.add(b"b", b"value").unwrap();
+        writer.add(b"c", b"").unwrap();
+        assert!(matches!(writer.add(b"", b""), Err(SstError::EmptyKey)));
+        writer.finish().unwrap();
+
+        let reader = SstReader::from_bytes(bytes).unwrap();
+        reader.verify().unwrap();
+        assert_eq!(reader.get(b"a").unwrap(), Some(Vec::new()));
+        assert_eq!(reader.get_entry(b"c").unwrap(), Some(Entry::Value(Vec::new())));
+        assert!(reader.contains_key(b"c").unwrap());
+        assert_eq!(reader.get(b"").unwrap(), None);
+        assert_eq!(strings(reader.iter()), [("a", ""), ("b", "value"), ("c", "")].map(|(k, v)| (k.into(), v.into())));
+    }
+
+    #[test]
+    fn repeated_keys_are_rejected_unless_deduplicated() {
+        let mut bytes = Vec::new();
+        let mut writer = SstWriter::with_writer(&mut bytes);
+        writer.add(b"a", b"1").unwrap();
+        assert!(matches!(writer.add(b"a", b"2"), Err(SstError::DuplicateKey)));
+        assert!(matches!(writer.delete(b"a"), Err(SstError::DuplicateKey)));
+        writer.add(b"b", b"1").unwrap();
+        writer.finish().unwrap();
+        let reader = SstReader::from_bytes(bytes).unwrap();
+        assert_eq!(strings(reader.iter()), [("a", "1"), ("b", "1")].map(|(k, v)| (k.into(), v.into())));
+
+        // With dedup_keys the last entry for a key wins, tombstones included
+        let mut bytes = Vec::new();
+        let mut writer = SstWriterBuilder::new().block_size(64).dedup_keys(true).build_with_writer(&mut bytes);
+        for i in 0..100u32 {
+            let key = format!("key{:05}", i);
+            writer.add(key.as_bytes(), b"first").unwrap();
+            match i % 3 {
+                0 => writer.delete(key.as_bytes()).unwrap(),
+                1 => writer.add(key.as_bytes(), b"second").unwrap(),
+                _ => {
+                    writer.delete(key.as_bytes()).unwrap();
+                    writer.add(key.as_bytes(), b"third").unwrap();
+                }
+            }
+        }
+        assert!(matches!(writer.add(b"key00050", b"late"), Err(SstError::KeysOutOfOrder)));
+        let stats = writer.finish().unwrap();
+        assert_eq!(stats.entries, 100);
+        let reader = SstReader::from_bytes(bytes).unwrap();
+        assert!(reader.num_blocks() > 5, "{} blocks", reader.num_blocks());
+        reader.verify().unwrap();
+        for i in 0..100u32 {
+            let key = format!("key{:05}", i);
+            let expected = match i % 3 {
+                0 => Entry::Tombstone,
+                1 => Entry::Value(b"second".to_vec()),
+                _ => Entry::Value(b"third".to_vec()),
+            };
+            assert_eq!(reader.get_entry(key.as_bytes()).unwrap(), Some(expected), "{}", key);
+        }
+    }
+
+    fn checkpoint_key(i: u32) -> Vec<u8> {
+        format!("key{:05}", i).into_bytes()
+    }
+
+    #[test]
+    fn resumed_writers_continue_from_the_checkpoint() {
+        let (path, state) = (TempPath::new("resume.sst"), TempPath::new("resume.state"));
+        let options = SstWriterBuilder::new().block_size(256).bloom_bits_per_key(10);
+        let mut writer = options.clone().build(&path).unwrap();
+        for i in 0..500 {
+            writer.add(&checkpoint_key(i), &checkpoint_key(i)).unwrap();
+        }
+        writer.checkpoint(&state).unwrap();
+        // Entries added after the checkpoint are lost when the writer is dropped unfinished
+        for i in 500..700 {
+            writer.add(&checkpoint_key(i), b"lost").unwrap();
+        }
+        drop(writer);
+
+        let mut writer = options.clone().resume(&path, &state).unwrap();
+        assert!(matches!(writer.add(&checkpoint_key(100), b"value"), Err(SstError::KeysOutOfOrder)));
+        assert!(matches!(writer.add(&checkpoint_key(499), b"value"), Err(SstError::DuplicateKey)));
+        for i in 500..1000 {
+            writer.add(&checkpoint_key(i), &checkpoint_key(i)).unwrap();
+        }
+        let stats = writer.finish().unwrap();
+        assert_eq!(stats.entries, 1000);
+        assert_eq!(stats.total_bytes, fs::metadata(&path).unwrap().len());
+
+        let reader = SstReader::open(&path).unwrap();
+        reader.verify().unwrap();
+        reader.verify_file_checksum().unwrap();
+        let expected: Vec<_> = (0..1000).map(|i| (checkpoint_key(i), checkpoint_key(i))).collect();
+        assert_eq!(reader.iter().map(Result::unwrap).collect::<Vec<_>>(), expected);
+        assert_eq!(reader.len(), Some(1000));
+        assert_eq!(reader.properties().unwrap().min_key.as_deref(), Some(&checkpoint_key(0)[..]));
+        assert_eq!(reader.get(&checkpoint_key(250)).unwrap(), Some(checkpoint_key(250)));
+    }
+
+    #[test]
+    fn checkpoints_that_dont_match_the_file_are_refused() {
+        let (path, state) = (TempPath::new("mismatch.sst"), TempPath::new("mismatch.state"));
+        let mut writer = SstWriterBuilder::new().block_size(256).build(&path).unwrap();
+        for i in 0..500 {
+            writer.add(&checkpoint_key(i), b"value").unwrap();
+        }
+        writer.checkpoint(&state).unwrap();
+        writer.finish().unwrap();
+        let original = fs::read(&path).unwrap();
+        let resume = || SstWriterBuilder::new().resume(&path, &state);
+
+        // The file has been cut short of the checkpoint
+        fs::write(&path, &original[..100]).unwrap();
+        assert!(matches!(resume(), Err(SstError::CorruptCheckpoint(_))));
+        // A different file at least as long as the checkpointed one is left as it is
+        let mut other = original.clone();
+        other[HEADER_SIZE as usize + 10] ^= 1;
+        fs::write(&path, &other).unwrap();
+        assert!(matches!(resume(), Err(SstError::CorruptCheckpoint(_))));
+        assert_eq!(fs::read(&path).unwrap(), other);
+        // A state file that has been cut short
+        fs::write(&path, &original).unwrap();
+        let saved = fs::read(&state).unwrap();
+        fs::write(&state, &saved[..saved.len() - 1]).unwrap();
+        assert!(matches!(resume(), Err(SstError::CorruptCheckpoint(_))));
+
+        fs::write(&state, &saved).unwrap();
+        resume().unwrap().finish().unwrap();
+        assert_eq!(SstReader::open(&path).unwrap().iter().count(), 500);
+    }
+
+    #[test]
+    fn larger_restart_intervals_give_smaller_blocks_that_read_the_same() {
+        let mut sizes = Vec::new();
+        for restart_interval in [1, 2, 4, 16, 64] {
+            let mut bytes = Vec::new();
+            let mut writer = SstWriterBuilder::new()
+                .restart_interval(restart_interval)
+                .bloom_bits_per_key(0)
+                .build_with_writer(&mut bytes);
+            for i in 0..2000u32 {
+                writer.add(format!("user/profile/{:08}", i).as_bytes(), b"value").unwrap();
+            }
+            let stats = writer.finish().unwrap();
+            sizes.push(stats.data_bytes);
+
+            let reader = SstReader::from_bytes(bytes).unwrap();
+            reader.verify().unwrap();
+            for i in 0..2001u32 {
+                let key = format!("user/profile/{:08}", i);
+                assert_eq!(reader.get(key.as_bytes()).unwrap().is_some(), i < 2000, "{}", key);
+            }
+            assert_eq!(reader.iter().count(), 2000);
+        }
+        // Fewer restart points mean fewer keys stored in full, and fewer offsets
+        assert!(sizes.windows(2).all(|pair| pair[0] > pair[1]), "{:?}", sizes);
+        assert!(sizes[0] > sizes[4] * 3 / 2, "{:?}", sizes);
+    }
+
+    #[test]
+    fn bytes_written_counts_the_blocks_and_blobs_flushed_so_far() {
+        let mut bytes = Vec::new();
+        let mut writer = SstWriterBuilder::new()
+            .max_entries_per_block(10)
+            .blob_threshold(100)
+            .build_with_writer(&mut bytes);
+        assert_eq!(writer.bytes_written(), 0);
+        let mut blob_bytes = 0;
+        for i in 0..300u32 {
+            let value = vec![b'v'; if i % 7 == 0 { 150 } else { 20 }];
+            writer.add(format!("key{:05}", i).as_bytes(), &value).unwrap();
+            if value.len() >= 100 {
+                blob_bytes += value.len() as u64;
+            }
+            let data_bytes: u64 = writer.index.iter().map(|entry| entry.block_size).sum();
+            assert_eq!(writer.bytes_written(), HEADER_SIZE + data_bytes + blob_bytes, "entry {}", i);
+        }
+        // The last add filled a block, so everything before the metadata has been written
+        let written = writer.bytes_written();
+        let stats = writer.finish().unwrap();
+        assert_eq!(written, HEADER_SIZE + stats.data_bytes + stats.blob_bytes);
+
+        let footer = Footer::read_from(&mut io::Cursor::new(&bytes)).unwrap();
+        assert_eq!(written, footer.bloom_offset);
+        let metadata = stats.bloom_bytes + stats.index_bytes + stats.properties_bytes + footer.len;
+        assert_eq!(written, bytes.len() as u64 - metadata);
+    }
+
+    #[test]
+    fn blocks_hold_at_most_the_entry_cap() {
+        for (cap, expected_cap) in [(0, 1), (1, 1), (3, 3), (7, 7)] {
+            let mut bytes = Vec::new();
+            let mut writer = SstWriterBuilder::new().max_entries_per_block(cap).build_with_writer(&mut bytes);
+            for i in 0..100u32 {
+                writer.add(format!("key{:05}", i).as_bytes(), b"value").unwrap();
+            }
+            let stats = writer.finish().unwrap();
+            assert_eq!(stats.data_blocks, 100u64.div_ceil(expected_cap as u64), "cap {}", cap);
+
+            let reader = SstReader::from_bytes(bytes).unwrap();
+            let sizes: Vec<_> = reader.blocks().map(|block| block.unwrap().entries().unwrap().len()).collect();
+            // The byte size is far from reached, so every block but the last is full
+            let (last, full) = sizes.split_last().unwrap();
+            assert!(full.iter().all(|&len| len == expected_cap), "cap {}: {:?}", cap, sizes);
+            assert!((1..=expected_cap).contains(last), "cap {}: {:?}", cap, sizes);
+            assert_eq!(reader.iter().count(), 100);
+        }
+
+        // Whichever limit is reached first cuts the block
+        let mut bytes = Vec::new();
+        let options = SstWriterBuilder::new().block_size(128).max_entries_per_block(50);
+        let mut writer = options.build_with_writer(&mut bytes);
+        for i in 0..100u32 {
+            writer.add(format!("key{:05}", i).as_bytes(), &[b'v'; 40]).unwrap();
+        }
+        writer.finish().unwrap();
+        let reader = SstReader::from_bytes(bytes).unwrap();
+        assert!(reader.blocks().all(|block| block.unwrap().entries().unwrap().len() < 5));
+    }
+
+    #[test]
+    fn block_size_estimates_match_the_serialized_blocks() {
+        // Keys and values long enough for multi-byte varints, with prefixes to share
+        let entry = |i: usize| {
+            let key = format!("{:05}/{}", i, "k".repeat(i % 200)).into_bytes();
+            (key, vec![b'v'; i * 37 % 20_000])
+        };
+        for restart_interval in [1, 3, 16] {
+            for hash_index in [false, true] {
+                let mut block = DataBlock::new(restart_interval);
+                for i in 0..300 {
+                    let (key, value) = entry(i);
+                    let value_type = if i % 5 == 0 { VALUE_TYPE_TOMBSTONE } else { VALUE_TYPE_VALUE };
+                    block.add(&key, value_type, &value);
+                    let len = block.to_bytes(hash_index, &BytewiseComparator).len();
+                    assert_eq!(block.serialized_len(hash_index), len, "entry {}, interval {}", i, restart_interval);
+                }
+            }
+        }
+
+        // The size blocks are cut at is the size they take in the file when uncompressed
+        for checksum in [Checksum::None, Checksum::Crc32, Checksum::XxHash64] {
+            let mut writer = SstWriterBuilder::new()
+                .block_size(usize::MAX)
+                .checksum(checksum)
+                .hash_index(true)
+                .build_with_writer(Vec::new());
+            for i in 0..300 {
+                let (key, value) = entry(i);
+                writer.add(&key, &value).unwrap();
+                if i % 30 == 29 {
+                    let estimate = writer.current_block_size();
+                    writer.flush_block().unwrap();
+                    assert_eq!(writer.index.last().unwrap().block_size, estimate as u64, "entry {}", i);
+                }
+            }
+        }
+    }
+
+    // A sink whose writes all fail, as on a full disk
+    struct BrokenWriter;
+
+    impl Write for BrokenWriter {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::other("disk full"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn dropped_scoped_writers_leave_a_readable_file() {
+        let path = TempPath::new("scoped.sst");
+        {
+            let mut writer = ScopedSstWriter::new(SstWriterBuilder::new().block_size(64).build(&path).unwrap());
+            for (key, value) in FRUIT {
+                writer.add(key.as_bytes(), value.as_bytes()).unwrap();
+            }
+            writer.delete(b"zucchini").unwrap();
+        }
+        let reader = SstReader::open(&path).unwrap();
+        reader.verify().unwrap();
+        assert_eq!(strings(reader.iter()), FRUIT.map(|(key, value)| (key.to_string(), value.to_string())));
+        assert_eq!(reader.get_entry(b"zucchini").unwrap(), Some(Entry::Tombstone));
+
+        // Unwrapped writers are left unfinished, so the file has no footer
+        let mut writer = ScopedSstWriter::new(SstWriter::new(&path).unwrap());
+        writer.add(b"key", b"value").unwrap();
+        drop(writer.into_inner());
+        assert!(SstReader::open(&path).is_err());
+
+        // Errors finishing on drop go to the handler
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let writer = ScopedSstWriter::new(SstWriterBuilder::new().build_with_writer(BrokenWriter))
+            .on_drop_error(move |e| sender.send(e.to_string()).unwrap());
+        drop(writer);
+        assert!(receiver.recv().unwrap().contains("disk full"));
+    }
+
+    #[test]
+    fn the_last_operation_on_a_key_in_a_write_batch_wins() {
+        let path = TempPath::new("write-batch.sst");
+        let batch = [
+            (Op::Put, "cherry", "red"),
+            (Op::Put, "apple", "green"),
+            (Op::Delete, "banana", ""),
+            (Op::Put, "banana", "yellow"),
+            (Op::Put, "date", "brown"),
+            (Op::Put, "apple", "red"),
+            (Op::Delete, "cherry", "ignored"),
+            (Op::Delete, "elderberry", ""),
+        ];
+        let stats = SstWriter::from_write_batch(&path, &batch).unwrap();
+        assert_eq!(stats.entries, 5);
+
+        let reader = SstReader::open(&path).unwrap();
+        reader.verify().unwrap();
+        // A put and a later delete, out of key order, leave a tombstone
+        assert_eq!(reader.get_entry(b"cherry").unwrap(), Some(Entry::Tombstone));
+        assert_eq!(reader.get(b"cherry").unwrap(), None);
+        // A delete and a later put leave the value
+        assert_eq!(reader.get(b"banana").unwrap(), Some(b"yellow".to_vec()));
+        assert_eq!(reader.get(b"apple").unwrap(), Some(b"red".to_vec()));
+        assert_eq!(reader.get_entry(b"elderberry").unwrap(), Some(Entry::Tombstone));
+        let expected = [("apple", "red"), ("banana", "yellow"), ("date", "brown")];
+        assert_eq!(strings(reader.iter()), expected.map(|(k, v)| (k.into(), v.into())));
+    }
+}
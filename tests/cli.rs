@@ -0,0 +1,161 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/fruit.sst");
+const FIXTURE_TSV: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/fruit.tsv");
+
+// Runs the binary with `args`
+fn sst(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_sst")).args(args).output().unwrap()
+}
+
+fn stdout(output: &Output) -> &str {
+    std::str::from_utf8(&output.stdout).unwrap()
+}
+
+fn stderr(output: &Output) -> &str {
+    std::str::from_utf8(&output.stderr).unwrap()
+}
+
+// A path in the temp directory that is removed on drop
+struct TempPath(PathBuf);
+
+impl TempPath {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("sst-cli-{}-{}", std::process::id(), name));
+        let _ = fs::remove_file(&path);
+        TempPath(path)
+    }
+
+    fn as_str(&self) -> &str {
+        self.0.to_str().unwrap()
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+#[test]
+fn get_prints_the_value() {
+    let output = sst(&["get", FIXTURE, "banana"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "An elongated, edible fruit.\n");
+}
+
+#[test]
+fn get_of_a_missing_key_fails() {
+    let output = sst(&["get", FIXTURE, "fig"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "");
+    assert_eq!(stderr(&output), "key not found\n");
+}
+
+#[test]
+fn encoded_keys_are_accepted_anywhere_after_get() {
+    for args in [
+        ["get", "--hex", FIXTURE, "feff"],
+        ["get", FIXTURE, "--hex", "feff"],
+        ["get", FIXTURE, "feff", "--hex"],
+        ["get", "--base64", FIXTURE, "/v8="],
+        ["get", FIXTURE, "/v8=", "--base64"],
+    ] {
+        let output = sst(&args);
+        assert!(output.status.success(), "{:?}: {}", args, stderr(&output));
+        assert_eq!(stdout(&output), "A key that is not UTF-8.\n", "{:?}", args);
+    }
+    // Printable keys can be encoded too
+    let output = sst(&["get", "--hex", FIXTURE, "6170706c65"]);
+    assert_eq!(stdout(&output), "A fruit that grows on trees.\n");
+}
+
+#[test]
+fn invalid_arguments_print_the_usage() {
+    for args in [
+        &["get", FIXTURE][..],
+        &["get", "--hex", FIXTURE, "fef"],
+        &["get", "--base64", FIXTURE, "!!"],
+        &["get", "--hex", "--base64", FIXTURE, "feff"],
+        &["dump"],
+        &["dump", FIXTURE, "extra"],
+        &["frobnicate", FIXTURE],
+        &[],
+    ] {
+        let output = sst(args);
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        assert!(stderr(&output).contains("Usage: sst"), "{:?}: {}", args, stderr(&output));
+    }
+    let output = sst(&["get", "--hex", FIXTURE, "fef"]);
+    assert!(stderr(&output).contains("fef is not valid hex"), "{}", stderr(&output));
+    assert!(stderr(&output).contains("Usage: sst get"), "{}", stderr(&output));
+}
+
+#[test]
+fn help_describes_every_subcommand() {
+    let output = sst(&["--help"]);
+    assert!(output.status.success());
+    for command in ["get", "dump", "verify", "build", "info"] {
+        assert!(stdout(&output).contains(command), "{}", command);
+        let output = sst(&[command, "--help"]);
+        assert!(output.status.success(), "{}", command);
+        assert!(stdout(&output).contains(&format!("Usage: sst {}", command)), "{}", stdout(&output));
+    }
+    assert!(stdout(&sst(&["get", "--help"])).contains("--base64"));
+}
+
+#[test]
+fn dump_prints_every_entry() {
+    let output = sst(&["dump", FIXTURE]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let dump = stdout(&output);
+    assert_eq!(dump.lines().count(), 8);
+    assert!(dump.contains(r#"{"key": "YXBwbGU=", "value": "QSBmcnVpdCB0aGF0IGdyb3dzIG9uIHRyZWVzLg=="}"#));
+    assert!(dump.contains(r#"{"key": "/v8=", "value": "QSBrZXkgdGhhdCBpcyBub3QgVVRGLTgu"}"#));
+}
+
+#[test]
+fn info_prints_the_properties() {
+    let output = sst(&["info", FIXTURE]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let info = stdout(&output);
+    for line in [
+        "format version:    19",
+        "entries:           6",
+        "first key:         apple",
+        r"last key:          \xfe\xff",
+    ] {
+        assert!(info.lines().any(|l| l == line), "{}", info);
+    }
+}
+
+#[test]
+fn verify_checks_the_file() {
+    let output = sst(&["verify", FIXTURE]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "OK\n");
+
+    // Flip a byte of the data block, which starts after the 8-byte header
+    let corrupt = TempPath::new("corrupt.sst");
+    let mut bytes = fs::read(FIXTURE).unwrap();
+    bytes[20] ^= 0x01;
+    fs::write(&corrupt.0, bytes).unwrap();
+    let output = sst(&["verify", corrupt.as_str()]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).starts_with("error: "), "{}", stderr(&output));
+}
+
+#[test]
+fn build_writes_what_the_fixture_holds() {
+    let output_path = TempPath::new("built.sst");
+    let output = sst(&["build", FIXTURE_TSV, output_path.as_str()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output).lines().next().unwrap().split(" in ").next(), Some("Wrote 6 entries"));
+    assert_eq!(stdout(&sst(&["dump", output_path.as_str()])), stdout(&sst(&["dump", FIXTURE])));
+
+    let output = sst(&["build", "missing.tsv", output_path.as_str()]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(!Path::new("missing.tsv").exists());
+}